@@ -1,5 +1,2032 @@
+use crate::coins::{CoinView, UndoCoins};
+use crate::layout;
+use crate::names::{NameUndo, NameView};
+use crate::orphans::OrphanPool;
+use crate::state::ChainState;
+use crate::verify::{check_block, check_context, check_inputs, RejectCode, VerifyError};
+use crate::versionbits::{has_bit, DeploymentState, StateCache, ThresholdState};
+use crate::{ChainEntry, ChainError, ChainEvent, Result};
+use extended_primitives::{Buffer, Hash};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use handshake_encoding::{Decodable, Encodable};
+use handshake_primitives::{Block, BlockHeader, Genesis, NameState};
+use handshake_protocol::consensus::{
+    get_median_time, get_next_bits, MAX_FUTURE_BLOCK_TIME, VERIFY_COVENANTS_HARDENED,
+    VERSION_TOP_BITS,
+};
+use handshake_protocol::deployment::Deployment;
 use handshake_protocol::network::Network;
+use handshake_protocol::time_data::TimeData;
+use handshake_store::{
+    Batch, FileStore, FileType, KvRead, KvStore, MemoryStore, Migrator, StoreError, Tree,
+};
+use handshake_types::NameHash;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 pub struct Chain {
     network: Network,
+    //Blocks, undo data, coins and the name tree. See `layout` for the keys.
+    db: Arc<dyn KvStore>,
+    //Flat files for blocks and undo coins. Without them, both are kept in `db`.
+    files: Option<FileStore>,
+    //Every entry we know of, including those on side chains. Mirrors the entries in the store.
+    entries: HashMap<Hash, ChainEntry>,
+    //Hashes of the best chain, indexed by height. Mirrors the height index in the store.
+    heights: Vec<Hash>,
+    subscribers: Vec<UnboundedSender<ChainEvent>>,
+    //Network-adjusted time, fed by our peers.
+    time: TimeData,
+    //Unspent outputs as of the tip.
+    coins: CoinView,
+    //Name states as of the tip, moved along by the covenants of each block like the coin view.
+    tree: Tree,
+    //The last committed tree root, which the next block's header must commit to.
+    tree_root: Hash,
+    //Height the tree root was committed at.
+    commit_height: u32,
+    //How many blocks below the tip to keep, when pruning.
+    prune: Option<u32>,
+    //Main chain blocks below this height have had their data pruned.
+    pruned: u32,
+    //Blocks waiting on a parent we haven't seen.
+    orphans: OrphanPool,
+    //Blocks stored before the body of some block on their branch, keyed by their parent. They
+    //connect once the branch is complete.
+    pending: HashMap<Hash, Vec<Hash>>,
+    //Deployment states at the end of each window we have evaluated.
+    state_cache: StateCache,
+    //Verification flags in force for the tip.
+    state: DeploymentState,
+}
+
+impl Chain {
+    /// Creates a chain holding only the network's genesis block, kept in memory.
+    pub fn new(network: Network) -> Self {
+        Chain::with_store(network, Arc::new(MemoryStore::new()))
+            .expect("memory store writes do not fail")
+    }
+
+    /// Opens the chain kept in `db`, starting it from the network's genesis block if the store is
+    /// empty. Stores in an older layout are migrated first. A chain that was cut off part way
+    /// through a block picks up from the last block it fully wrote.
+    pub fn with_store(network: Network, db: Arc<dyn KvStore>) -> Result<Self> {
+        Chain::create(network, db, None)
+    }
+
+    /// Like `with_store`, but blocks and undo coins are written to `files`, which should index
+    /// into the same store.
+    pub fn with_files(network: Network, db: Arc<dyn KvStore>, files: FileStore) -> Result<Self> {
+        Chain::create(network, db, Some(files))
+    }
+
+    fn create(network: Network, db: Arc<dyn KvStore>, files: Option<FileStore>) -> Result<Self> {
+        Migrator::new(layout::VERSION, Vec::new()).run(db.as_ref())?;
+
+        let mut chain = Chain {
+            network,
+            db: db.clone(),
+            files,
+            entries: HashMap::new(),
+            heights: Vec::new(),
+            subscribers: Vec::new(),
+            time: TimeData::new(),
+            coins: CoinView::with_store(db.clone()),
+            tree: Tree::with_store(db),
+            tree_root: Hash::default(),
+            commit_height: 0,
+            prune: None,
+            pruned: 0,
+            orphans: OrphanPool::default(),
+            pending: HashMap::new(),
+            state_cache: StateCache::new(),
+            state: DeploymentState::new(),
+        };
+
+        chain.pruned = match chain.db.get(&layout::pruned())? {
+            Some(raw) if raw.len() == 4 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]),
+            Some(_) => return Err(corrupt("Bad pruned height")),
+            None => 0,
+        };
+
+        match chain.read::<ChainState>(&layout::state())? {
+            Some(state) => chain.recover(state)?,
+            None => {
+                let genesis = network.genesis_block();
+                let entry = ChainEntry::from_header(&genesis.header, None);
+
+                chain.save_entry(&entry)?;
+
+                let mut batch = Batch::new();
+                chain.write_data(FileType::Block, &entry, &genesis.encode(), &mut batch)?;
+                chain.db.write(batch)?;
+
+                chain.connect(&entry)?;
+            }
+        }
+
+        Ok(chain)
+    }
+
+    //Rebuilds the in-memory state from the store. Every block's writes land in one batch ending
+    //with the chain state, so the store holds exactly the blocks up to `state.tip`. Anything
+    //past that (a stray height record, a block stored but never connected) is rolled back or
+    //connected again here.
+    fn recover(&mut self, state: ChainState) -> Result<()> {
+        for (_, raw) in self.db.prefix(&[layout::ENTRY])? {
+            if let Some(entry) = decode_record::<ChainEntry>(Some(raw))? {
+                self.entries.insert(entry.hash, entry);
+            }
+        }
+
+        let tip = match self.entries.get(&state.tip) {
+            Some(tip) => tip.clone(),
+            None => return Err(corrupt("Chain tip has no entry")),
+        };
+
+        let mut heights = vec![Hash::default(); tip.height as usize + 1];
+        let mut entry = tip.clone();
+        loop {
+            heights[entry.height as usize] = entry.hash;
+
+            if entry.is_genesis() {
+                break;
+            }
+
+            entry = match self.entries.get(&entry.prev_block) {
+                Some(prev) => prev.clone(),
+                None => return Err(corrupt("Chain entry is missing its parent")),
+            };
+        }
+
+        if heights[0] != self.network.genesis_block().header.hash() {
+            return Err(corrupt("Store holds a chain for another network"));
+        }
+
+        //The height index should match the best chain exactly.
+        let mut batch = Batch::new();
+        let mut indexed = 0;
+        for (key, value) in self.db.prefix(&[layout::HEIGHT])? {
+            let height = layout::parse_height(&key)?;
+
+            match heights.get(height as usize) {
+                Some(hash) if hash.to_array()[..] == value[..] => indexed += 1,
+                _ => batch.delete(&key),
+            }
+        }
+        if indexed != heights.len() {
+            for (height, hash) in heights.iter().enumerate() {
+                batch.put(&layout::height(height as u32), &hash.to_array());
+            }
+        }
+        self.db.write(batch)?;
+
+        self.heights = heights;
+        self.tree.inject(&state.tree_root)?;
+        self.tree_root = state.tree_root;
+        self.commit_height = state.commit_height;
+
+        //Name changes since the last tree commit were never written, so play them again.
+        for height in state.commit_height + 1..=tip.height {
+            let hash = self.heights[height as usize];
+
+            let block = match self.get_block(&hash)? {
+                Some(block) => block,
+                None => return Err(corrupt("Missing block to replay names from")),
+            };
+
+            let names = self.connect_names(&block, height)?;
+            names.apply(&mut self.tree)?;
+        }
+
+        self.update_deployment_state();
+
+        //A block whose data was stored but which never got connected, either because we stopped
+        //before it did or because it is still waiting on the body of an earlier block.
+        let stored: Vec<ChainEntry> = self
+            .entries
+            .values()
+            .filter(|entry| !self.is_main_chain(entry))
+            .cloned()
+            .collect();
+
+        let mut best: Option<ChainEntry> = None;
+        for entry in stored {
+            if !self.has_block(&entry.hash)? {
+                continue;
+            }
+
+            if !self.can_connect(&entry)? {
+                self.pending
+                    .entry(entry.prev_block)
+                    .or_insert_with(Vec::new)
+                    .push(entry.hash);
+                continue;
+            }
+
+            if !self.is_better(&entry) {
+                continue;
+            }
+
+            if best
+                .as_ref()
+                .map_or(true, |best| entry.chainwork > best.chainwork)
+            {
+                best = Some(entry);
+            }
+        }
+
+        if let Some(best) = best {
+            //If it turns out to be invalid we stay on the tip we have.
+            match self.set_best_chain(&best) {
+                Ok(()) => self.prune_blocks()?,
+                Err(ChainError::Verify(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn db(&self) -> &Arc<dyn KvStore> {
+        &self.db
+    }
+
+    pub fn coins(&self) -> &CoinView {
+        &self.coins
+    }
+
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// Turns pruning on, keeping the data of the last `keep` blocks and dropping the rest, or off
+    /// with None. Blocks already pruned stay gone. At least a tree interval of blocks is always
+    /// kept, so that the names changed since the last tree commit can be replayed.
+    pub fn set_prune(&mut self, keep: Option<u32>) -> Result<()> {
+        self.prune = keep.map(|keep| keep.max(self.network.tree_interval()));
+        self.prune_blocks()
+    }
+
+    /// Whether some blocks may be missing, so we can't serve the whole chain to peers.
+    pub fn is_pruned(&self) -> bool {
+        self.prune.is_some() || self.pruned > 0
+    }
+
+    /// Main chain blocks below this height have been pruned.
+    pub fn pruned_height(&self) -> u32 {
+        self.pruned
+    }
+
+    /// The name tree root blocks building on the tip must commit to.
+    pub fn tree_root(&self) -> Hash {
+        self.tree_root
+    }
+
+    pub fn get_name_state(&self, name_hash: &NameHash) -> Result<Option<NameState>> {
+        let mut raw = match self.tree.get(name_hash)? {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+
+        let ns =
+            NameState::decode(&mut raw).map_err(|_| ChainError::CorruptNameState(*name_hash))?;
+
+        Ok(Some(ns))
+    }
+
+    /// Renewals commit to a recent main chain block, proving the owner still holds their key. The
+    /// block must be buried by the renewal maturity but no older than the renewal period.
+    pub fn verify_renewal(&self, hash: &Hash, height: u32) -> bool {
+        let maturity = self.network.renewal_maturity();
+
+        //No block could have matured yet.
+        if height < maturity {
+            return true;
+        }
+
+        let entry = match self.entries.get(hash) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        if !self.is_main_chain(entry) {
+            return false;
+        }
+
+        if entry.height > height - maturity {
+            return false;
+        }
+
+        entry.height + self.network.renewal_period() >= height
+    }
+
+    pub fn time_data(&self) -> &TimeData {
+        &self.time
+    }
+
+    /// Records a peer's reported time for network-adjusted time.
+    pub fn add_time_sample(&mut self, id: &str, time: u64) {
+        self.time.add(id, time);
+    }
+
+    /// Returns a stream of connect, disconnect and reorg events for the best chain.
+    pub fn subscribe(&mut self) -> UnboundedReceiver<ChainEvent> {
+        let (tx, rx) = unbounded();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Adds a header to the block index. The best chain only ever moves onto blocks we have in
+    /// full, so the tip stays where it is until the block arrives.
+    pub fn add_header(&mut self, header: BlockHeader) -> Result<ChainEntry> {
+        if self.entries.contains_key(&header.hash()) {
+            return Err(ChainError::DuplicateEntry);
+        }
+
+        let entry = self.index_header(&header)?;
+
+        self.resolve_orphans(&entry.hash);
+
+        Ok(entry)
+    }
+
+    /// Adds a full block. The block's header is indexed if we haven't seen it yet, and the chain
+    /// reorganizes if the block's branch now has the most work.
+    pub fn add_block(&mut self, block: Block) -> Result<ChainEntry> {
+        self.add_block_from(block, None)
+    }
+
+    /// Adds a block sent by `peer`. A block whose parent we don't know yet is held as an orphan
+    /// and added once the parent arrives, in which case this returns `ChainError::Orphan`.
+    pub fn add_block_from(&mut self, block: Block, peer: Option<&str>) -> Result<ChainEntry> {
+        let hash = block.header.hash();
+
+        if self.has_block(&hash)? || self.orphans.has(&hash) {
+            return Err(ChainError::DuplicateEntry);
+        }
+
+        if !self.entries.contains_key(&hash) && !self.entries.contains_key(&block.header.prev_block)
+        {
+            //Only hold on to orphans that could be valid.
+            if !block.header.verify_pow() {
+                return Err(ChainError::HighHash);
+            }
+            check_block(&block)?;
+
+            self.orphans.add(block, peer, self.time.now());
+            return Err(ChainError::Orphan);
+        }
+
+        let entry = self.store_block(block)?;
+
+        self.resolve_orphans(&entry.hash);
+
+        Ok(entry)
+    }
+
+    pub fn has_orphan(&self, hash: &Hash) -> bool {
+        self.orphans.has(hash)
+    }
+
+    /// The first block of the orphan chain `hash` belongs to. Its parent is what sync needs to
+    /// fetch next.
+    pub fn get_orphan_root(&self, hash: &Hash) -> Option<Hash> {
+        self.orphans.get_root(hash)
+    }
+
+    /// Forgets the orphans a peer sent, e.g. once it has been banned.
+    pub fn remove_peer_orphans(&mut self, peer: &str) -> usize {
+        self.orphans.remove_peer(peer)
+    }
+
+    //Adds the orphans waiting on `hash`, then the orphans waiting on those. Orphans that turn out
+    //to be invalid are dropped.
+    fn resolve_orphans(&mut self, hash: &Hash) {
+        let mut parents = vec![*hash];
+
+        while let Some(parent) = parents.pop() {
+            if let Some(orphan) = self.orphans.remove_by_prev(&parent) {
+                if let Ok(entry) = self.store_block(orphan.block) {
+                    parents.push(entry.hash);
+                }
+            }
+        }
+    }
+
+    //Indexes and verifies a block whose parent is known, then moves the best chain onto it if it
+    //has the most work. A block we lack earlier bodies for is stored and connected once they
+    //arrive, along with any blocks that were waiting on it.
+    pub(crate) fn store_block(&mut self, block: Block) -> Result<ChainEntry> {
+        let hash = block.header.hash();
+
+        let (entry, indexed) = match self.entries.get(&hash) {
+            Some(entry) => (entry.clone(), false),
+            None => (self.index_header(&block.header)?, true),
+        };
+
+        if let Some(prev) = self.get_previous(&entry) {
+            if let Err(e) = self.verify_block(&block, prev) {
+                //Don't keep a header we only learned about through an invalid block.
+                if indexed {
+                    self.remove_entry(&hash)?;
+                }
+                return Err(e);
+            }
+        }
+
+        let mut batch = Batch::new();
+        self.write_data(FileType::Block, &entry, &block.encode(), &mut batch)?;
+        self.db.write(batch)?;
+
+        if !self.can_connect(&entry)? {
+            self.pending
+                .entry(entry.prev_block)
+                .or_insert_with(Vec::new)
+                .push(hash);
+            return Ok(entry);
+        }
+
+        self.activate(&entry, indexed)?;
+        self.connect_pending(&hash);
+
+        Ok(entry)
+    }
+
+    //Moves the best chain onto a stored block if it has the most work. A block that fails to
+    //connect has its data dropped, and its entry too if `remove` is set.
+    fn activate(&mut self, entry: &ChainEntry, remove: bool) -> Result<()> {
+        if !self.is_better(entry) {
+            return Ok(());
+        }
+
+        if let Err(e) = self.set_best_chain(entry) {
+            let mut batch = Batch::new();
+            self.remove_data(FileType::Block, &entry.hash, &mut batch)?;
+            self.db.write(batch)?;
+            if remove {
+                self.remove_entry(&entry.hash)?;
+            }
+            return Err(e);
+        }
+
+        //Only once the block is in for good, so that failing here doesn't take it back out.
+        self.prune_blocks()
+    }
+
+    //Activates the blocks that were waiting on `hash`, then the blocks waiting on those. Their
+    //entries stay indexed even if they turn out to be invalid, since later headers may build on
+    //them.
+    fn connect_pending(&mut self, hash: &Hash) {
+        let mut parents = vec![*hash];
+
+        while let Some(parent) = parents.pop() {
+            for child in self.pending.remove(&parent).unwrap_or_default() {
+                let entry = match self.entries.get(&child) {
+                    Some(entry) => entry.clone(),
+                    None => continue,
+                };
+
+                if self.activate(&entry, false).is_ok() {
+                    parents.push(child);
+                }
+            }
+        }
+    }
+
+    //Whether we have the block for `entry` and every block between it and the best chain.
+    fn can_connect(&self, entry: &ChainEntry) -> Result<bool> {
+        for entry in self.ancestors(entry) {
+            if self.is_main_chain(entry) {
+                break;
+            }
+
+            if !self.has_block(&entry.hash)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Runs the consensus checks for a block that would follow `prev`. Genesis is never verified.
+    pub fn verify_block(&self, block: &Block, prev: &ChainEntry) -> Result<()> {
+        check_block(block)?;
+        check_context(block, prev.height + 1, self.get_median_time(prev))?;
+
+        Ok(())
+    }
+
+    pub fn get_block(&self, hash: &Hash) -> Result<Option<Block>> {
+        self.read_data(FileType::Block, hash)
+    }
+
+    pub fn has_block(&self, hash: &Hash) -> Result<bool> {
+        match self.files.as_ref() {
+            Some(files) => Ok(files.has(FileType::Block, hash)?),
+            None => Ok(self.db.has(&layout::data(FileType::Block, hash))?),
+        }
+    }
+
+    pub(crate) fn index_header(&mut self, header: &BlockHeader) -> Result<ChainEntry> {
+        let hash = header.hash();
+
+        if !header.verify_pow() {
+            return Err(ChainError::HighHash);
+        }
+
+        let prev = match self.entries.get(&header.prev_block) {
+            Some(prev) => prev,
+            None => return Err(ChainError::Orphan),
+        };
+
+        if header.bits != self.get_next_bits(prev, header.time) {
+            return Err(ChainError::BadDiffBits);
+        }
+
+        if header.time <= self.get_median_time(prev) {
+            return Err(ChainError::TimeTooOld);
+        }
+
+        if header.time > self.time.now() + MAX_FUTURE_BLOCK_TIME {
+            return Err(ChainError::TimeTooNew);
+        }
+
+        verify_checkpoint(self.network.get_checkpoint(prev.height + 1), &hash)?;
+
+        let entry = ChainEntry::from_header(header, Some(prev));
+
+        self.save_entry(&entry)?;
+
+        Ok(entry)
+    }
+
+    fn save_entry(&mut self, entry: &ChainEntry) -> Result<()> {
+        self.db.put(&layout::entry(&entry.hash), &entry.encode())?;
+        self.entries.insert(entry.hash, entry.clone());
+
+        Ok(())
+    }
+
+    fn remove_entry(&mut self, hash: &Hash) -> Result<()> {
+        self.db.delete(&layout::entry(hash))?;
+        self.entries.remove(hash);
+
+        Ok(())
+    }
+
+    //Reads and decodes a record from the store.
+    fn read<T: Decodable>(&self, key: &[u8]) -> Result<Option<T>> {
+        decode_record(self.db.get(key)?)
+    }
+
+    //Blocks and undo coins go to the flat files if we have them, and to the store otherwise. Data
+    //already in the files, as it is when reindexing, isn't written twice.
+    fn write_data(
+        &mut self,
+        file_type: FileType,
+        entry: &ChainEntry,
+        data: &[u8],
+        batch: &mut Batch,
+    ) -> Result<()> {
+        match self.files.as_mut() {
+            Some(files) => {
+                if !files.has(file_type, &entry.hash)? {
+                    files.write(file_type, &entry.hash, entry.height, data, batch)?;
+                }
+            }
+            None => batch.put(&layout::data(file_type, &entry.hash), data),
+        }
+
+        Ok(())
+    }
+
+    fn read_data<T: Decodable>(&self, file_type: FileType, hash: &Hash) -> Result<Option<T>> {
+        let raw = match self.files.as_ref() {
+            Some(files) => files.read(file_type, hash)?,
+            None => self.db.get(&layout::data(file_type, hash))?,
+        };
+
+        decode_record(raw)
+    }
+
+    fn remove_data(&self, file_type: FileType, hash: &Hash, batch: &mut Batch) -> Result<()> {
+        match self.files.as_ref() {
+            Some(files) => files.remove(file_type, hash, batch)?,
+            None => batch.delete(&layout::data(file_type, hash)),
+        }
+
+        Ok(())
+    }
+
+    pub fn get_entry(&self, hash: &Hash) -> Option<&ChainEntry> {
+        self.entries.get(hash)
+    }
+
+    pub fn get_entry_by_height(&self, height: u32) -> Option<&ChainEntry> {
+        let hash = self.heights.get(height as usize)?;
+        self.entries.get(hash)
+    }
+
+    pub fn has_entry(&self, hash: &Hash) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn tip(&self) -> &ChainEntry {
+        let hash = self.heights.last().expect("chain always holds genesis");
+        &self.entries[hash]
+    }
+
+    pub fn height(&self) -> u32 {
+        self.tip().height
+    }
+
+    pub fn is_main_chain(&self, entry: &ChainEntry) -> bool {
+        self.heights.get(entry.height as usize) == Some(&entry.hash)
+    }
+
+    pub fn get_previous(&self, entry: &ChainEntry) -> Option<&ChainEntry> {
+        if entry.is_genesis() {
+            return None;
+        }
+
+        self.entries.get(&entry.prev_block)
+    }
+
+    /// Returns the ancestor of `entry` at `height`, following the entry's own branch.
+    pub fn get_ancestor<'a>(
+        &'a self,
+        entry: &'a ChainEntry,
+        height: u32,
+    ) -> Option<&'a ChainEntry> {
+        if height > entry.height {
+            return None;
+        }
+
+        if self.is_main_chain(entry) {
+            return self.get_entry_by_height(height);
+        }
+
+        let mut current = entry;
+
+        while current.height > height {
+            current = self.get_previous(current)?;
+        }
+
+        Some(current)
+    }
+
+    fn is_better(&self, entry: &ChainEntry) -> bool {
+        entry.chainwork > self.tip().chainwork
+    }
+
+    /// Iterates from `entry` back to genesis.
+    pub fn ancestors<'a>(&'a self, entry: &'a ChainEntry) -> Ancestors<'a> {
+        Ancestors {
+            chain: self,
+            next: Some(entry),
+        }
+    }
+
+    /// The bits required for a block building on `prev` with timestamp `time`.
+    pub fn get_next_bits(&self, prev: &ChainEntry, time: u64) -> u32 {
+        get_next_bits(self.network, time, self.ancestors(prev))
+    }
+
+    /// Median time past of the 11 entries ending at `entry`.
+    pub fn get_median_time(&self, entry: &ChainEntry) -> u64 {
+        get_median_time(self.ancestors(entry))
+    }
+
+    /// The state of `deployment` for the block after `prev`. States are evaluated at the last
+    /// entry of each miner window, walking back to the last window we have a cached state for.
+    pub fn get_state(&mut self, prev: &ChainEntry, deployment: &Deployment) -> ThresholdState {
+        let bit = deployment.bit;
+        let window = deployment
+            .window
+            .unwrap_or_else(|| self.network.miner_window());
+        let threshold = deployment
+            .threshold
+            .unwrap_or_else(|| self.network.activation_threshold());
+
+        let offset = (prev.height + 1) % window;
+        let mut next = if offset == 0 {
+            Some(prev.clone())
+        } else if prev.height < offset {
+            None
+        } else {
+            self.get_ancestor(prev, prev.height - offset).cloned()
+        };
+
+        let mut state = ThresholdState::Defined;
+        let mut compute = Vec::new();
+
+        while let Some(entry) = next {
+            if let Some(cached) = self.state_cache.get(bit, &entry.hash) {
+                state = cached;
+                break;
+            }
+
+            //Nothing before the start time can have moved past Defined.
+            if self.get_median_time(&entry) < deployment.start_time {
+                self.state_cache
+                    .set(bit, entry.hash, ThresholdState::Defined);
+                break;
+            }
+
+            next = if entry.height < window {
+                None
+            } else {
+                self.get_ancestor(&entry, entry.height - window).cloned()
+            };
+
+            compute.push(entry);
+        }
+
+        while let Some(entry) = compute.pop() {
+            let time = self.get_median_time(&entry);
+
+            state = match state {
+                ThresholdState::Defined => {
+                    if time >= deployment.timeout {
+                        ThresholdState::Failed
+                    } else if time >= deployment.start_time {
+                        ThresholdState::Started
+                    } else {
+                        ThresholdState::Defined
+                    }
+                }
+                ThresholdState::Started => {
+                    if time >= deployment.timeout {
+                        ThresholdState::Failed
+                    } else {
+                        let count = self
+                            .ancestors(&entry)
+                            .take(window as usize)
+                            .filter(|e| has_bit(e.version, bit))
+                            .count() as u32;
+
+                        if count >= threshold {
+                            ThresholdState::LockedIn
+                        } else {
+                            ThresholdState::Started
+                        }
+                    }
+                }
+                ThresholdState::LockedIn => ThresholdState::Active,
+                state => state,
+            };
+
+            self.state_cache.set(bit, entry.hash, state);
+        }
+
+        state
+    }
+
+    pub fn is_active(&mut self, prev: &ChainEntry, deployment: &Deployment) -> bool {
+        self.get_state(prev, deployment) == ThresholdState::Active
+    }
+
+    /// The verification flags for the block after `prev`.
+    pub fn get_deployments(&mut self, prev: &ChainEntry) -> DeploymentState {
+        let mut state = DeploymentState::new();
+
+        if let Some(hardening) = self.network.get_deployment("hardening") {
+            if self.is_active(prev, hardening) {
+                state.name_flags |= VERIFY_COVENANTS_HARDENED;
+            }
+        }
+
+        state
+    }
+
+    /// The verification flags in force for the tip.
+    pub fn deployment_state(&self) -> DeploymentState {
+        self.state
+    }
+
+    /// The version a block building on `prev` should carry, signalling for every deployment
+    /// that is started or locked in.
+    pub fn compute_block_version(&mut self, prev: &ChainEntry) -> u32 {
+        let mut version = 0;
+
+        for deployment in self.network.deployments() {
+            match self.get_state(prev, deployment) {
+                ThresholdState::Started | ThresholdState::LockedIn => {
+                    version |= 1 << deployment.bit;
+                }
+                _ => {}
+            }
+        }
+
+        version | VERSION_TOP_BITS
+    }
+
+    /// Builds a block locator for `start` (or the tip): the hashes of its last ten ancestors, then
+    /// ancestors at exponentially growing gaps, ending at genesis. An unknown start hash is sent
+    /// first, followed by a locator for the tip.
+    pub fn get_locator(&self, start: Option<Hash>) -> Vec<Hash> {
+        let mut hashes = Vec::new();
+
+        let entry = match start.and_then(|hash| self.entries.get(&hash)) {
+            Some(entry) => entry,
+            None => {
+                if let Some(hash) = start {
+                    hashes.push(hash);
+                }
+                self.tip()
+            }
+        };
+
+        hashes.push(entry.hash);
+
+        let mut height = entry.height;
+        let mut step = 1;
+
+        while height > 0 {
+            height = height.saturating_sub(step);
+
+            if hashes.len() > 10 {
+                step *= 2;
+            }
+
+            let ancestor = self
+                .get_ancestor(entry, height)
+                .expect("entries link back to genesis");
+            hashes.push(ancestor.hash);
+        }
+
+        hashes
+    }
+
+    /// The first locator hash on our main chain, which is the last block we share with the peer
+    /// that sent it. Falls back to genesis.
+    pub fn find_locator(&self, locator: &[Hash]) -> &ChainEntry {
+        for hash in locator {
+            if let Some(entry) = self.entries.get(hash) {
+                if self.is_main_chain(entry) {
+                    return entry;
+                }
+            }
+        }
+
+        self.get_entry_by_height(0)
+            .expect("chain always holds genesis")
+    }
+
+    /// Walks back from both entries until their branches meet.
+    pub fn find_fork(&self, a: &ChainEntry, b: &ChainEntry) -> Option<ChainEntry> {
+        let mut a = a.clone();
+        let mut b = b.clone();
+
+        while a.hash != b.hash {
+            if a.height > b.height {
+                a = self.get_previous(&a)?.clone();
+            } else {
+                b = self.get_previous(&b)?.clone();
+            }
+        }
+
+        Some(a)
+    }
+
+    fn set_best_chain(&mut self, entry: &ChainEntry) -> Result<()> {
+        let tip = self.tip().clone();
+
+        let mut result = Ok(());
+
+        if entry.prev_block != tip.hash {
+            result = self.reorganize(&tip, entry);
+        }
+
+        if result.is_ok() {
+            result = self.connect(entry);
+        }
+
+        //A block on the new branch failed its input checks, so go back to the branch we had.
+        if result.is_err() {
+            self.restore(&tip);
+        }
+
+        result
+    }
+
+    //Disconnects the current branch back to the fork point, then connects the competing branch
+    //up to (but not including) the competitor itself.
+    fn reorganize(&mut self, tip: &ChainEntry, competitor: &ChainEntry) -> Result<()> {
+        let fork = self.rewind(tip, competitor)?;
+
+        let mut connects = Vec::new();
+        let mut entry = competitor.clone();
+        while entry.prev_block != fork.hash {
+            entry = self.entries[&entry.prev_block].clone();
+            connects.push(entry.clone());
+        }
+
+        for entry in connects.iter().rev() {
+            self.connect(entry)?;
+        }
+
+        self.emit(ChainEvent::Reorg {
+            old_tip: tip.clone(),
+            new_tip: competitor.clone(),
+            fork,
+        });
+
+        Ok(())
+    }
+
+    //Disconnects from `tip` back to where it meets `other`, returning the fork point.
+    fn rewind(&mut self, tip: &ChainEntry, other: &ChainEntry) -> Result<ChainEntry> {
+        //Both branches always meet at genesis.
+        let fork = self
+            .find_fork(tip, other)
+            .expect("chain entries must share genesis");
+
+        //Check before taking anything apart, so a reorg we can't finish leaves the tip alone.
+        if fork.height < tip.height && fork.height + 1 < self.pruned {
+            return Err(ChainError::Pruned);
+        }
+
+        let mut entry = tip.clone();
+        while entry.hash != fork.hash {
+            self.disconnect(&entry)?;
+            entry = self.entries[&entry.prev_block].clone();
+        }
+
+        Ok(fork)
+    }
+
+    //Returns the best chain to `target` after a failed reorganization. Every block on the way was
+    //connected before, so reconnecting them cannot fail.
+    fn restore(&mut self, target: &ChainEntry) {
+        let current = self.tip().clone();
+
+        if current.hash == target.hash {
+            return;
+        }
+
+        let fork = self
+            .rewind(&current, target)
+            .expect("connected blocks must disconnect");
+
+        let mut connects = Vec::new();
+        let mut entry = target.clone();
+        while entry.hash != fork.hash {
+            connects.push(entry.clone());
+            entry = self.entries[&entry.prev_block].clone();
+        }
+
+        for entry in connects.iter().rev() {
+            self.connect(entry)
+                .expect("previously connected blocks must reconnect");
+        }
+
+        if current.hash != fork.hash {
+            self.emit(ChainEvent::Reorg {
+                old_tip: current,
+                new_tip: target.clone(),
+                fork,
+            });
+        }
+    }
+
+    //Blocks spend and create coins as they connect. Genesis, whose outputs are not spendable,
+    //leaves the coin view alone.
+    fn connect(&mut self, entry: &ChainEntry) -> Result<()> {
+        debug_assert_eq!(entry.height as usize, self.heights.len());
+
+        //Everything the block changes in the store is written in one batch. Until it is, the
+        //only thing changed in memory is the tree's working root, which goes back if the write
+        //fails.
+        let mut batch = Batch::new();
+        let block = self.get_block(&entry.hash)?;
+        let root = self.tree.root_hash();
+
+        if !entry.is_genesis() {
+            let block = match block.as_ref() {
+                Some(block) => block,
+                None => return Err(corrupt("Missing block to connect")),
+            };
+
+            if block.header.tree_root != self.tree_root {
+                return Err(VerifyError::invalid("bad-tree-root", 100).into());
+            }
+
+            check_inputs(block, entry.height, self.network, &self.coins)?;
+            let names = self.connect_names(block, entry.height)?;
+
+            let undo = self.coins.connect_block(block, entry.height, &mut batch)?;
+            self.write_data(FileType::Undo, entry, &undo.encode(), &mut batch)?;
+
+            let undo = names.apply(&mut self.tree)?;
+            batch.put(&layout::name_undo(&entry.hash), &undo.encode());
+        }
+
+        let commit = entry.height % self.network.tree_interval() == 0;
+        let state = if commit {
+            self.tree.commit_batch(&mut batch);
+            ChainState::new(entry.hash, self.tree.root_hash(), entry.height)
+        } else {
+            self.chain_state(entry.hash)
+        };
+
+        batch.put(&layout::height(entry.height), &entry.hash.to_array());
+        //Last, so the state only ever points at a fully written block.
+        batch.put(&layout::state(), &state.encode());
+
+        if let Err(e) = self.db.write(batch) {
+            self.tree.rewind(root);
+            return Err(e.into());
+        }
+
+        if commit {
+            self.tree.committed();
+        }
+        self.tree_root = state.tree_root;
+        self.commit_height = state.commit_height;
+        self.heights.push(entry.hash);
+        self.update_deployment_state();
+
+        let block = block_or_header(entry, block);
+        self.emit(ChainEvent::Connect(entry.clone(), block));
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self, entry: &ChainEntry) -> Result<()> {
+        debug_assert_eq!(self.heights.last(), Some(&entry.hash));
+
+        //Without its undo data the block can't be taken back out.
+        if entry.height < self.pruned {
+            return Err(ChainError::Pruned);
+        }
+
+        let mut batch = Batch::new();
+        let root = self.tree.root_hash();
+
+        let block = match self.get_block(&entry.hash)? {
+            Some(block) => block,
+            None => return Err(corrupt("Missing block to disconnect")),
+        };
+
+        let undo = match self.read_data::<UndoCoins>(FileType::Undo, &entry.hash)? {
+            Some(undo) => undo,
+            None => return Err(corrupt("Missing undo coins to disconnect")),
+        };
+
+        self.coins.disconnect_block(&block, undo, &mut batch);
+        self.remove_data(FileType::Undo, &entry.hash, &mut batch)?;
+
+        if let Some(undo) = self.read::<NameUndo>(&layout::name_undo(&entry.hash))? {
+            undo.apply(&mut self.tree)?;
+            batch.delete(&layout::name_undo(&entry.hash));
+        }
+
+        //The header commits to the root that was current before this block.
+        let state = if entry.height % self.network.tree_interval() == 0 {
+            let commit_height = entry.height - self.network.tree_interval();
+            ChainState::new(entry.prev_block, entry.tree_root, commit_height)
+        } else {
+            self.chain_state(entry.prev_block)
+        };
+
+        batch.delete(&layout::height(entry.height));
+        batch.put(&layout::state(), &state.encode());
+
+        if let Err(e) = self.db.write(batch) {
+            self.tree.rewind(root);
+            return Err(e.into());
+        }
+
+        self.tree_root = state.tree_root;
+        self.commit_height = state.commit_height;
+        self.heights.pop();
+        self.update_deployment_state();
+
+        self.emit(ChainEvent::Disconnect(entry.clone(), block));
+
+        Ok(())
+    }
+
+    //Drops the blocks, undo coins and name undo records of main chain blocks that have fallen
+    //more than `prune` blocks below the tip.
+    fn prune_blocks(&mut self) -> Result<()> {
+        let keep = match self.prune {
+            Some(keep) => keep,
+            None => return Ok(()),
+        };
+
+        let target = (self.height() + 1).saturating_sub(keep);
+        if target <= self.pruned {
+            return Ok(());
+        }
+
+        let mut batch = Batch::new();
+        for height in self.pruned..target {
+            let hash = self.heights[height as usize];
+
+            //Flat files go a whole file at a time below.
+            if self.files.is_none() {
+                self.remove_data(FileType::Block, &hash, &mut batch)?;
+                self.remove_data(FileType::Undo, &hash, &mut batch)?;
+            }
+
+            batch.delete(&layout::name_undo(&hash));
+        }
+        batch.put(&layout::pruned(), &target.to_le_bytes());
+        self.db.write(batch)?;
+
+        self.pruned = target;
+
+        if let Some(files) = self.files.as_mut() {
+            files.prune(target)?;
+        }
+
+        Ok(())
+    }
+
+    fn chain_state(&self, tip: Hash) -> ChainState {
+        ChainState::new(tip, self.tree_root, self.commit_height)
+    }
+
+    fn update_deployment_state(&mut self) {
+        let tip = self.tip().clone();
+
+        self.state = match self.get_previous(&tip).cloned() {
+            Some(prev) => self.get_deployments(&prev),
+            None => DeploymentState::new(),
+        };
+    }
+
+    fn connect_names(&self, block: &Block, height: u32) -> Result<NameView> {
+        let mut view = NameView::new();
+
+        for tx in block.txdata.iter() {
+            view.connect_tx(&self.tree, tx, height, self.network, |hash| {
+                self.verify_renewal(hash, height)
+            })?;
+        }
+
+        Ok(view)
+    }
+
+    fn emit(&mut self, event: ChainEvent) {
+        //Drop any subscribers that have gone away.
+        self.subscribers
+            .retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+}
+
+//A block at a checkpointed height has to be the checkpointed block.
+fn verify_checkpoint(checkpoint: Option<Hash>, hash: &Hash) -> Result<()> {
+    if checkpoint.map_or(false, |checkpoint| checkpoint != *hash) {
+        return Err(VerifyError {
+            code: RejectCode::Checkpoint,
+            reason: "checkpoint mismatch",
+            score: 100,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+pub(crate) fn corrupt(reason: &str) -> ChainError {
+    StoreError::Corrupt(reason.to_string()).into()
+}
+
+pub(crate) fn decode_record<T: Decodable>(raw: Option<Vec<u8>>) -> Result<Option<T>> {
+    let raw = match raw {
+        Some(raw) => raw,
+        None => return Ok(None),
+    };
+
+    match T::decode(&mut Buffer::from(raw)) {
+        Ok(record) => Ok(Some(record)),
+        Err(_) => Err(corrupt("Undecodable chain record")),
+    }
+}
+
+//Entries that were only added as headers are represented by a block with no transactions.
+pub(crate) fn block_or_header(entry: &ChainEntry, block: Option<Block>) -> Block {
+    match block {
+        Some(block) => block,
+        None => Block {
+            header: entry.to_header(),
+            txdata: Vec::new(),
+        },
+    }
+}
+
+pub struct Ancestors<'a> {
+    chain: &'a Chain,
+    next: Option<&'a ChainEntry>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = &'a ChainEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.next?;
+        self.next = self.chain.get_previous(entry);
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{coinbase, mine_block, spend};
+    use handshake_primitives::block_template::builder::BlockTemplateBuilder;
+    use handshake_primitives::covenants::{Covenant, OpenCovenant};
+    use handshake_primitives::Outpoint;
+    use handshake_protocol::consensus::MANDATORY_VERIFY_FLAGS;
+    use handshake_store::kv::BatchOp;
+    use handshake_types::Name;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn block(header: BlockHeader, height: u32) -> Block {
+        Block {
+            header,
+            txdata: vec![coinbase(height)],
+        }
+    }
+
+    //Headers commit to a coinbase-only body, so `block` can fill them in later.
+    fn mine(prev: &ChainEntry, salt: u32) -> BlockHeader {
+        mine_block(prev, salt, Vec::new()).header
+    }
+
+    #[test]
+    fn test_add_headers() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        assert_eq!(genesis.hash, Network::Regtest.genesis_hash());
+        assert!(chain.has_block(&genesis.hash).unwrap());
+
+        let mut prev = genesis.clone();
+        for _ in 0..10 {
+            prev = chain.add_header(mine(&prev, 0)).unwrap();
+        }
+
+        //Headers are indexed, but the tip waits for the blocks.
+        assert_eq!(chain.height(), 0);
+        assert_eq!(chain.tip(), &genesis);
+        assert!(!chain.is_main_chain(&prev));
+        assert_eq!(chain.get_entry(&prev.hash), Some(&prev));
+        assert!(prev.chainwork > genesis.chainwork);
+
+        let ancestor = chain.get_ancestor(&prev, 3).unwrap();
+        assert_eq!(ancestor.height, 3);
+    }
+
+    #[test]
+    fn test_reject_headers() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+        let header = mine(&genesis, 0);
+
+        chain.add_header(header.clone()).unwrap();
+        assert_eq!(chain.add_header(header), Err(ChainError::DuplicateEntry));
+
+        let mut orphan = mine(&genesis, 0);
+        orphan.prev_block = Hash::from([1; 32]);
+        assert_eq!(chain.add_header(orphan), Err(ChainError::Orphan));
+
+        let mut bad_bits = mine(&genesis, 1);
+        bad_bits.bits = 0x2000ffff;
+        while !bad_bits.verify_pow() {
+            bad_bits.nonce += 1;
+        }
+        assert_eq!(chain.add_header(bad_bits), Err(ChainError::BadDiffBits));
+    }
+
+    fn mine_at(prev: &ChainEntry, time: u64) -> BlockHeader {
+        let mut header = BlockHeader {
+            prev_block: prev.hash,
+            time,
+            bits: 0x207fffff,
+            ..Default::default()
+        };
+
+        while !header.verify_pow() {
+            header.nonce += 1;
+        }
+
+        header
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let hash = Hash::from([1; 32]);
+
+        assert!(verify_checkpoint(None, &hash).is_ok());
+        assert!(verify_checkpoint(Some(hash), &hash).is_ok());
+
+        match verify_checkpoint(Some(Hash::from([2; 32])), &hash) {
+            Err(ChainError::Verify(e)) => {
+                assert_eq!(e.code, RejectCode::Checkpoint);
+                assert_eq!(e.reason, "checkpoint mismatch");
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_header_time() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        let mut prev = genesis.clone();
+        for _ in 0..11 {
+            prev = chain.add_header(mine(&prev, 0)).unwrap();
+        }
+
+        //Times run 600..6600 past genesis, so the median of the last 11 is 3600.
+        let mtp = chain.get_median_time(&prev);
+        assert_eq!(mtp, genesis.time + 3600);
+
+        assert_eq!(
+            chain.add_header(mine_at(&prev, mtp)),
+            Err(ChainError::TimeTooOld)
+        );
+
+        //Earlier than the previous block is fine, as long as it's above the median.
+        assert!(chain.add_header(mine_at(&prev, mtp + 1)).is_ok());
+
+        let future = chain.time_data().now() + MAX_FUTURE_BLOCK_TIME + 60;
+        assert_eq!(
+            chain.add_header(mine_at(&prev, future)),
+            Err(ChainError::TimeTooNew)
+        );
+    }
+
+    #[test]
+    fn test_best_tip_by_work() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        let a1 = chain
+            .add_block(mine_block(&genesis, 0, Vec::new()))
+            .unwrap();
+        let a2 = chain.add_block(mine_block(&a1, 0, Vec::new())).unwrap();
+
+        //An equal amount of work does not move the tip.
+        let b1 = chain
+            .add_block(mine_block(&genesis, 1, Vec::new()))
+            .unwrap();
+        let b2 = chain.add_block(mine_block(&b1, 1, Vec::new())).unwrap();
+        assert_eq!(chain.tip(), &a2);
+        assert!(!chain.is_main_chain(&b2));
+
+        let b3 = chain.add_block(mine_block(&b2, 1, Vec::new())).unwrap();
+        assert_eq!(chain.tip(), &b3);
+        assert!(chain.is_main_chain(&b1));
+        assert!(!chain.is_main_chain(&a1));
+        assert_eq!(chain.get_entry_by_height(1), Some(&b1));
+    }
+
+    #[test]
+    fn test_locator() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        let mut main = vec![genesis.clone()];
+        for _ in 0..30 {
+            let prev = main.last().unwrap().clone();
+            main.push(chain.add_block(mine_block(&prev, 0, Vec::new())).unwrap());
+        }
+
+        //Ten single steps back from the tip, then the gaps double until genesis.
+        let locator = chain.get_locator(None);
+        let heights: Vec<u32> = locator
+            .iter()
+            .map(|hash| chain.get_entry(hash).unwrap().height)
+            .collect();
+        assert_eq!(
+            heights,
+            vec![30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19, 17, 13, 5, 0]
+        );
+
+        //A side branch off height 20 is located along its own ancestors.
+        let mut side = main[20].clone();
+        for _ in 0..3 {
+            side = chain.add_header(mine(&side, 1)).unwrap();
+        }
+        assert_eq!(chain.tip(), &main[30]);
+
+        let locator = chain.get_locator(Some(side.hash));
+        assert_eq!(locator[0], side.hash);
+        assert_eq!(locator[3], main[20].hash);
+        assert_eq!(*locator.last().unwrap(), genesis.hash);
+
+        //A peer on the side branch shares everything up to the fork with us.
+        assert_eq!(chain.find_locator(&locator), &main[20]);
+        assert_eq!(chain.find_locator(&chain.get_locator(None)), &main[30]);
+
+        //Unknown hashes are skipped, and with nothing in common we fall back to genesis.
+        let unknown = Hash::from([1; 32]);
+        let locator = chain.get_locator(Some(unknown));
+        assert_eq!(locator[0], unknown);
+        assert_eq!(locator[1], main[30].hash);
+        assert_eq!(chain.find_locator(&[unknown]), &genesis);
+    }
+
+    #[test]
+    fn test_reorg_events() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        let a1 = chain
+            .add_block(block(mine(&genesis, 0), genesis.height + 1))
+            .unwrap();
+        let a2 = chain.add_block(block(mine(&a1, 0), a1.height + 1)).unwrap();
+        let b1 = chain
+            .add_block(block(mine(&genesis, 1), genesis.height + 1))
+            .unwrap();
+        let b2 = chain.add_block(block(mine(&b1, 1), b1.height + 1)).unwrap();
+
+        let mut events = chain.subscribe();
+
+        let b3 = chain.add_block(block(mine(&b2, 1), b2.height + 1)).unwrap();
+        assert_eq!(chain.tip(), &b3);
+
+        let mut next = || events.try_next().unwrap().unwrap();
+
+        match next() {
+            ChainEvent::Disconnect(entry, block) => {
+                assert_eq!(entry, a2);
+                assert_eq!(block.header.hash(), a2.hash);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        match next() {
+            ChainEvent::Disconnect(entry, _) => assert_eq!(entry, a1),
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        match next() {
+            ChainEvent::Connect(entry, _) => assert_eq!(entry, b1),
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        match next() {
+            ChainEvent::Connect(entry, _) => assert_eq!(entry, b2),
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        match next() {
+            ChainEvent::Reorg {
+                old_tip,
+                new_tip,
+                fork,
+            } => {
+                assert_eq!(old_tip, a2);
+                assert_eq!(new_tip, b3);
+                assert_eq!(fork, genesis);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        match next() {
+            ChainEvent::Connect(entry, _) => assert_eq!(entry, b3),
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_orphans() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        let b1 = mine_block(&genesis, 0, Vec::new());
+        let e1 = ChainEntry::from_header(&b1.header, Some(&genesis));
+        let b2 = mine_block(&e1, 0, Vec::new());
+        let e2 = ChainEntry::from_header(&b2.header, Some(&e1));
+        let b3 = mine_block(&e2, 0, Vec::new());
+
+        assert_eq!(
+            chain.add_block_from(b3.clone(), Some("peer")),
+            Err(ChainError::Orphan)
+        );
+        assert_eq!(
+            chain.add_block_from(b3.clone(), Some("peer")),
+            Err(ChainError::DuplicateEntry)
+        );
+        assert_eq!(
+            chain.add_block_from(b2.clone(), Some("peer")),
+            Err(ChainError::Orphan)
+        );
+
+        //Sync has to fetch the parent of the earliest orphan.
+        assert_eq!(chain.get_orphan_root(&b3.header.hash()), Some(e2.hash));
+        assert_eq!(chain.tip(), &genesis);
+
+        //The missing parent connects both orphans behind it.
+        chain.add_block(b1).unwrap();
+        assert_eq!(chain.height(), 3);
+        assert_eq!(chain.tip().hash, b3.header.hash());
+        assert!(!chain.has_orphan(&b2.header.hash()));
+        assert!(!chain.has_orphan(&b3.header.hash()));
+
+        //Orphans are checked for proof of work before we hold on to them.
+        let mut junk = mine_block(chain.tip(), 1, Vec::new());
+        junk.header.prev_block = Hash::from([1; 32]);
+        junk.header.bits = 0x1d00ffff;
+        assert_eq!(chain.add_block(junk), Err(ChainError::HighHash));
+    }
+
+    #[test]
+    fn test_block_after_header() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        let header1 = mine(&genesis, 0);
+        let entry1 = chain.add_header(header1.clone()).unwrap();
+        let header2 = mine(&entry1, 0);
+        let entry2 = chain.add_header(header2.clone()).unwrap();
+        assert!(!chain.has_block(&entry1.hash).unwrap());
+        assert_eq!(chain.tip(), &genesis);
+
+        let mut events = chain.subscribe();
+
+        //The second block has to wait for the first.
+        assert_eq!(chain.add_block(block(header2, 2)).unwrap(), entry2);
+        assert!(chain.has_block(&entry2.hash).unwrap());
+        assert_eq!(chain.tip(), &genesis);
+        assert!(events.try_next().is_err());
+
+        assert_eq!(chain.add_block(block(header1, 1)).unwrap(), entry1);
+        assert_eq!(chain.tip(), &entry2);
+        assert!(chain
+            .coins()
+            .has(&Outpoint::new(coinbase(1).hash(), 0))
+            .unwrap());
+        assert!(chain
+            .coins()
+            .has(&Outpoint::new(coinbase(2).hash(), 0))
+            .unwrap());
+
+        match events.try_next().unwrap().unwrap() {
+            ChainEvent::Connect(entry, _) => assert_eq!(entry, entry1),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        match events.try_next().unwrap().unwrap() {
+            ChainEvent::Connect(entry, _) => assert_eq!(entry, entry2),
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_verify_block() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        //A coinbase for the wrong height no longer matches the header's merkle root.
+        let header = mine(&genesis, 0);
+        let bad_body = block(header.clone(), 2);
+        match chain.add_block(bad_body) {
+            Err(ChainError::Verify(e)) => assert_eq!(e.reason, "bad-txnmrklroot"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert!(!chain.has_entry(&header.hash()));
+
+        //Committing to the wrong height is caught by the contextual checks.
+        let mut wrong_height = block(BlockHeader::default(), 2);
+        wrong_height.header = BlockHeader {
+            prev_block: genesis.hash,
+            time: genesis.time + 600,
+            bits: 0x207fffff,
+            merkle_root: wrong_height.create_merkle_root(),
+            witness_root: wrong_height.create_witness_root(),
+            ..Default::default()
+        };
+        while !wrong_height.header.verify_pow() {
+            wrong_height.header.nonce += 1;
+        }
+        match chain.add_block(wrong_height) {
+            Err(ChainError::Verify(e)) => assert_eq!(e.reason, "bad-cb-height"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        let entry = chain.add_block(block(header, 1)).unwrap();
+        assert_eq!(chain.tip(), &entry);
+    }
+
+    #[test]
+    fn test_tree_root() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+
+        let mut bad = mine_block(&genesis, 0, Vec::new());
+        bad.header.tree_root = Hash::from([1; 32]);
+        while !bad.header.verify_pow() {
+            bad.header.nonce += 1;
+        }
+
+        match chain.add_block(bad) {
+            Err(ChainError::Verify(e)) => assert_eq!(e.reason, "bad-tree-root"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(chain.tip(), &genesis);
+
+        //No names have been touched, so every commit is of the empty tree.
+        let mut prev = genesis;
+        for _ in 0..Network::Regtest.tree_interval() + 1 {
+            prev = chain.add_block(mine_block(&prev, 0, Vec::new())).unwrap();
+        }
+        assert_eq!(chain.tree_root(), Hash::default());
+    }
+
+    #[test]
+    fn test_coins_follow_reorg() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+        let a1 = chain
+            .add_block(mine_block(&genesis, 0, Vec::new()))
+            .unwrap();
+        let a2 = chain.add_block(mine_block(&a1, 0, Vec::new())).unwrap();
+
+        let reward = Outpoint::new(coinbase(1).hash(), 0);
+        assert!(chain.coins().has(&reward).unwrap());
+
+        let a3 = chain
+            .add_block(mine_block(&a2, 0, vec![spend(reward)]))
+            .unwrap();
+        assert!(!chain.coins().has(&reward).unwrap());
+
+        //The competing branch does not spend the reward, so it comes back on reorg.
+        let b3 = chain.add_block(mine_block(&a2, 1, Vec::new())).unwrap();
+        let b4 = chain.add_block(mine_block(&b3, 1, Vec::new())).unwrap();
+        assert_eq!(chain.tip(), &b4);
+        assert!(chain.coins().has(&reward).unwrap());
+
+        let a4 = chain.add_block(mine_block(&a3, 0, Vec::new())).unwrap();
+        chain.add_block(mine_block(&a4, 0, Vec::new())).unwrap();
+        assert!(!chain.coins().has(&reward).unwrap());
+    }
+
+    #[test]
+    fn test_flat_files() {
+        let dir = std::env::temp_dir().join(format!("handshake-chain-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let db: Arc<dyn KvStore> = Arc::new(MemoryStore::new());
+        let network = Network::Regtest;
+        let files = FileStore::open(&dir, network.magic(), db.clone()).unwrap();
+        let mut chain = Chain::with_files(network, db, files).unwrap();
+
+        let genesis = chain.tip().clone();
+        let a1 = chain
+            .add_block(mine_block(&genesis, 0, Vec::new()))
+            .unwrap();
+        let a2 = chain.add_block(mine_block(&a1, 0, Vec::new())).unwrap();
+        let reward = Outpoint::new(coinbase(1).hash(), 0);
+        let spending = mine_block(&a2, 0, vec![spend(reward)]);
+        chain.add_block(spending.clone()).unwrap();
+
+        assert_eq!(
+            chain.get_block(&spending.header.hash()).unwrap(),
+            Some(spending)
+        );
+        assert!(dir.join("blk00000.dat").exists());
+        assert!(dir.join("rev00000.dat").exists());
+
+        //Disconnecting reads the undo coins back from the rev file.
+        let b3 = chain.add_block(mine_block(&a2, 1, Vec::new())).unwrap();
+        chain.add_block(mine_block(&b3, 1, Vec::new())).unwrap();
+        assert!(chain.coins().has(&reward).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recovery() {
+        let db: Arc<dyn KvStore> = Arc::new(MemoryStore::new());
+        let network = Network::Regtest;
+        let mut chain = Chain::with_store(network, db.clone()).unwrap();
+
+        let mut prev = chain.tip().clone();
+        for _ in 0..network.tree_interval() {
+            prev = chain.add_block(mine_block(&prev, 0, Vec::new())).unwrap();
+        }
+
+        //Opened after the last tree commit, so the name only lives in memory.
+        let name: Name = "handshake".parse().unwrap();
+        let mut opening = spend(Outpoint::new(coinbase(1).hash(), 0));
+        opening.outputs[0].covenant = Covenant::Open(OpenCovenant {
+            name_hash: name.hash(),
+            height: 0,
+            name: name.clone(),
+        });
+        prev = chain
+            .add_block(mine_block(&prev, 0, vec![opening]))
+            .unwrap();
+        prev = chain.add_block(mine_block(&prev, 0, Vec::new())).unwrap();
+
+        let state = chain.get_name_state(&name.hash()).unwrap();
+        assert!(state.is_some());
+        let root = chain.tree().root_hash();
+        let tree_root = chain.tree_root();
+        drop(chain);
+
+        let chain = Chain::with_store(network, db.clone()).unwrap();
+        assert_eq!(chain.tip(), &prev);
+        assert_eq!(chain.tree_root(), tree_root);
+        assert_eq!(chain.tree().root_hash(), root);
+        assert_eq!(chain.get_name_state(&name.hash()).unwrap(), state);
+        drop(chain);
+
+        //A block whose data made it to the store but which was never connected.
+        let next = mine_block(&prev, 0, Vec::new());
+        let entry = ChainEntry::from_header(&next.header, Some(&prev));
+        db.put(&layout::entry(&entry.hash), &entry.encode())
+            .unwrap();
+        db.put(&layout::block(&entry.hash), &next.encode()).unwrap();
+
+        let chain = Chain::with_store(network, db.clone()).unwrap();
+        assert_eq!(chain.tip(), &entry);
+        assert!(chain
+            .coins()
+            .has(&Outpoint::new(coinbase(entry.height).hash(), 0))
+            .unwrap());
+        drop(chain);
+
+        //A block the tree still needs to replay its names from, gone from the store.
+        db.delete(&layout::block(&prev.hash)).unwrap();
+        match Chain::with_store(network, db) {
+            Err(ChainError::Store(StoreError::Corrupt(_))) => {}
+            result => panic!(
+                "unexpected result: {:?}",
+                result.map(|chain| chain.height())
+            ),
+        }
+    }
+
+    //Fails writes that would move the chain state while `fail` is set.
+    struct FailingStore {
+        inner: MemoryStore,
+        fail: AtomicBool,
+    }
+
+    impl KvRead for FailingStore {
+        fn get(&self, key: &[u8]) -> handshake_store::Result<Option<Vec<u8>>> {
+            self.inner.get(key)
+        }
+
+        fn range(
+            &self,
+            start: &[u8],
+            end: Option<&[u8]>,
+        ) -> handshake_store::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+            self.inner.range(start, end)
+        }
+    }
+
+    impl KvStore for FailingStore {
+        fn write(&self, batch: Batch) -> handshake_store::Result<()> {
+            let state = layout::state();
+            let moves_state = batch.ops().iter().any(|op| match op {
+                BatchOp::Put(key, _) => *key == state,
+                BatchOp::Delete(_) => false,
+            });
+
+            if moves_state && self.fail.load(Ordering::SeqCst) {
+                return Err(StoreError::Io("write failed".to_string()));
+            }
+
+            self.inner.write(batch)
+        }
+
+        fn snapshot(&self) -> Box<dyn KvRead + Send + Sync> {
+            self.inner.snapshot()
+        }
+    }
+
+    #[test]
+    fn test_failed_connect() {
+        let db = Arc::new(FailingStore {
+            inner: MemoryStore::new(),
+            fail: AtomicBool::new(false),
+        });
+        let network = Network::Regtest;
+        let mut chain = Chain::with_store(network, db.clone()).unwrap();
+
+        let mut prev = chain.tip().clone();
+        for _ in 0..network.tree_interval() - 2 {
+            prev = chain.add_block(mine_block(&prev, 0, Vec::new())).unwrap();
+        }
+
+        let name: Name = "handshake".parse().unwrap();
+        let mut opening = spend(Outpoint::new(coinbase(1).hash(), 0));
+        opening.outputs[0].covenant = Covenant::Open(OpenCovenant {
+            name_hash: name.hash(),
+            height: 0,
+            name,
+        });
+        prev = chain
+            .add_block(mine_block(&prev, 0, vec![opening]))
+            .unwrap();
+
+        //The next block commits the tree. Its write failing leaves everything as it was.
+        let root = chain.tree().root_hash();
+        let block = mine_block(&prev, 0, Vec::new());
+        db.fail.store(true, Ordering::SeqCst);
+        assert!(chain.add_block(block.clone()).is_err());
+
+        assert_eq!(chain.tip(), &prev);
+        assert_eq!(chain.tree().root_hash(), root);
+        assert_eq!(chain.tree_root(), Hash::default());
+
+        db.fail.store(false, Ordering::SeqCst);
+        let entry = chain.add_block(block).unwrap();
+        assert_eq!(entry.height % network.tree_interval(), 0);
+        assert_eq!(chain.tree_root(), root);
+        assert!(chain.tree().is_committed(&root).unwrap());
+    }
+
+    #[test]
+    fn test_missing_undo() {
+        let db: Arc<dyn KvStore> = Arc::new(MemoryStore::new());
+        let network = Network::Regtest;
+        let mut chain = Chain::with_store(network, db.clone()).unwrap();
+        let genesis = chain.tip().clone();
+
+        let a1 = chain
+            .add_block(mine_block(&genesis, 0, Vec::new()))
+            .unwrap();
+        let b1 = chain
+            .add_block(mine_block(&genesis, 1, Vec::new()))
+            .unwrap();
+        db.delete(&layout::data(FileType::Undo, &a1.hash)).unwrap();
+
+        //Without its undo coins a1 can't be taken back out, so the reorg fails.
+        let b2 = mine_block(&b1, 1, Vec::new());
+        match chain.add_block(b2.clone()) {
+            Err(ChainError::Store(StoreError::Corrupt(_))) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(chain.tip(), &a1);
+
+        //Nor does it on startup, when b2 was stored but never connected.
+        let e2 = chain.index_header(&b2.header).unwrap();
+        let mut batch = Batch::new();
+        chain
+            .write_data(FileType::Block, &e2, &b2.encode(), &mut batch)
+            .unwrap();
+        db.write(batch).unwrap();
+        drop(chain);
+
+        match Chain::with_store(network, db) {
+            Err(ChainError::Store(StoreError::Corrupt(_))) => {}
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("opened a chain that can't reorganize"),
+        }
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut chain = Chain::new(Network::Regtest);
+        let interval = Network::Regtest.tree_interval();
+
+        //Asking for less than a tree interval still keeps a whole one.
+        chain.set_prune(Some(1)).unwrap();
+        assert!(chain.is_pruned());
+
+        let mut prev = chain.tip().clone();
+        for _ in 0..interval * 2 {
+            prev = chain.add_block(mine_block(&prev, 0, Vec::new())).unwrap();
+        }
+
+        assert_eq!(chain.pruned_height(), interval + 1);
+        let pruned = chain.get_entry_by_height(interval).unwrap().hash;
+        let kept = chain.get_entry_by_height(interval + 1).unwrap().hash;
+        assert!(!chain.has_block(&pruned).unwrap());
+        assert!(chain.has_block(&kept).unwrap());
+        assert!(!chain.db().has(&layout::undo(&pruned)).unwrap());
+
+        //A reorg past the pruned blocks can't be done.
+        let mut fork = chain.get_entry_by_height(interval - 1).unwrap().clone();
+        for _ in 0..interval + 1 {
+            fork = chain.add_block(mine_block(&fork, 1, Vec::new())).unwrap();
+        }
+        assert_eq!(
+            chain.add_block(mine_block(&fork, 1, Vec::new())),
+            Err(ChainError::Pruned)
+        );
+        assert_eq!(chain.tip(), &prev);
+    }
+
+    #[test]
+    fn test_invalid_reorg_restores_tip() {
+        let mut chain = Chain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+        let a1 = chain
+            .add_block(mine_block(&genesis, 0, Vec::new()))
+            .unwrap();
+
+        //Side branches are only checked against the coin view once they connect.
+        let missing = Outpoint::new(Hash::from([1; 32]), 0);
+        let b1 = chain
+            .add_block(mine_block(&genesis, 1, vec![spend(missing)]))
+            .unwrap();
+        let coins = chain.coins().len().unwrap();
+
+        match chain.add_block(mine_block(&b1, 1, Vec::new())) {
+            Err(ChainError::Verify(e)) => assert_eq!(e.reason, "bad-txns-inputs-missingorspent"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        assert_eq!(chain.tip(), &a1);
+        assert_eq!(chain.coins().len().unwrap(), coins);
+
+        //Spending a coinbase before it matures is rejected outright.
+        let early = spend(Outpoint::new(coinbase(1).hash(), 0));
+        match chain.add_block(mine_block(&a1, 0, vec![early])) {
+            Err(ChainError::Verify(e)) => {
+                assert_eq!(e.reason, "bad-txns-premature-spend-of-coinbase")
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(chain.tip(), &a1);
+    }
+
+    #[test]
+    fn test_versionbits() {
+        let network = Network::Regtest;
+        let mut chain = Chain::new(network);
+        let window = network.miner_window();
+        let threshold = network.activation_threshold();
+
+        let deployment = Deployment {
+            name: "test",
+            bit: 5,
+            start_time: 0,
+            timeout: u64::max_value(),
+            threshold: None,
+            window: None,
+            required: false,
+            force: false,
+        };
+        let testdummy = network.get_deployment("testdummy").unwrap();
+
+        let genesis = chain.tip().clone();
+        assert_eq!(
+            chain.get_state(&genesis, &deployment),
+            ThresholdState::Defined
+        );
+
+        //Mines a window, the first `signals` blocks of which signal for the deployment.
+        fn mine_window(chain: &mut Chain, window: u32, signals: u32) -> ChainEntry {
+            for i in 0..window {
+                let tip = chain.tip().clone();
+                let version = if i < signals { 1 << 5 } else { 0 };
+                chain
+                    .add_block(mine_block(&tip, version, Vec::new()))
+                    .unwrap();
+            }
+            chain.tip().clone()
+        }
+
+        //The state only moves once the first window is complete.
+        let tip = mine_window(&mut chain, window - 1, 0);
+        assert_eq!(chain.get_state(&tip, &deployment), ThresholdState::Started);
+        assert_eq!(chain.compute_block_version(&tip) & (1 << 5), 1 << 5);
+
+        let tip = mine_window(&mut chain, window, threshold - 1);
+        assert_eq!(chain.get_state(&tip, &deployment), ThresholdState::Started);
+
+        let tip = mine_window(&mut chain, window, threshold);
+        assert_eq!(chain.get_state(&tip, &deployment), ThresholdState::LockedIn);
+
+        //Mid-window the state is that of the last complete window.
+        let mid = mine_window(&mut chain, window / 2, 0);
+        assert_eq!(chain.get_state(&mid, &deployment), ThresholdState::LockedIn);
+
+        let tip = mine_window(&mut chain, window - window / 2, 0);
+        assert!(chain.is_active(&tip, &deployment));
+        assert_eq!(chain.compute_block_version(&tip) & (1 << 5), 0);
+
+        //Forced deployments still need signalling, so testdummy is started but not active.
+        assert_eq!(chain.get_state(&tip, testdummy), ThresholdState::Started);
+        assert!(!chain.is_active(&tip, testdummy));
+        let bit = 1 << testdummy.bit;
+        assert_eq!(chain.compute_block_version(&tip) & bit, bit);
+
+        //Nobody signalled for hardening, so claims are still checked the old way.
+        assert!(!chain.deployment_state().has_hardening());
+        assert_eq!(chain.deployment_state(), DeploymentState::new());
+
+        //The flags only leave the chain through block templates.
+        let flags = chain.get_deployments(&tip).flags;
+        let template = BlockTemplateBuilder::new().with_flags(flags).build();
+        assert_eq!(template.flags, MANDATORY_VERIFY_FLAGS);
+    }
 }
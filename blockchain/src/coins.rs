@@ -0,0 +1,384 @@
+use crate::{layout, Result};
+use extended_primitives::Buffer;
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_primitives::{Address, Block, Covenant, Outpoint, Output, Transaction};
+use handshake_store::{Batch, KvRead, KvStore, MemoryStore, StoreError};
+use handshake_types::Amount;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// An unspent output along with the context needed to spend it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CoinEntry {
+    pub output: Output,
+    /// Height of the block that created the coin.
+    pub height: u32,
+    pub coinbase: bool,
+}
+
+impl CoinEntry {
+    pub fn new(output: Output, height: u32, coinbase: bool) -> Self {
+        CoinEntry {
+            output,
+            height,
+            coinbase,
+        }
+    }
+
+    pub fn value(&self) -> Amount {
+        self.output.value
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.output.address
+    }
+
+    pub fn covenant(&self) -> &Covenant {
+        &self.output.covenant
+    }
+
+    /// Coinbase outputs can only be spent once they are `maturity` blocks deep.
+    pub fn is_mature(&self, height: u32, maturity: u32) -> bool {
+        !self.coinbase || height.saturating_sub(self.height) >= maturity
+    }
+}
+
+impl Encodable for CoinEntry {
+    fn size(&self) -> usize {
+        //height (4) + flags (1)
+        5 + self.output.size()
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        buffer.write_u32(self.height);
+        buffer.write_u8(self.coinbase as u8);
+        buffer.extend(self.output.encode());
+
+        buffer
+    }
+}
+
+impl Decodable for CoinEntry {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        let height = buffer.read_u32()?;
+        let coinbase = buffer.read_u8()? & 1 == 1;
+        let output = Output::decode(buffer)?;
+
+        Ok(CoinEntry {
+            output,
+            height,
+            coinbase,
+        })
+    }
+}
+
+/// The coins a block spent, in the order it spent them. Replaying these backwards restores the
+/// coin view to its state before the block.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct UndoCoins {
+    pub items: Vec<CoinEntry>,
+}
+
+impl UndoCoins {
+    pub fn new() -> Self {
+        UndoCoins::default()
+    }
+
+    pub fn push(&mut self, coin: CoinEntry) {
+        self.items.push(coin);
+    }
+
+    pub fn pop(&mut self) -> Option<CoinEntry> {
+        self.items.pop()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl Encodable for UndoCoins {
+    fn size(&self) -> usize {
+        let mut size = 4;
+
+        for item in self.items.iter() {
+            size += item.size();
+        }
+
+        size
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        buffer.write_u32(self.items.len() as u32);
+
+        for item in self.items.iter() {
+            buffer.extend(item.encode());
+        }
+
+        buffer
+    }
+}
+
+impl Decodable for UndoCoins {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        let count = buffer.read_u32()?;
+        let mut items = Vec::new();
+
+        for _ in 0..count {
+            items.push(CoinEntry::decode(buffer)?);
+        }
+
+        Ok(UndoCoins { items })
+    }
+}
+
+/// The set of unspent outputs, keyed by outpoint and kept in the chain's key-value store.
+#[derive(Clone)]
+pub struct CoinView {
+    store: Arc<dyn KvStore>,
+}
+
+impl CoinView {
+    /// A view kept in memory.
+    pub fn new() -> Self {
+        CoinView::with_store(Arc::new(MemoryStore::new()))
+    }
+
+    pub fn with_store(store: Arc<dyn KvStore>) -> Self {
+        CoinView { store }
+    }
+
+    pub fn get(&self, prevout: &Outpoint) -> Result<Option<CoinEntry>> {
+        match self.store.get(&layout::coin(prevout))? {
+            Some(raw) => Ok(Some(decode_coin(raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn has(&self, prevout: &Outpoint) -> Result<bool> {
+        Ok(self.store.has(&layout::coin(prevout))?)
+    }
+
+    /// Counts the coins by scanning the store.
+    pub fn len(&self) -> Result<usize> {
+        Ok(self.store.prefix(&[layout::COIN])?.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    pub fn add(&mut self, prevout: Outpoint, coin: CoinEntry) -> Result<()> {
+        Ok(self.store.put(&layout::coin(&prevout), &coin.encode())?)
+    }
+
+    pub fn spend(&mut self, prevout: &Outpoint) -> Result<Option<CoinEntry>> {
+        let coin = self.get(prevout)?;
+
+        if coin.is_some() {
+            self.store.delete(&layout::coin(prevout))?;
+        }
+
+        Ok(coin)
+    }
+
+    /// Adds a transaction's outputs. Unspendable outputs never enter the view.
+    pub fn add_tx(&mut self, tx: &Transaction, height: u32) -> Result<()> {
+        let mut batch = Batch::new();
+        add_outputs(tx, height, &mut batch, &mut HashMap::new());
+        Ok(self.store.write(batch)?)
+    }
+
+    /// Removes a transaction's outputs.
+    pub fn remove_tx(&mut self, tx: &Transaction) -> Result<()> {
+        let mut batch = Batch::new();
+        remove_outputs(tx, &mut batch);
+        Ok(self.store.write(batch)?)
+    }
+
+    /// Adds the writes that apply an already verified block to `batch`, returning the coins it
+    /// spent. The view is unchanged until the batch is written.
+    pub fn connect_block(
+        &mut self,
+        block: &Block,
+        height: u32,
+        batch: &mut Batch,
+    ) -> Result<UndoCoins> {
+        let mut undo = UndoCoins::new();
+        //Coins created or spent earlier in the block, which the store doesn't have yet.
+        let mut overlay: HashMap<Outpoint, Option<CoinEntry>> = HashMap::new();
+
+        for tx in block.txdata.iter() {
+            if !tx.is_coinbase() {
+                for input in tx.inputs.iter() {
+                    let coin = match overlay.insert(input.prevout, None) {
+                        Some(coin) => coin,
+                        None => self.get(&input.prevout)?,
+                    };
+
+                    //Blocks are checked against the view before they connect, so this means the
+                    //view and the chain disagree.
+                    let coin = match coin {
+                        Some(coin) => coin,
+                        None => {
+                            return Err(StoreError::Corrupt(
+                                "Block spends a missing coin".to_string(),
+                            )
+                            .into())
+                        }
+                    };
+                    batch.delete(&layout::coin(&input.prevout));
+                    undo.push(coin);
+                }
+            }
+
+            add_outputs(tx, height, batch, &mut overlay);
+        }
+
+        Ok(undo)
+    }
+
+    /// Adds the writes that reverse `connect_block` to `batch`, restoring every coin the block
+    /// spent.
+    pub fn disconnect_block(&mut self, block: &Block, mut undo: UndoCoins, batch: &mut Batch) {
+        for tx in block.txdata.iter().rev() {
+            remove_outputs(tx, batch);
+
+            if tx.is_coinbase() {
+                continue;
+            }
+
+            for input in tx.inputs.iter().rev() {
+                let coin = undo.pop().expect("undo data must cover every spent input");
+                batch.put(&layout::coin(&input.prevout), &coin.encode());
+            }
+        }
+
+        debug_assert!(undo.is_empty());
+    }
+}
+
+impl Default for CoinView {
+    fn default() -> Self {
+        CoinView::new()
+    }
+}
+
+impl fmt::Debug for CoinView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CoinView").finish()
+    }
+}
+
+fn decode_coin(raw: Vec<u8>) -> Result<CoinEntry> {
+    CoinEntry::decode(&mut Buffer::from(raw))
+        .map_err(|_| StoreError::Corrupt("Undecodable coin".to_string()).into())
+}
+
+fn add_outputs(
+    tx: &Transaction,
+    height: u32,
+    batch: &mut Batch,
+    overlay: &mut HashMap<Outpoint, Option<CoinEntry>>,
+) {
+    let txid = tx.hash();
+    let coinbase = tx.is_coinbase();
+
+    for (index, output) in tx.outputs.iter().enumerate() {
+        if output.is_unspendable() {
+            continue;
+        }
+
+        let prevout = Outpoint::new(txid, index as u32);
+        let coin = CoinEntry::new(output.clone(), height, coinbase);
+        batch.put(&layout::coin(&prevout), &coin.encode());
+        overlay.insert(prevout, Some(coin));
+    }
+}
+
+fn remove_outputs(tx: &Transaction, batch: &mut Batch) {
+    let txid = tx.hash();
+
+    for index in 0..tx.outputs.len() {
+        batch.delete(&layout::coin(&Outpoint::new(txid, index as u32)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{coinbase_paying, output, spend_paying, spend_to};
+    use handshake_primitives::BlockHeader;
+
+    #[test]
+    fn test_coin_entry_encoding() {
+        let coin = CoinEntry::new(output(5000, Covenant::None), 10, true);
+
+        let mut encoded = coin.encode();
+        assert_eq!(encoded.len(), coin.size());
+        assert_eq!(CoinEntry::decode(&mut encoded).unwrap(), coin);
+
+        assert!(!coin.is_mature(11, 2));
+        assert!(coin.is_mature(12, 2));
+    }
+
+    #[test]
+    fn test_connect_disconnect() {
+        let funding = spend_to(
+            Vec::new(),
+            vec![output(1000, Covenant::None), output(2000, Covenant::None)],
+        );
+        let mut view = CoinView::new();
+        view.add_tx(&funding, 1).unwrap();
+        let before = view.store.prefix(&[layout::COIN]).unwrap();
+
+        let spend = spend_paying(Outpoint::new(funding.hash(), 0), 900);
+        let chained = spend_paying(Outpoint::new(spend.hash(), 0), 800);
+        let block = Block {
+            header: BlockHeader::default(),
+            txdata: vec![coinbase_paying(2, 50), spend.clone(), chained.clone()],
+        };
+
+        let mut batch = Batch::new();
+        let undo = view.connect_block(&block, 2, &mut batch).unwrap();
+        assert_eq!(undo.items.len(), 2);
+
+        //Nothing changes until the batch is written.
+        assert!(view.has(&Outpoint::new(funding.hash(), 0)).unwrap());
+        view.store.write(batch).unwrap();
+
+        assert!(!view.has(&Outpoint::new(funding.hash(), 0)).unwrap());
+        assert!(!view.has(&Outpoint::new(spend.hash(), 0)).unwrap());
+        assert_eq!(
+            view.get(&Outpoint::new(chained.hash(), 0))
+                .unwrap()
+                .unwrap()
+                .height,
+            2
+        );
+
+        let mut encoded = undo.encode();
+        let undo = UndoCoins::decode(&mut encoded).unwrap();
+
+        let mut batch = Batch::new();
+        view.disconnect_block(&block, undo, &mut batch);
+        view.store.write(batch).unwrap();
+        assert_eq!(view.store.prefix(&[layout::COIN]).unwrap(), before);
+
+        //A spend of a coin the view doesn't have is an error, not a panic.
+        let missing = Block {
+            header: BlockHeader::default(),
+            txdata: vec![coinbase_paying(2, 50), chained],
+        };
+        assert!(view.connect_block(&missing, 2, &mut Batch::new()).is_err());
+    }
+}
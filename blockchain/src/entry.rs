@@ -0,0 +1,168 @@
+use extended_primitives::{Buffer, Hash, Uint256};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_primitives::BlockHeader;
+use handshake_protocol::consensus::HeaderInfo;
+use handshake_types::{Compact, Difficulty};
+
+/// An entry in the block index. Holds the header of a block along with the
+/// information the chain needs to place it: its height and the total work of
+/// the chain up to and including it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainEntry {
+    pub hash: Hash,
+    pub height: u32,
+    pub version: u32,
+    pub prev_block: Hash,
+    pub merkle_root: Hash,
+    pub witness_root: Hash,
+    pub tree_root: Hash,
+    pub reserved_root: Hash,
+    pub time: u64,
+    pub bits: u32,
+    pub nonce: u32,
+    pub extra_nonce: Buffer,
+    pub mask: Hash,
+    /// Cumulative work of the chain ending at this entry.
+    pub chainwork: Uint256,
+}
+
+impl ChainEntry {
+    /// Creates an entry from a header. `prev` is the entry the header builds on, and should only
+    /// be None for the genesis block.
+    pub fn from_header(header: &BlockHeader, prev: Option<&ChainEntry>) -> Self {
+        let mut entry = ChainEntry {
+            hash: header.hash(),
+            height: 0,
+            version: header.version,
+            prev_block: header.prev_block,
+            merkle_root: header.merkle_root,
+            witness_root: header.witness_root,
+            tree_root: header.tree_root,
+            reserved_root: header.reserved_root,
+            time: header.time,
+            bits: header.bits,
+            nonce: header.nonce,
+            extra_nonce: header.extra_nonce.clone(),
+            mask: header.mask,
+            chainwork: Uint256::from(0u64),
+        };
+
+        entry.height = match prev {
+            Some(prev) => prev.height + 1,
+            None => 0,
+        };
+
+        entry.chainwork = entry.get_chainwork(prev);
+
+        entry
+    }
+
+    pub fn is_genesis(&self) -> bool {
+        self.height == 0
+    }
+
+    /// Returns the work represented by this entry's bits.
+    pub fn get_proof(&self) -> Uint256 {
+        Difficulty::from_bits(Compact::from(self.bits)).to_work()
+    }
+
+    /// Cumulative work of the chain ending at this entry.
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty::from_work(self.chainwork)
+    }
+
+    fn get_chainwork(&self, prev: Option<&ChainEntry>) -> Uint256 {
+        let proof = self.get_proof();
+
+        match prev {
+            Some(prev) => prev.chainwork + proof,
+            None => proof,
+        }
+    }
+
+    pub fn to_header(&self) -> BlockHeader {
+        BlockHeader {
+            version: self.version,
+            prev_block: self.prev_block,
+            merkle_root: self.merkle_root,
+            witness_root: self.witness_root,
+            tree_root: self.tree_root,
+            reserved_root: self.reserved_root,
+            time: self.time,
+            bits: self.bits,
+            nonce: self.nonce,
+            extra_nonce: self.extra_nonce.clone(),
+            mask: self.mask,
+        }
+    }
+}
+
+impl HeaderInfo for ChainEntry {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+}
+
+impl Encodable for ChainEntry {
+    fn size(&self) -> usize {
+        //height (4) + header + chainwork (32)
+        4 + self.to_header().size() + 32
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        buffer.write_u32(self.height);
+        buffer.extend(self.to_header().encode());
+
+        //Chainwork as four 64 bit words, least significant first.
+        for word in 0..4 {
+            buffer.write_u64((self.chainwork >> (64 * word)).low_u64());
+        }
+
+        buffer
+    }
+}
+
+impl Decodable for ChainEntry {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        let height = buffer.read_u32()?;
+        let header = BlockHeader::decode(buffer)?;
+
+        let mut chainwork = Uint256::from(0u64);
+        for word in 0..4 {
+            chainwork = chainwork + (Uint256::from(buffer.read_u64()?) << (64 * word));
+        }
+
+        let mut entry = ChainEntry::from_header(&header, None);
+        entry.height = height;
+        entry.chainwork = chainwork;
+
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use handshake_primitives::Genesis;
+    use handshake_protocol::network::Network;
+
+    #[test]
+    fn test_encoding() {
+        let genesis = Network::Regtest.genesis_block();
+        let mut entry = ChainEntry::from_header(&genesis.header, None);
+        entry.height = 12;
+        entry.chainwork = entry.chainwork + (Uint256::from(7u64) << 130);
+
+        let mut encoded = entry.encode();
+        assert_eq!(encoded.len(), entry.size());
+        assert_eq!(ChainEntry::decode(&mut encoded).unwrap(), entry);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::verify::VerifyError;
+use handshake_store::StoreError;
+use handshake_types::NameHash;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum ChainError {
+    DuplicateEntry,
+    Orphan,
+    HighHash,
+    BadDiffBits,
+    TimeTooOld,
+    TimeTooNew,
+    Verify(VerifyError),
+    Store(StoreError),
+    CorruptNameState(NameHash),
+    Pruned,
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChainError::DuplicateEntry => write!(f, "Entry already exists in the chain"),
+            ChainError::Orphan => write!(f, "Previous block is not in the chain"),
+            ChainError::HighHash => write!(f, "Proof of work failed"),
+            ChainError::BadDiffBits => write!(f, "Incorrect proof of work bits"),
+            ChainError::TimeTooOld => write!(f, "Block timestamp is not above median time past"),
+            ChainError::TimeTooNew => write!(f, "Block timestamp is too far in the future"),
+            ChainError::Verify(ref e) => write!(f, "{}", e),
+            ChainError::Store(ref e) => write!(f, "{}", e),
+            ChainError::CorruptNameState(ref hash) => {
+                write!(f, "Name state could not be decoded: {}", hash)
+            }
+            ChainError::Pruned => write!(f, "Block data has been pruned"),
+        }
+    }
+}
+
+impl From<VerifyError> for ChainError {
+    fn from(e: VerifyError) -> Self {
+        ChainError::Verify(e)
+    }
+}
+
+impl From<StoreError> for ChainError {
+    fn from(e: StoreError) -> Self {
+        ChainError::Store(e)
+    }
+}
@@ -0,0 +1,20 @@
+use crate::ChainEntry;
+use handshake_primitives::Block;
+
+/// Notifications sent to chain subscribers as the best chain changes.
+///
+/// Entries that were only ever added as headers are sent with a block that has no transactions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainEvent {
+    /// A block was added to the tip of the best chain.
+    Connect(ChainEntry, Block),
+    /// A block was removed from the tip of the best chain.
+    Disconnect(ChainEntry, Block),
+    /// The best chain switched branches. Sent after the old branch has been disconnected and the
+    /// new branch connected up to, but not including, `new_tip`.
+    Reorg {
+        old_tip: ChainEntry,
+        new_tip: ChainEntry,
+        fork: ChainEntry,
+    },
+}
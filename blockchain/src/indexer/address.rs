@@ -0,0 +1,221 @@
+use super::{read_records, Index};
+use crate::chain::corrupt;
+use crate::{layout, ChainEntry, Result};
+use extended_primitives::Hash;
+use handshake_encoding::Encodable;
+use handshake_primitives::{Address, Block, Outpoint, Output, Transaction};
+use handshake_store::{Batch, KvRead, KvStore};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+//Outputs paying an address, by address, height and outpoint.
+const OUTPUT: u8 = b'o';
+//Transactions paying or spending from an address, by address, height and txid.
+const TX: u8 = b't';
+//The address each indexed output pays, so spends can be traced back to it.
+const COIN: u8 = b'c';
+
+/// An output paying an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressOutput {
+    pub outpoint: Outpoint,
+    /// Height of the block that created the output.
+    pub height: u32,
+    pub output: Output,
+}
+
+/// Finds the outputs paying an address and the transactions that paid or spent from it. Results
+/// come in chain order and are paged with `skip` and `limit`.
+pub struct AddressIndex {
+    db: Arc<dyn KvStore>,
+}
+
+impl AddressIndex {
+    pub fn new(db: Arc<dyn KvStore>) -> Self {
+        AddressIndex { db }
+    }
+
+    pub fn get_outputs(
+        &self,
+        address: &Address,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<AddressOutput>> {
+        let prefix = key(OUTPUT, &[&address.encode()]);
+        let records = read_records::<Output, _>(self.db.as_ref(), &prefix)?;
+
+        records
+            .into_iter()
+            .skip(skip)
+            .take(limit)
+            .map(|(key, output)| {
+                let rest = &key[prefix.len()..];
+                if rest.len() != 40 {
+                    return Err(corrupt("Bad address index key"));
+                }
+
+                Ok(AddressOutput {
+                    outpoint: Outpoint::new(read_hash(&rest[4..36]), read_u32(&rest[36..])),
+                    height: read_u32(&rest[..4]),
+                    output,
+                })
+            })
+            .collect()
+    }
+
+    pub fn get_txids(&self, address: &Address, skip: usize, limit: usize) -> Result<Vec<Hash>> {
+        let prefix = key(TX, &[&address.encode()]);
+
+        self.db
+            .prefix(&prefix)?
+            .into_iter()
+            .skip(skip)
+            .take(limit)
+            .map(|(key, _)| {
+                let rest = &key[prefix.len()..];
+                if rest.len() != 36 {
+                    return Err(corrupt("Bad address index key"));
+                }
+
+                Ok(read_hash(&rest[4..]))
+            })
+            .collect()
+    }
+
+    //Every address a transaction pays or spends from. `created` holds the addresses of outputs
+    //from earlier in the same block, which aren't in the store yet.
+    fn addresses(
+        &self,
+        tx: &Transaction,
+        created: &HashMap<Outpoint, Vec<u8>>,
+    ) -> Result<BTreeSet<Vec<u8>>> {
+        let mut addresses: BTreeSet<Vec<u8>> = tx
+            .outputs
+            .iter()
+            .map(|output| output.address.encode().to_vec())
+            .collect();
+
+        for input in tx.inputs.iter() {
+            if input.prevout.is_null() {
+                continue;
+            }
+
+            let address = match created.get(&input.prevout) {
+                Some(address) => Some(address.clone()),
+                None => self.db.get(&coin_key(&input.prevout))?,
+            };
+
+            if let Some(address) = address {
+                addresses.insert(address);
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    //Runs `f` with each transaction and the addresses it touches.
+    fn each_tx<F>(&self, block: &Block, mut f: F) -> Result<()>
+    where
+        F: FnMut(&Transaction, BTreeSet<Vec<u8>>),
+    {
+        let mut created = HashMap::new();
+
+        for tx in block.txdata.iter() {
+            let addresses = self.addresses(tx, &created)?;
+            let txid = tx.hash();
+
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let outpoint = Outpoint::new(txid, index as u32);
+                created.insert(outpoint, output.address.encode().to_vec());
+            }
+
+            f(tx, addresses);
+        }
+
+        Ok(())
+    }
+}
+
+impl Index for AddressIndex {
+    const ID: u8 = b'a';
+
+    fn connect(&self, entry: &ChainEntry, block: &Block, batch: &mut Batch) -> Result<()> {
+        let height = entry.height.to_be_bytes();
+
+        self.each_tx(block, |tx, addresses| {
+            let hash = tx.hash();
+            let txid = hash.to_array();
+
+            for address in addresses.iter() {
+                batch.put(&key(TX, &[address, &height, &txid]), &[]);
+            }
+
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let outpoint = Outpoint::new(hash, index as u32);
+                let address = output.address.encode();
+
+                batch.put(
+                    &key(
+                        OUTPUT,
+                        &[&address, &height, &txid, &(index as u32).to_be_bytes()],
+                    ),
+                    &output.encode(),
+                );
+                batch.put(&coin_key(&outpoint), &address);
+            }
+        })
+    }
+
+    fn disconnect(&self, entry: &ChainEntry, block: &Block, batch: &mut Batch) -> Result<()> {
+        let height = entry.height.to_be_bytes();
+
+        self.each_tx(block, |tx, addresses| {
+            let hash = tx.hash();
+            let txid = hash.to_array();
+
+            for address in addresses.iter() {
+                batch.delete(&key(TX, &[address, &height, &txid]));
+            }
+
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let outpoint = Outpoint::new(hash, index as u32);
+                let address = output.address.encode();
+
+                batch.delete(&key(
+                    OUTPUT,
+                    &[&address, &height, &txid, &(index as u32).to_be_bytes()],
+                ));
+                batch.delete(&coin_key(&outpoint));
+            }
+        })
+    }
+}
+
+fn key(table: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let mut key = layout::index(AddressIndex::ID, &[&[table]]);
+
+    for part in parts {
+        key.extend_from_slice(part);
+    }
+
+    key
+}
+
+fn coin_key(outpoint: &Outpoint) -> Vec<u8> {
+    key(
+        COIN,
+        &[&outpoint.txid.to_array(), &outpoint.index.to_be_bytes()],
+    )
+}
+
+fn read_hash(bytes: &[u8]) -> Hash {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(bytes);
+    Hash::from(hash)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut int = [0; 4];
+    int.copy_from_slice(bytes);
+    u32::from_be_bytes(int)
+}
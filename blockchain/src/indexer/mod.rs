@@ -0,0 +1,421 @@
+//! Optional indexes over the best chain, for explorers and wallets. Each index keeps its records
+//! under its own id in the `layout::INDEX` namespace, along with the tip it has indexed up to, so
+//! indexes can be turned on, dropped and rebuilt independently of the chain and of each other.
+
+pub mod address;
+pub mod name;
+pub mod tx;
+
+pub use address::{AddressIndex, AddressOutput};
+pub use name::{NameIndex, NameRecord};
+pub use tx::{TxIndex, TxRecord};
+
+use crate::chain::{block_or_header, decode_record};
+use crate::{layout, Chain, ChainEntry, ChainError, ChainEvent, Result};
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_primitives::Block;
+use handshake_store::{Batch, KvRead, KvStore};
+use std::sync::Arc;
+
+/// What an index records for each block. Indexes only add their writes to the batch; `Indexer`
+/// writes it along with the new tip.
+pub trait Index {
+    /// The byte after `layout::INDEX` that every key of this index starts with.
+    const ID: u8;
+
+    fn connect(&self, entry: &ChainEntry, block: &Block, batch: &mut Batch) -> Result<()>;
+
+    /// Undoes `connect` for the block at the tip of the index.
+    fn disconnect(&self, entry: &ChainEntry, block: &Block, batch: &mut Batch) -> Result<()>;
+}
+
+/// The last block an index has recorded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexTip {
+    pub hash: Hash,
+    pub height: u32,
+}
+
+impl Encodable for IndexTip {
+    fn size(&self) -> usize {
+        36
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        buffer.write_hash(self.hash);
+        buffer.write_u32(self.height);
+
+        buffer
+    }
+}
+
+impl Decodable for IndexTip {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> std::result::Result<Self, Self::Err> {
+        let hash = buffer.read_hash()?;
+        let height = buffer.read_u32()?;
+
+        Ok(IndexTip { hash, height })
+    }
+}
+
+/// Keeps an index in step with the chain. Feed it the chain's events with `handle`; an index that
+/// is new or has fallen behind ignores events that don't follow its tip until `sync` catches it
+/// up.
+pub struct Indexer<I: Index> {
+    db: Arc<dyn KvStore>,
+    index: I,
+    tip: Option<IndexTip>,
+}
+
+impl<I: Index> Indexer<I> {
+    /// Opens `index` in `db`, picking up from wherever it was left.
+    pub fn open(db: Arc<dyn KvStore>, index: I) -> Result<Self> {
+        let tip = decode_record(db.get(&layout::index_tip(I::ID))?)?;
+
+        Ok(Indexer { db, index, tip })
+    }
+
+    pub fn index(&self) -> &I {
+        &self.index
+    }
+
+    /// None until the genesis block has been indexed.
+    pub fn tip(&self) -> Option<&IndexTip> {
+        self.tip.as_ref()
+    }
+
+    /// Records a block following the tip. Returns whether it did, which it doesn't if the block
+    /// is not the next one.
+    pub fn connect(&mut self, entry: &ChainEntry, block: &Block) -> Result<bool> {
+        let follows = match self.tip {
+            Some(tip) => tip.hash == entry.prev_block,
+            None => entry.is_genesis(),
+        };
+
+        if !follows {
+            return Ok(false);
+        }
+
+        let mut batch = Batch::new();
+        self.index.connect(entry, block, &mut batch)?;
+
+        let tip = IndexTip {
+            hash: entry.hash,
+            height: entry.height,
+        };
+        self.write(batch, Some(tip))?;
+
+        Ok(true)
+    }
+
+    /// Removes the block at the tip. Returns whether it did, which it doesn't if `entry` is not
+    /// the tip.
+    pub fn disconnect(&mut self, entry: &ChainEntry, block: &Block) -> Result<bool> {
+        match self.tip {
+            Some(tip) if tip.hash == entry.hash => {}
+            _ => return Ok(false),
+        }
+
+        let mut batch = Batch::new();
+        self.index.disconnect(entry, block, &mut batch)?;
+
+        let tip = if entry.is_genesis() {
+            None
+        } else {
+            Some(IndexTip {
+                hash: entry.prev_block,
+                height: entry.height - 1,
+            })
+        };
+        self.write(batch, tip)?;
+
+        Ok(true)
+    }
+
+    pub fn handle(&mut self, event: &ChainEvent) -> Result<()> {
+        match event {
+            ChainEvent::Connect(entry, block) => {
+                self.connect(entry, block)?;
+            }
+            ChainEvent::Disconnect(entry, block) => {
+                self.disconnect(entry, block)?;
+            }
+            ChainEvent::Reorg { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    /// Brings the index up to the chain's tip, first rolling back any blocks that have left the
+    /// best chain since it last ran.
+    pub fn sync(&mut self, chain: &Chain) -> Result<()> {
+        while let Some(tip) = self.tip {
+            let entry = match chain.get_entry(&tip.hash) {
+                Some(entry) => entry.clone(),
+                //Indexed against blocks this chain has never seen, so start over.
+                None => return self.rebuild(chain),
+            };
+
+            if chain.is_main_chain(&entry) {
+                break;
+            }
+
+            let block = block_or_header(&entry, chain.get_block(&entry.hash)?);
+            self.disconnect(&entry, &block)?;
+        }
+
+        let start = self.tip.map_or(0, |tip| tip.height + 1);
+
+        //The blocks it would need to catch up on are gone.
+        if start < chain.pruned_height() {
+            return Err(ChainError::Pruned);
+        }
+
+        for height in start..=chain.height() {
+            let entry = match chain.get_entry_by_height(height) {
+                Some(entry) => entry.clone(),
+                None => break,
+            };
+
+            let block = block_or_header(&entry, chain.get_block(&entry.hash)?);
+            self.connect(&entry, &block)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops everything the index holds, including its tip. This is how an index is turned off
+    /// for good.
+    pub fn reset(&mut self) -> Result<()> {
+        let mut batch = Batch::new();
+
+        for (key, _) in self.db.prefix(&layout::index_tip(I::ID))? {
+            batch.delete(&key);
+        }
+
+        self.db.write(batch)?;
+        self.tip = None;
+
+        Ok(())
+    }
+
+    /// Indexes the whole chain again from its stored blocks.
+    pub fn rebuild(&mut self, chain: &Chain) -> Result<()> {
+        self.reset()?;
+        self.sync(chain)
+    }
+
+    fn write(&mut self, mut batch: Batch, tip: Option<IndexTip>) -> Result<()> {
+        let key = layout::index_tip(I::ID);
+
+        match tip {
+            Some(tip) => batch.put(&key, &tip.encode()),
+            None => batch.delete(&key),
+        }
+
+        self.db.write(batch)?;
+        self.tip = tip;
+
+        Ok(())
+    }
+}
+
+/// Which of the optional indexes a node keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IndexOptions {
+    pub tx: bool,
+    pub address: bool,
+    pub name: bool,
+}
+
+/// The indexes a node has turned on, driven together.
+pub struct Indexers {
+    pub tx: Option<Indexer<TxIndex>>,
+    pub address: Option<Indexer<AddressIndex>>,
+    pub name: Option<Indexer<NameIndex>>,
+}
+
+impl Indexers {
+    /// Opens the indexes turned on in `options`. Indexes that are off keep whatever they already
+    /// stored; call `reset` on them to drop it.
+    pub fn open(db: Arc<dyn KvStore>, options: IndexOptions) -> Result<Self> {
+        let tx = if options.tx {
+            Some(Indexer::open(db.clone(), TxIndex::new(db.clone()))?)
+        } else {
+            None
+        };
+
+        let address = if options.address {
+            Some(Indexer::open(db.clone(), AddressIndex::new(db.clone()))?)
+        } else {
+            None
+        };
+
+        let name = if options.name {
+            Some(Indexer::open(db.clone(), NameIndex::new(db))?)
+        } else {
+            None
+        };
+
+        Ok(Indexers { tx, address, name })
+    }
+
+    pub fn handle(&mut self, event: &ChainEvent) -> Result<()> {
+        if let Some(tx) = self.tx.as_mut() {
+            tx.handle(event)?;
+        }
+        if let Some(address) = self.address.as_mut() {
+            address.handle(event)?;
+        }
+        if let Some(name) = self.name.as_mut() {
+            name.handle(event)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn sync(&mut self, chain: &Chain) -> Result<()> {
+        if let Some(tx) = self.tx.as_mut() {
+            tx.sync(chain)?;
+        }
+        if let Some(address) = self.address.as_mut() {
+            address.sync(chain)?;
+        }
+        if let Some(name) = self.name.as_mut() {
+            name.sync(chain)?;
+        }
+
+        Ok(())
+    }
+}
+
+//Every key an index has under `prefix`, decoded as `T`.
+fn read_records<T: Decodable, S: KvRead + ?Sized>(
+    db: &S,
+    prefix: &[u8],
+) -> Result<Vec<(Vec<u8>, T)>> {
+    let mut records = Vec::new();
+
+    for (key, value) in db.prefix(prefix)? {
+        if let Some(record) = decode_record(Some(value))? {
+            records.push((key, record));
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{address, address_from, mine_block};
+    use handshake_primitives::covenants::OpenCovenant;
+    use handshake_primitives::{Covenant, Input, Outpoint, Output, Transaction};
+    use handshake_protocol::network::Network;
+    use handshake_store::MemoryStore;
+    use handshake_types::{Amount, Name};
+
+    #[test]
+    fn test_indexers() {
+        let db: Arc<dyn KvStore> = Arc::new(MemoryStore::new());
+        let mut chain = Chain::with_store(Network::Regtest, db.clone()).unwrap();
+        let options = IndexOptions {
+            tx: true,
+            address: true,
+            name: true,
+        };
+        let mut indexers = Indexers::open(db.clone(), options).unwrap();
+        indexers.sync(&chain).unwrap();
+
+        let mut events = chain.subscribe();
+
+        let genesis = chain.tip().clone();
+        let b1 = mine_block(&genesis, 0, Vec::new());
+        let reward = Outpoint::new(b1.txdata[0].hash(), 0);
+        let a1 = chain.add_block(b1).unwrap();
+        let a2 = chain.add_block(mine_block(&a1, 0, Vec::new())).unwrap();
+
+        let name: Name = "handshake".parse().unwrap();
+        let open = Covenant::Open(OpenCovenant {
+            name_hash: name.hash(),
+            height: 0,
+            name,
+        });
+        let input = Input {
+            prevout: reward,
+            ..Default::default()
+        };
+        let output = Output {
+            value: Amount::ZERO,
+            address: address_from(1),
+            covenant: open,
+        };
+        let opening = Transaction::new(0, vec![input], vec![output]);
+        let a3 = chain
+            .add_block(mine_block(&a2, 0, vec![opening.clone()]))
+            .unwrap();
+
+        while let Ok(Some(event)) = events.try_next() {
+            indexers.handle(&event).unwrap();
+        }
+
+        let txs = indexers.tx.as_ref().unwrap().index();
+        let record = TxRecord {
+            block: a3.hash,
+            height: 3,
+            index: 1,
+        };
+        assert_eq!(txs.get(&opening.hash()).unwrap(), Some(record));
+
+        let addresses = indexers.address.as_ref().unwrap().index();
+        let outputs = addresses.get_outputs(&address_from(1), 0, 10).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].outpoint, Outpoint::new(opening.hash(), 0));
+
+        //Three coinbases pay the miner, and the OPEN spends one of them.
+        let txids = addresses.get_txids(&address(), 0, 10).unwrap();
+        assert_eq!(txids.len(), 4);
+        assert!(txids.contains(&opening.hash()));
+        assert_eq!(addresses.get_txids(&address(), 1, 2).unwrap(), txids[1..3]);
+
+        let names = indexers.name.as_ref().unwrap().index();
+        let history = names.get_history(&opening.outputs[0].covenant.get_name_hash().unwrap());
+        assert_eq!(history.unwrap()[0].height, 3);
+
+        //A reorg onto a branch without the OPEN takes it back out of every index.
+        let b3 = chain.add_block(mine_block(&a2, 1, Vec::new())).unwrap();
+        let b4 = chain.add_block(mine_block(&b3, 1, Vec::new())).unwrap();
+        while let Ok(Some(event)) = events.try_next() {
+            indexers.handle(&event).unwrap();
+        }
+
+        let name_hash = opening.outputs[0].covenant.get_name_hash().unwrap();
+        let txs = indexers.tx.as_ref().unwrap();
+        assert_eq!(txs.tip().unwrap().hash, b4.hash);
+        assert_eq!(txs.index().get(&opening.hash()).unwrap(), None);
+        let addresses = indexers.address.as_ref().unwrap().index();
+        assert!(addresses
+            .get_outputs(&address_from(1), 0, 10)
+            .unwrap()
+            .is_empty());
+        let names = indexers.name.as_ref().unwrap().index();
+        assert!(names.get_history(&name_hash).unwrap().is_empty());
+
+        //An index turned on later catches up on its own, and a rebuild ends up where it was.
+        let mut late = Indexer::open(db.clone(), TxIndex::new(db.clone())).unwrap();
+        assert_eq!(late.tip().unwrap().hash, b4.hash);
+        late.reset().unwrap();
+        assert_eq!(late.tip(), None);
+        late.sync(&chain).unwrap();
+        assert_eq!(late.tip().unwrap().height, 4);
+
+        let names = indexers.name.as_mut().unwrap();
+        names.rebuild(&chain).unwrap();
+        assert_eq!(names.tip().unwrap().hash, b4.hash);
+    }
+}
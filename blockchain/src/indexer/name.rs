@@ -0,0 +1,107 @@
+use super::{read_records, Index};
+use crate::chain::corrupt;
+use crate::{layout, ChainEntry, Result};
+use extended_primitives::Hash;
+use handshake_encoding::Encodable;
+use handshake_primitives::{Block, Outpoint, Output};
+use handshake_store::{Batch, KvStore};
+use handshake_types::NameHash;
+use std::sync::Arc;
+
+/// An output carrying a covenant for a name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameRecord {
+    pub outpoint: Outpoint,
+    /// Height of the block that created the output.
+    pub height: u32,
+    pub output: Output,
+}
+
+/// The history of every name: each covenant-bearing output for it, from its OPEN or CLAIM on.
+pub struct NameIndex {
+    db: Arc<dyn KvStore>,
+}
+
+impl NameIndex {
+    pub fn new(db: Arc<dyn KvStore>) -> Self {
+        NameIndex { db }
+    }
+
+    /// Every output for the name, in chain order.
+    pub fn get_history(&self, name_hash: &NameHash) -> Result<Vec<NameRecord>> {
+        let prefix = layout::index(NameIndex::ID, &[&name_hash.to_array()]);
+
+        read_records::<Output, _>(self.db.as_ref(), &prefix)?
+            .into_iter()
+            .map(|(key, output)| {
+                let rest = &key[prefix.len()..];
+                if rest.len() != 40 {
+                    return Err(corrupt("Bad name index key"));
+                }
+
+                let mut height = [0; 4];
+                let mut txid = [0; 32];
+                let mut index = [0; 4];
+                height.copy_from_slice(&rest[..4]);
+                txid.copy_from_slice(&rest[4..36]);
+                index.copy_from_slice(&rest[36..]);
+
+                Ok(NameRecord {
+                    outpoint: Outpoint::new(Hash::from(txid), u32::from_be_bytes(index)),
+                    height: u32::from_be_bytes(height),
+                    output,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Index for NameIndex {
+    const ID: u8 = b'n';
+
+    fn connect(&self, entry: &ChainEntry, block: &Block, batch: &mut Batch) -> Result<()> {
+        for (key, output) in records(entry, block) {
+            batch.put(&key, &output.encode());
+        }
+
+        Ok(())
+    }
+
+    fn disconnect(&self, entry: &ChainEntry, block: &Block, batch: &mut Batch) -> Result<()> {
+        for (key, _) in records(entry, block) {
+            batch.delete(&key);
+        }
+
+        Ok(())
+    }
+}
+
+//Keys for the name outputs in a block, along with the outputs.
+fn records<'a>(
+    entry: &ChainEntry,
+    block: &'a Block,
+) -> impl Iterator<Item = (Vec<u8>, &'a Output)> {
+    let height = entry.height.to_be_bytes();
+
+    block.txdata.iter().flat_map(move |tx| {
+        let txid = tx.hash().to_array();
+
+        tx.outputs
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, output)| {
+                let name_hash = output.covenant.get_name_hash()?;
+                let key = layout::index(
+                    NameIndex::ID,
+                    &[
+                        &name_hash.to_array(),
+                        &height,
+                        &txid,
+                        &(index as u32).to_be_bytes(),
+                    ],
+                );
+
+                Some((key, output))
+            })
+    })
+}
@@ -0,0 +1,94 @@
+use super::Index;
+use crate::chain::decode_record;
+use crate::{layout, ChainEntry, Result};
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_primitives::Block;
+use handshake_store::{Batch, KvRead, KvStore};
+use std::sync::Arc;
+
+/// Where a transaction was confirmed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxRecord {
+    pub block: Hash,
+    pub height: u32,
+    /// Position of the transaction in the block.
+    pub index: u32,
+}
+
+impl Encodable for TxRecord {
+    fn size(&self) -> usize {
+        40
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        buffer.write_hash(self.block);
+        buffer.write_u32(self.height);
+        buffer.write_u32(self.index);
+
+        buffer
+    }
+}
+
+impl Decodable for TxRecord {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> std::result::Result<Self, Self::Err> {
+        let block = buffer.read_hash()?;
+        let height = buffer.read_u32()?;
+        let index = buffer.read_u32()?;
+
+        Ok(TxRecord {
+            block,
+            height,
+            index,
+        })
+    }
+}
+
+/// Finds the block of any confirmed transaction by its hash.
+pub struct TxIndex {
+    db: Arc<dyn KvStore>,
+}
+
+impl TxIndex {
+    pub fn new(db: Arc<dyn KvStore>) -> Self {
+        TxIndex { db }
+    }
+
+    pub fn get(&self, txid: &Hash) -> Result<Option<TxRecord>> {
+        decode_record(self.db.get(&key(txid))?)
+    }
+}
+
+fn key(txid: &Hash) -> Vec<u8> {
+    layout::index(TxIndex::ID, &[&txid.to_array()])
+}
+
+impl Index for TxIndex {
+    const ID: u8 = b't';
+
+    fn connect(&self, entry: &ChainEntry, block: &Block, batch: &mut Batch) -> Result<()> {
+        for (index, tx) in block.txdata.iter().enumerate() {
+            let record = TxRecord {
+                block: entry.hash,
+                height: entry.height,
+                index: index as u32,
+            };
+
+            batch.put(&key(&tx.hash()), &record.encode());
+        }
+
+        Ok(())
+    }
+
+    fn disconnect(&self, _entry: &ChainEntry, block: &Block, batch: &mut Batch) -> Result<()> {
+        for tx in block.txdata.iter() {
+            batch.delete(&key(&tx.hash()));
+        }
+
+        Ok(())
+    }
+}
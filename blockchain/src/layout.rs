@@ -0,0 +1,109 @@
+//! Keys the chain keeps its records under in the key-value store. Every kind of record has its own
+//! one byte prefix; `n` and `r` are taken by the name tree, `l`, `f` and `k` by the flat file index
+//! and `m` by the schema version, which share the store.
+
+use crate::Result;
+use extended_primitives::Hash;
+use handshake_primitives::Outpoint;
+use handshake_store::kv::key;
+use handshake_store::{FileType, StoreError};
+
+/// Version of the layout below. Bump it, along with a migration for existing stores, whenever a
+/// record changes shape.
+pub const VERSION: u32 = 1;
+
+/// The chain state record, written last with every block.
+pub const STATE: u8 = b's';
+
+/// The height below which main chain blocks have been pruned.
+pub const PRUNED: u8 = b'p';
+
+/// Chain entries by block hash.
+pub const ENTRY: u8 = b'e';
+
+/// Main chain block hashes by height.
+pub const HEIGHT: u8 = b'h';
+
+/// Full blocks by hash, when there are no flat files to keep them in.
+pub const BLOCK: u8 = b'b';
+
+/// Coins spent by each connected block, when there are no flat files to keep them in.
+pub const UNDO: u8 = b'u';
+
+/// Name states each connected block overwrote.
+pub const NAME_UNDO: u8 = b'v';
+
+/// Unspent outputs by outpoint.
+pub const COIN: u8 = b'c';
+
+/// The optional indexes, each under its own second byte.
+pub const INDEX: u8 = b'i';
+
+pub fn state() -> Vec<u8> {
+    vec![STATE]
+}
+
+pub fn pruned() -> Vec<u8> {
+    vec![PRUNED]
+}
+
+pub fn entry(hash: &Hash) -> Vec<u8> {
+    key(ENTRY, &[&hash.to_array()])
+}
+
+//Big endian so heights iterate in order.
+pub fn height(height: u32) -> Vec<u8> {
+    key(HEIGHT, &[&height.to_be_bytes()])
+}
+
+pub fn parse_height(key: &[u8]) -> Result<u32> {
+    if key.len() != 5 || key[0] != HEIGHT {
+        return Err(StoreError::Corrupt("Bad height key".to_string()).into());
+    }
+
+    let mut height = [0; 4];
+    height.copy_from_slice(&key[1..]);
+    Ok(u32::from_be_bytes(height))
+}
+
+pub fn block(hash: &Hash) -> Vec<u8> {
+    key(BLOCK, &[&hash.to_array()])
+}
+
+pub fn undo(hash: &Hash) -> Vec<u8> {
+    key(UNDO, &[&hash.to_array()])
+}
+
+/// Where a block or its undo coins go when they are kept in the store.
+pub fn data(file_type: FileType, hash: &Hash) -> Vec<u8> {
+    match file_type {
+        FileType::Block => block(hash),
+        FileType::Undo => undo(hash),
+    }
+}
+
+pub fn name_undo(hash: &Hash) -> Vec<u8> {
+    key(NAME_UNDO, &[&hash.to_array()])
+}
+
+pub fn coin(prevout: &Outpoint) -> Vec<u8> {
+    key(
+        COIN,
+        &[&prevout.txid.to_array(), &prevout.index.to_be_bytes()],
+    )
+}
+
+/// A key in the index with the given id. The index's tip is kept under the bare id.
+pub fn index(id: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let mut key = key(INDEX, &[&[id]]);
+
+    for part in parts {
+        key.extend_from_slice(part);
+    }
+
+    key
+}
+
+pub fn index_tip(id: u8) -> Vec<u8> {
+    index(id, &[])
+}
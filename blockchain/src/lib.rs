@@ -1 +1,32 @@
 pub mod chain;
+pub mod coins;
+pub mod entry;
+pub mod error;
+pub mod events;
+pub mod indexer;
+pub mod layout;
+pub mod mempool;
+pub mod names;
+pub mod orphans;
+pub mod reindex;
+pub mod state;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod verify;
+pub mod versionbits;
+
+pub type Result<T> = std::result::Result<T, error::ChainError>;
+
+pub use chain::Chain;
+pub use coins::{CoinEntry, CoinView, UndoCoins};
+pub use entry::ChainEntry;
+pub use error::ChainError;
+pub use events::ChainEvent;
+pub use indexer::{IndexOptions, Indexer, Indexers};
+pub use mempool::{Mempool, MempoolEntry, MempoolOptions};
+pub use names::{NameUndo, NameView};
+pub use orphans::{Orphan, OrphanPool};
+pub use reindex::{reindex, ReindexOptions, ReindexProgress};
+pub use state::ChainState;
+pub use verify::VerifyError;
+pub use versionbits::{DeploymentState, ThresholdState};
@@ -0,0 +1,907 @@
+//! Transactions waiting to be mined. Each is checked against the chain's tip and the rest of the
+//! pool when it arrives, and the pool keeps fee totals over every transaction's unconfirmed
+//! ancestors and descendants so packages can be weighed as a whole.
+//!
+//! Names add their own conflicts on top of double spends: the pool holds at most one OPEN for a
+//! name, and one transaction registering, updating or otherwise moving a name on from its owner.
+//!
+//! Claims are kept apart from the other transactions, by the name they claim, as hsd does. They
+//! are paid out by the coinbase, so they spend nothing and pay no fee, and a miner adds them to
+//! the block it builds. The pool holds one claim per name; a claim for the name confirmed in a
+//! block evicts it, along with the pool's transactions for the name.
+
+use crate::coins::CoinEntry;
+use crate::names::{check_covenants, NameView};
+use crate::verify::{check_sanity, RejectCode, VerifyError};
+use crate::{Chain, ChainError, ChainEvent, Result};
+use extended_primitives::Hash;
+use handshake_primitives::{Block, Covenant, Outpoint, Transaction};
+use handshake_protocol::consensus::{max_coin, WITNESS_SCALE_FACTOR};
+use handshake_types::{Amount, NameHash};
+use std::collections::{HashMap, HashSet};
+
+/// Lowest fee rate relayed, in doos per kilobyte of virtual size, as in hsd.
+pub const MIN_RELAY_FEE: u64 = 1000;
+
+/// Default limit on the total virtual size of the pool (100MB).
+pub const MAX_MEMPOOL_SIZE: usize = 100 * 1000 * 1000;
+
+/// Transactions still unconfirmed after this long (72 hours) are dropped, as in hsd.
+pub const MEMPOOL_EXPIRY: u64 = 72 * 60 * 60;
+
+/// Most unconfirmed ancestors a transaction may have, counting itself.
+pub const MAX_ANCESTORS: usize = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MempoolOptions {
+    /// Total virtual size the pool is trimmed back to.
+    pub max_size: usize,
+    /// Seconds a transaction may wait before it is dropped.
+    pub expiry: u64,
+    /// Doos per kilobyte of virtual size.
+    pub min_relay_fee: u64,
+    pub max_ancestors: usize,
+}
+
+impl Default for MempoolOptions {
+    fn default() -> Self {
+        MempoolOptions {
+            max_size: MAX_MEMPOOL_SIZE,
+            expiry: MEMPOOL_EXPIRY,
+            min_relay_fee: MIN_RELAY_FEE,
+            max_ancestors: MAX_ANCESTORS,
+        }
+    }
+}
+
+/// A transaction in the pool. The ancestor and descendant totals cover every unconfirmed
+/// transaction it depends on, or that depends on it, including itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolEntry {
+    pub tx: Transaction,
+    pub txid: Hash,
+    pub fee: Amount,
+    /// Virtual size: the weight over the witness scale factor, rounded up.
+    pub size: usize,
+    /// Height of the block the transaction was checked for.
+    pub height: u32,
+    /// When the transaction entered the pool.
+    pub time: u64,
+    pub ancestor_count: usize,
+    pub ancestor_size: usize,
+    pub ancestor_fee: u64,
+    pub descendant_count: usize,
+    pub descendant_size: usize,
+    pub descendant_fee: u64,
+}
+
+impl MempoolEntry {
+    /// Doos per kilobyte of virtual size.
+    pub fn fee_rate(&self) -> u64 {
+        fee_rate(self.fee.as_doos(), self.size)
+    }
+
+    /// The fee rate of the transaction together with everything spending from it, which is what
+    /// evicting it would give up.
+    pub fn descendant_rate(&self) -> u64 {
+        fee_rate(self.descendant_fee, self.descendant_size)
+    }
+}
+
+/// Unconfirmed transactions, checked against the tip of a chain. The pool doesn't hold on to
+/// the chain: it is passed in whenever the pool needs it, and `handle` keeps the pool in step
+/// with the chain's events.
+#[derive(Debug, Default)]
+pub struct Mempool {
+    options: MempoolOptions,
+    entries: HashMap<Hash, MempoolEntry>,
+    //The pool transaction spending each outpoint.
+    spents: HashMap<Outpoint, Hash>,
+    //The pool transaction holding a lock on each name, see `name_locks`.
+    names: HashMap<NameHash, Hash>,
+    //Claims waiting for a block, by the name they claim.
+    claims: HashMap<NameHash, Transaction>,
+    //Total virtual size of the pool.
+    size: usize,
+}
+
+impl Mempool {
+    pub fn new(options: MempoolOptions) -> Self {
+        Mempool {
+            options,
+            ..Default::default()
+        }
+    }
+
+    pub fn options(&self) -> &MempoolOptions {
+        &self.options
+    }
+
+    pub fn get(&self, txid: &Hash) -> Option<&MempoolEntry> {
+        self.entries.get(txid)
+    }
+
+    pub fn has(&self, txid: &Hash) -> bool {
+        self.entries.contains_key(txid)
+    }
+
+    /// The pool transaction spending `outpoint`, if any.
+    pub fn get_spender(&self, outpoint: &Outpoint) -> Option<Hash> {
+        self.spents.get(outpoint).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total virtual size of the pool.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The claim waiting for a block for `name_hash`, if any.
+    pub fn get_claim(&self, name_hash: &NameHash) -> Option<&Transaction> {
+        self.claims.get(name_hash)
+    }
+
+    /// Every claim waiting for a block, for a miner to add to its coinbase.
+    pub fn claims(&self) -> Vec<&Transaction> {
+        self.claims.values().collect()
+    }
+
+    /// Every txid in the pool, e.g. to answer a `mempool` request.
+    pub fn txids(&self) -> Vec<Hash> {
+        self.entries.keys().copied().collect()
+    }
+
+    /// Checks a transaction against the chain's tip and the pool, and adds it. Inputs must be
+    /// confirmed or in the pool already; orphan transactions are refused. Claims go to
+    /// `add_claim`. Returns the txid.
+    pub fn add_tx(&mut self, chain: &Chain, tx: Transaction) -> Result<Hash> {
+        let txid = tx.hash();
+        let height = chain.height() + 1;
+        let network = chain.network();
+
+        if self.entries.contains_key(&txid) {
+            return Err(reject(RejectCode::Duplicate, "txn-already-in-mempool"));
+        }
+
+        check_sanity(&tx)?;
+
+        if tx.is_coinbase() {
+            if claim_name(&tx).is_some() {
+                return self.add_claim(chain, tx);
+            }
+
+            return Err(VerifyError::invalid("coinbase", 100).into());
+        }
+
+        if !tx.is_final(height, chain.get_median_time(chain.tip())) {
+            return Err(reject(RejectCode::Nonstandard, "non-final"));
+        }
+
+        for input in tx.inputs.iter() {
+            if self.spents.contains_key(&input.prevout) {
+                return Err(reject(RejectCode::Duplicate, "txn-mempool-conflict"));
+            }
+        }
+
+        for (name_hash, reason) in name_locks(&tx) {
+            if self.names.contains_key(&name_hash) {
+                return Err(reject(RejectCode::Duplicate, reason));
+            }
+        }
+
+        let mut coins = Vec::new();
+        let mut value = Amount::ZERO;
+
+        for input in tx.inputs.iter() {
+            let prevout = &input.prevout;
+
+            let coin = match self.entries.get(&prevout.txid) {
+                Some(parent) => parent
+                    .tx
+                    .outputs
+                    .get(prevout.index as usize)
+                    .map(|output| CoinEntry::new(output.clone(), height, false)),
+                None => chain.coins().get(prevout)?,
+            };
+
+            let coin = match coin {
+                Some(coin) => coin,
+                None => {
+                    return Err(VerifyError::invalid("bad-txns-inputs-missingorspent", 0).into())
+                }
+            };
+
+            if !coin.is_mature(height, network.coinbase_maturity()) {
+                return Err(VerifyError::invalid("bad-txns-premature-spend-of-coinbase", 0).into());
+            }
+
+            value = match value.checked_add(coin.value()) {
+                Some(value) if value.as_doos() <= max_coin().as_doos() => value,
+                _ => {
+                    return Err(VerifyError::invalid("bad-txns-inputvalues-outofrange", 100).into())
+                }
+            };
+
+            coins.push(coin);
+        }
+
+        check_covenants(&tx, &coins)?;
+
+        NameView::new().connect_tx(chain.tree(), &tx, height, network, |hash| {
+            chain.verify_renewal(hash, height)
+        })?;
+
+        let out = tx.get_output_value().unwrap_or(Amount::ZERO);
+        let fee = match value.checked_sub(out) {
+            Some(fee) => fee,
+            None => return Err(VerifyError::invalid("bad-txns-in-belowout", 100).into()),
+        };
+
+        let size = virtual_size(&tx);
+        if fee_rate(fee.as_doos(), size) < self.options.min_relay_fee {
+            return Err(reject(RejectCode::InsufficientFee, "insufficient fee"));
+        }
+
+        let ancestors = self.ancestors(&tx);
+        if ancestors.len() + 1 > self.options.max_ancestors {
+            return Err(reject(RejectCode::Nonstandard, "too-long-mempool-chain"));
+        }
+
+        let mut entry = MempoolEntry {
+            tx,
+            txid,
+            fee,
+            size,
+            height,
+            time: chain.time_data().now(),
+            ancestor_count: 1,
+            ancestor_size: size,
+            ancestor_fee: fee.as_doos(),
+            descendant_count: 1,
+            descendant_size: size,
+            descendant_fee: fee.as_doos(),
+        };
+
+        for ancestor in ancestors.iter() {
+            let ancestor = self
+                .entries
+                .get_mut(ancestor)
+                .expect("ancestors are in the pool");
+
+            entry.ancestor_count += 1;
+            entry.ancestor_size += ancestor.size;
+            entry.ancestor_fee += ancestor.fee.as_doos();
+
+            ancestor.descendant_count += 1;
+            ancestor.descendant_size += size;
+            ancestor.descendant_fee += fee.as_doos();
+        }
+
+        for input in entry.tx.inputs.iter() {
+            self.spents.insert(input.prevout, txid);
+        }
+
+        for (name_hash, _) in name_locks(&entry.tx) {
+            self.names.insert(name_hash, txid);
+        }
+
+        self.size += size;
+        let now = entry.time;
+        self.entries.insert(txid, entry);
+
+        if !self.children(&txid).is_empty() {
+            self.update_totals();
+        }
+
+        if self.size > self.options.max_size {
+            self.limit_size(now);
+
+            if !self.entries.contains_key(&txid) {
+                return Err(reject(RejectCode::InsufficientFee, "mempool full"));
+            }
+        }
+
+        Ok(txid)
+    }
+
+    /// Checks a claim against the name tree at the chain's tip, and adds it. A claim spends
+    /// nothing: it is a coinbase-style transaction whose only output claims a reserved name for
+    /// the next block. Only one claim per name is held. Returns the claim's txid.
+    pub fn add_claim(&mut self, chain: &Chain, tx: Transaction) -> Result<Hash> {
+        let txid = tx.hash();
+        let height = chain.height() + 1;
+        let network = chain.network();
+
+        check_sanity(&tx)?;
+
+        let name_hash = match claim_name(&tx) {
+            Some(name_hash) => name_hash,
+            None => return Err(VerifyError::invalid("bad-claim", 100).into()),
+        };
+
+        if self.claims.contains_key(&name_hash) {
+            return Err(reject(RejectCode::Duplicate, "txn-mempool-claim-conflict"));
+        }
+
+        check_covenants(&tx, &[])?;
+
+        NameView::new().connect_tx(chain.tree(), &tx, height, network, |hash| {
+            chain.verify_renewal(hash, height)
+        })?;
+
+        self.claims.insert(name_hash, tx);
+
+        Ok(txid)
+    }
+
+    /// Removes a transaction along with everything spending from it. Returns the number of
+    /// transactions removed.
+    pub fn remove_tx(&mut self, txid: &Hash) -> usize {
+        if !self.entries.contains_key(txid) {
+            return 0;
+        }
+
+        let mut txids: Vec<Hash> = self.descendants(txid).into_iter().collect();
+        txids.push(*txid);
+
+        for txid in txids.iter() {
+            self.unlink(txid);
+        }
+
+        txids.len()
+    }
+
+    /// Drops transactions that have waited longer than the expiry, with their descendants.
+    /// Returns the number of transactions removed.
+    pub fn expire(&mut self, now: u64) -> usize {
+        let expiry = self.options.expiry;
+        let stale: Vec<Hash> = self
+            .entries
+            .values()
+            .filter(|entry| now.saturating_sub(entry.time) > expiry)
+            .map(|entry| entry.txid)
+            .collect();
+
+        stale.iter().map(|txid| self.remove_tx(txid)).sum()
+    }
+
+    /// Takes out the transactions a block confirmed, and those it made invalid: transactions
+    /// spending the same coins or holding a lock on a name the block touched.
+    pub fn remove_block(&mut self, block: &Block) {
+        for tx in block.txdata.iter() {
+            let txid = tx.hash();
+
+            //Whatever spends it stays, now with one less unconfirmed ancestor.
+            if self.entries.contains_key(&txid) {
+                self.unlink(&txid);
+                continue;
+            }
+
+            if !tx.is_coinbase() {
+                for input in tx.inputs.iter() {
+                    if let Some(spender) = self.get_spender(&input.prevout) {
+                        self.remove_tx(&spender);
+                    }
+                }
+            }
+
+            let claims = tx
+                .outputs
+                .iter()
+                .filter_map(|output| match output.covenant {
+                    Covenant::Claim(ref claim) => Some(claim.name_hash),
+                    _ => None,
+                });
+            let names: Vec<NameHash> = name_locks(tx)
+                .into_iter()
+                .map(|(name_hash, _)| name_hash)
+                .chain(claims)
+                .collect();
+
+            for name_hash in names.iter() {
+                if let Some(holder) = self.names.get(name_hash).copied() {
+                    self.remove_tx(&holder);
+                }
+
+                self.claims.remove(name_hash);
+            }
+        }
+    }
+
+    /// Keeps the pool in step with the chain. Transactions from disconnected blocks are offered
+    /// back to the pool, and dropped if they no longer fit. Claims are only valid in the block
+    /// they were made for, so they go once the chain has moved past it.
+    pub fn handle(&mut self, chain: &Chain, event: &ChainEvent) -> Result<()> {
+        match event {
+            ChainEvent::Connect(entry, block) => {
+                self.remove_block(block);
+                self.claims
+                    .retain(|_, claim| claim_height(claim) > Some(entry.height));
+            }
+            ChainEvent::Disconnect(_, block) => {
+                for tx in block.txdata.iter().skip(1) {
+                    match self.add_tx(chain, tx.clone()) {
+                        Ok(_) => {}
+                        Err(ChainError::Store(e)) => return Err(e.into()),
+                        Err(_) => {}
+                    }
+                }
+            }
+            ChainEvent::Reorg { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    //Brings the pool back under its size limit. Stale transactions go first, then the ones
+    //paying the least per byte along with everything spending from them.
+    fn limit_size(&mut self, now: u64) {
+        self.expire(now);
+
+        while self.size > self.options.max_size {
+            let worst = self
+                .entries
+                .values()
+                .min_by_key(|entry| entry.descendant_rate())
+                .map(|entry| entry.txid);
+
+            match worst {
+                Some(txid) => self.remove_tx(&txid),
+                None => break,
+            };
+        }
+    }
+
+    //Takes a single transaction out of the pool and out of its relatives' totals, leaving any
+    //descendants in place.
+    fn unlink(&mut self, txid: &Hash) {
+        let (size, fee, ancestors) = match self.entries.get(txid) {
+            Some(entry) => (entry.size, entry.fee.as_doos(), self.ancestors(&entry.tx)),
+            None => return,
+        };
+
+        for ancestor in ancestors.iter() {
+            if let Some(ancestor) = self.entries.get_mut(ancestor) {
+                ancestor.descendant_count -= 1;
+                ancestor.descendant_size -= size;
+                ancestor.descendant_fee -= fee;
+            }
+        }
+
+        for descendant in self.descendants(txid).iter() {
+            if let Some(descendant) = self.entries.get_mut(descendant) {
+                descendant.ancestor_count -= 1;
+                descendant.ancestor_size -= size;
+                descendant.ancestor_fee -= fee;
+            }
+        }
+
+        let entry = self.entries.remove(txid).expect("entry was just found");
+
+        for input in entry.tx.inputs.iter() {
+            self.spents.remove(&input.prevout);
+        }
+
+        for (name_hash, _) in name_locks(&entry.tx) {
+            if self.names.get(&name_hash) == Some(txid) {
+                self.names.remove(&name_hash);
+            }
+        }
+
+        self.size -= entry.size;
+    }
+
+    //Works every entry's ancestor and descendant totals out again. Only needed when a transaction
+    //comes back to the pool after transactions spending from it, as when a block is disconnected.
+    fn update_totals(&mut self) {
+        let txids = self.txids();
+
+        for txid in txids.iter() {
+            let entry = &self.entries[txid];
+            let ancestors = self.ancestors(&entry.tx);
+            let descendants = self.descendants(txid);
+
+            let sum = |txids: &HashSet<Hash>| {
+                txids
+                    .iter()
+                    .fold((1, entry.size, entry.fee.as_doos()), |total, txid| {
+                        let relative = &self.entries[txid];
+                        (
+                            total.0 + 1,
+                            total.1 + relative.size,
+                            total.2 + relative.fee.as_doos(),
+                        )
+                    })
+            };
+
+            let (ancestor_count, ancestor_size, ancestor_fee) = sum(&ancestors);
+            let (descendant_count, descendant_size, descendant_fee) = sum(&descendants);
+
+            let entry = self.entries.get_mut(txid).expect("txid is in the pool");
+            entry.ancestor_count = ancestor_count;
+            entry.ancestor_size = ancestor_size;
+            entry.ancestor_fee = ancestor_fee;
+            entry.descendant_count = descendant_count;
+            entry.descendant_size = descendant_size;
+            entry.descendant_fee = descendant_fee;
+        }
+    }
+
+    //Every pool transaction `tx` spends from, directly or not.
+    fn ancestors(&self, tx: &Transaction) -> HashSet<Hash> {
+        let mut ancestors = HashSet::new();
+        let mut queue = self.parents(tx);
+
+        while let Some(txid) = queue.pop() {
+            if !ancestors.insert(txid) {
+                continue;
+            }
+
+            if let Some(entry) = self.entries.get(&txid) {
+                queue.extend(self.parents(&entry.tx));
+            }
+        }
+
+        ancestors
+    }
+
+    //Every pool transaction spending from `txid`, directly or not.
+    fn descendants(&self, txid: &Hash) -> HashSet<Hash> {
+        let mut descendants = HashSet::new();
+        let mut queue = self.children(txid);
+
+        while let Some(txid) = queue.pop() {
+            if descendants.insert(txid) {
+                queue.extend(self.children(&txid));
+            }
+        }
+
+        descendants
+    }
+
+    fn parents(&self, tx: &Transaction) -> Vec<Hash> {
+        tx.inputs
+            .iter()
+            .map(|input| input.prevout.txid)
+            .filter(|txid| self.entries.contains_key(txid))
+            .collect()
+    }
+
+    fn children(&self, txid: &Hash) -> Vec<Hash> {
+        let outputs = match self.entries.get(txid) {
+            Some(entry) => entry.tx.outputs.len(),
+            None => return Vec::new(),
+        };
+
+        (0..outputs)
+            .filter_map(|index| self.get_spender(&Outpoint::new(*txid, index as u32)))
+            .collect()
+    }
+}
+
+//The names a transaction locks in the pool, with the reason a second transaction for the name
+//is refused. Bids, reveals and redeems don't lock anything: a name can have any number pending.
+fn name_locks(tx: &Transaction) -> Vec<(NameHash, &'static str)> {
+    tx.outputs
+        .iter()
+        .filter_map(|output| {
+            let reason = match output.covenant {
+                Covenant::Open(_) => "txn-mempool-open-conflict",
+                Covenant::Register(_)
+                | Covenant::Update(_)
+                | Covenant::Renew(_)
+                | Covenant::Transfer(_)
+                | Covenant::Finalize(_)
+                | Covenant::Revoke(_) => "txn-mempool-name-conflict",
+                _ => return None,
+            };
+
+            Some((output.covenant.get_name_hash()?, reason))
+        })
+        .collect()
+}
+
+//The name a claim transaction claims. Claims have a coinbase input and a single output.
+fn claim_name(tx: &Transaction) -> Option<NameHash> {
+    if !tx.is_coinbase() {
+        return None;
+    }
+
+    match tx.outputs.as_slice() {
+        [output] => match output.covenant {
+            Covenant::Claim(ref claim) => Some(claim.name_hash),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+//The height of the block a claim was made for.
+fn claim_height(tx: &Transaction) -> Option<u32> {
+    match tx.outputs.first()?.covenant {
+        Covenant::Claim(ref claim) => Some(claim.height),
+        _ => None,
+    }
+}
+
+fn virtual_size(tx: &Transaction) -> usize {
+    (tx.get_weight() + WITNESS_SCALE_FACTOR - 1) / WITNESS_SCALE_FACTOR
+}
+
+fn fee_rate(fee: u64, size: usize) -> u64 {
+    if size == 0 {
+        return 0;
+    }
+
+    fee * 1000 / size as u64
+}
+
+//Policy rejects don't reflect on the peer that sent the transaction.
+fn reject(code: RejectCode, reason: &'static str) -> ChainError {
+    VerifyError {
+        code,
+        reason,
+        score: 0,
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{coinbase_paying, seal, spend_paying};
+    use crate::ChainEntry;
+    use extended_primitives::Buffer;
+    use handshake_primitives::covenants::{ClaimCovenant, OpenCovenant, UpdateCovenant};
+    use handshake_protocol::network::Network;
+    use handshake_types::Name;
+
+    //Enough in each coinbase to pay fees with.
+    const REWARD: u64 = 1_000_000;
+
+    fn coinbase(height: u32) -> Transaction {
+        coinbase_paying(height, REWARD)
+    }
+
+    fn mine(prev: &ChainEntry, txs: Vec<Transaction>) -> Block {
+        let mut txdata = vec![coinbase(prev.height + 1)];
+        txdata.extend(txs);
+        seal(prev, 0, txdata)
+    }
+
+    //A chain whose first three coinbases are mature.
+    fn setup() -> Chain {
+        let mut chain = Chain::new(Network::Regtest);
+
+        for _ in 0..4 {
+            let tip = chain.tip().clone();
+            chain.add_block(mine(&tip, Vec::new())).unwrap();
+        }
+
+        chain
+    }
+
+    fn reward(height: u32) -> Outpoint {
+        Outpoint::new(coinbase(height).hash(), 0)
+    }
+
+    fn reason(result: Result<Hash>) -> &'static str {
+        match result {
+            Err(ChainError::Verify(e)) => e.reason,
+            other => panic!("expected a verify error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_add_tx() {
+        let mut chain = setup();
+        let mut pool = Mempool::default();
+
+        let parent = spend_paying(reward(1), REWARD - 1000);
+        let child = spend_paying(Outpoint::new(parent.hash(), 0), REWARD - 3000);
+        pool.add_tx(&chain, parent.clone()).unwrap();
+        pool.add_tx(&chain, child.clone()).unwrap();
+
+        let entry = pool.get(&parent.hash()).unwrap();
+        assert_eq!(entry.fee, Amount::from_doos(1000));
+        assert_eq!(entry.descendant_count, 2);
+        assert_eq!(entry.descendant_fee, 3000);
+        let entry = pool.get(&child.hash()).unwrap();
+        assert_eq!(entry.ancestor_count, 2);
+        assert_eq!(entry.ancestor_size, pool.size());
+
+        assert_eq!(
+            reason(pool.add_tx(&chain, parent.clone())),
+            "txn-already-in-mempool"
+        );
+        assert_eq!(
+            reason(pool.add_tx(&chain, spend_paying(reward(1), 0))),
+            "txn-mempool-conflict"
+        );
+        assert_eq!(
+            reason(pool.add_tx(&chain, spend_paying(reward(10), 0))),
+            "bad-txns-inputs-missingorspent"
+        );
+        assert_eq!(
+            reason(pool.add_tx(&chain, spend_paying(reward(4), 0))),
+            "bad-txns-premature-spend-of-coinbase"
+        );
+        assert_eq!(
+            reason(pool.add_tx(&chain, spend_paying(reward(2), REWARD - 1))),
+            "insufficient fee"
+        );
+        assert_eq!(reason(pool.add_tx(&chain, coinbase(5))), "coinbase");
+
+        //Confirming the parent leaves the child with no unconfirmed ancestors. A block spending
+        //the same coin as a pool transaction evicts it, along with its descendants.
+        let conflict = spend_paying(reward(2), REWARD - 1000);
+        pool.add_tx(&chain, conflict).unwrap();
+
+        let tip = chain.tip().clone();
+        let block = mine(&tip, vec![parent.clone(), spend_paying(reward(2), REWARD)]);
+        let entry = chain.add_block(block.clone()).unwrap();
+        pool.handle(&chain, &ChainEvent::Connect(entry.clone(), block.clone()))
+            .unwrap();
+
+        assert_eq!(pool.len(), 1);
+        let remaining = pool.get(&child.hash()).unwrap();
+        assert_eq!(remaining.ancestor_count, 1);
+        assert_eq!(remaining.ancestor_size, remaining.size);
+        assert_eq!(pool.get_spender(&reward(1)), None);
+
+        //Reorganizing the block out offers its transactions to the pool again. The parent
+        //comes back under the child it left behind.
+        let fork = chain.add_block(mine(&tip, Vec::new())).unwrap();
+        chain.add_block(mine(&fork, Vec::new())).unwrap();
+        pool.handle(&chain, &ChainEvent::Disconnect(entry, block))
+            .unwrap();
+
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.get(&parent.hash()).unwrap().descendant_count, 2);
+        assert_eq!(pool.get(&child.hash()).unwrap().ancestor_fee, 3000);
+    }
+
+    fn open(prevout: Outpoint, name: &Name) -> Transaction {
+        let mut tx = spend_paying(prevout, REWARD - 1000);
+        tx.outputs[0].covenant = Covenant::Open(OpenCovenant {
+            name_hash: name.hash(),
+            height: 0,
+            name: name.clone(),
+        });
+        tx
+    }
+
+    fn claim(height: u32, name: &Name) -> Transaction {
+        let mut tx = coinbase(0);
+        tx.outputs[0].covenant = Covenant::Claim(ClaimCovenant {
+            name_hash: name.hash(),
+            height,
+            name: name.clone(),
+            flags: 0,
+            commit_hash: Hash::default(),
+            commit_height: 0,
+        });
+        tx
+    }
+
+    #[test]
+    fn test_name_conflicts() {
+        let chain = setup();
+        let mut pool = Mempool::default();
+        let name: Name = "handshake".parse().unwrap();
+        let other: Name = "example".parse().unwrap();
+
+        pool.add_tx(&chain, open(reward(1), &name)).unwrap();
+        assert_eq!(
+            reason(pool.add_tx(&chain, open(reward(2), &name))),
+            "txn-mempool-open-conflict"
+        );
+        pool.add_tx(&chain, open(reward(2), &other)).unwrap();
+
+        //A claim confirmed for the name evicts the pool's OPEN for it.
+        let claimed = claim(chain.height() + 1, &other);
+        let tip = chain.tip().clone();
+        let mut block = mine(&tip, Vec::new());
+        block.txdata[0] = claimed;
+        pool.remove_block(&block);
+        assert!(!pool.has(&open(reward(2), &other).hash()));
+
+        //Anything moving a name along from its owner takes the same lock.
+        let mut update = spend_paying(reward(3), 0);
+        update.outputs[0].covenant = Covenant::Update(UpdateCovenant {
+            name_hash: name.hash(),
+            height: 0,
+            record_data: Buffer::new(),
+        });
+        assert_eq!(
+            name_locks(&update),
+            vec![(name.hash(), "txn-mempool-name-conflict")]
+        );
+        assert_eq!(
+            reason(pool.add_tx(&chain, update)),
+            "txn-mempool-name-conflict"
+        );
+
+        //Removing the OPEN frees the name.
+        pool.remove_tx(&open(reward(1), &name).hash());
+        pool.add_tx(&chain, open(reward(3), &name)).unwrap();
+    }
+
+    #[test]
+    fn test_claims() {
+        //Regtest reserves no names, so claims need mainnet.
+        let chain = Chain::new(Network::Mainnet);
+        let mut pool = Mempool::default();
+        let name: Name = "arpa".parse().unwrap();
+
+        let first = claim(1, &name);
+        assert_eq!(pool.add_tx(&chain, first.clone()), Ok(first.hash()));
+        assert_eq!(pool.get_claim(&name.hash()), Some(&first));
+        assert_eq!(pool.claims(), vec![&first]);
+        assert!(pool.is_empty());
+
+        //One claim per name, even one paying out differently.
+        let mut second = claim(1, &name);
+        second.outputs[0].value = Amount::from_doos(1);
+        assert_eq!(
+            reason(pool.add_claim(&chain, second.clone())),
+            "txn-mempool-claim-conflict"
+        );
+
+        let other: Name = "handshake".parse().unwrap();
+        assert_eq!(
+            reason(pool.add_claim(&chain, claim(1, &other))),
+            "bad-claim-notreserved"
+        );
+
+        //A block confirming any claim for the name evicts ours.
+        let tip = chain.tip().clone();
+        let mut block = mine(&tip, Vec::new());
+        block.txdata[0] = second;
+        pool.remove_block(&block);
+        assert_eq!(pool.get_claim(&name.hash()), None);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let chain = setup();
+        let size = virtual_size(&spend_paying(reward(1), 0));
+        let mut pool = Mempool::new(MempoolOptions {
+            max_size: size * 2,
+            ..Default::default()
+        });
+
+        let cheap = pool
+            .add_tx(&chain, spend_paying(reward(1), REWARD - 1000))
+            .unwrap();
+        let rich = pool
+            .add_tx(&chain, spend_paying(reward(2), REWARD - 5000))
+            .unwrap();
+
+        //Over the limit, the cheapest goes to make room.
+        let middle = pool
+            .add_tx(&chain, spend_paying(reward(3), REWARD - 3000))
+            .unwrap();
+        assert!(!pool.has(&cheap));
+        assert!(pool.has(&rich) && pool.has(&middle));
+        assert_eq!(pool.size(), size * 2);
+
+        //Unless the newcomer is the cheapest.
+        let child = spend_paying(Outpoint::new(rich, 0), REWARD - 5000 - 500);
+        assert_eq!(reason(pool.add_tx(&chain, child)), "mempool full");
+        assert_eq!(pool.len(), 2);
+
+        let now = chain.time_data().now();
+        assert_eq!(pool.expire(now), 0);
+        assert_eq!(pool.expire(now + MEMPOOL_EXPIRY + 1), 2);
+        assert!(pool.is_empty());
+        assert_eq!(pool.size(), 0);
+    }
+}
@@ -0,0 +1,918 @@
+use crate::coins::CoinEntry;
+use crate::verify::{VerifyError, VerifyResult};
+use crate::{ChainError, Result};
+use cryptoxide::blake2b::Blake2b;
+use cryptoxide::digest::Digest;
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_primitives::{AuctionState, Covenant, NameState, Outpoint, Output, Transaction};
+use handshake_protocol::network::Network;
+use handshake_store::Tree;
+use handshake_types::{Amount, NameHash};
+use std::collections::HashMap;
+
+/// The commitment a BID hides its value behind, blake2b(value || nonce). The REVEAL that spends
+/// the bid has to open it.
+pub fn blind(value: Amount, nonce: &Hash) -> Hash {
+    let mut buffer = Buffer::new();
+    buffer.write_u64(value.as_doos());
+    buffer.write_hash(*nonce);
+
+    let mut sh = Blake2b::new(32);
+    let mut output = [0; 32];
+    sh.input(&buffer);
+    sh.result(&mut output);
+
+    Hash::from(output)
+}
+
+/// Checks each output's covenant against the covenant of the coin spent at the same index. This
+/// only looks at the transaction itself, the name state checks happen in `NameView`.
+pub fn check_covenants(tx: &Transaction, coins: &[CoinEntry]) -> VerifyResult<()> {
+    if !verify_covenants(tx, coins) {
+        return Err(VerifyError::invalid("bad-txns-invalid-covenant", 100));
+    }
+
+    Ok(())
+}
+
+fn verify_covenants(tx: &Transaction, coins: &[CoinEntry]) -> bool {
+    if !tx
+        .outputs
+        .iter()
+        .all(|output| name_matches(&output.covenant))
+    {
+        return false;
+    }
+
+    //Claims are paid out by the coinbase, which never links to anything.
+    if tx.is_coinbase() {
+        return tx.outputs.iter().all(|output| match output.covenant {
+            Covenant::None | Covenant::Claim(_) => true,
+            _ => false,
+        });
+    }
+
+    for (index, output) in tx.outputs.iter().enumerate() {
+        match &output.covenant {
+            Covenant::Claim(_) => return false,
+            //Opens start a new auction.
+            Covenant::Open(open) if open.height != 0 => return false,
+            //Every other linked covenant must have a coin to continue from.
+            covenant if covenant.is_linked() && index >= coins.len() => return false,
+            _ => {}
+        }
+    }
+
+    for (index, coin) in coins.iter().enumerate() {
+        if !verify_link(coin, tx.outputs.get(index)) {
+            return false;
+        }
+    }
+
+    true
+}
+
+//Whether a covenant carrying its name carries the name its hash is for. Otherwise the name rules
+//would be checked against one name and applied to another.
+fn name_matches(covenant: &Covenant) -> bool {
+    match (covenant.get_name(), covenant.get_name_hash()) {
+        (Some(name), Some(name_hash)) => name.hash() == name_hash,
+        _ => true,
+    }
+}
+
+//Whether spending `coin` into `output` (the output at the same index, if any) is a valid step
+//in the name's lifecycle.
+fn verify_link(coin: &CoinEntry, output: Option<&Output>) -> bool {
+    let spent = coin.covenant();
+
+    let output = match spent {
+        //Coins that don't hold a name can be spent anywhere, but can't move a name along.
+        Covenant::None | Covenant::Open(_) | Covenant::Redeem(_) => {
+            return output.map_or(true, |output| !output.covenant.is_linked());
+        }
+        Covenant::Revoke(_) => return false,
+        _ => match output {
+            Some(output) => output,
+            None => return false,
+        },
+    };
+
+    let covenant = &output.covenant;
+
+    if covenant.get_name_hash() != spent.get_name_hash()
+        || covenant.get_height() != spent.get_height()
+    {
+        return false;
+    }
+
+    let same_owner = output.value == coin.value() && output.address == *coin.address();
+
+    match (spent, covenant) {
+        (Covenant::Bid(bid), Covenant::Reveal(reveal)) => {
+            //A reveal can release the part of the lockup that was not bid.
+            blind(output.value, &reveal.nonce) == bid.hash
+                && output.value.as_doos() <= coin.value().as_doos()
+        }
+        //Winners register, losers redeem. Claims always win.
+        (Covenant::Claim(_), Covenant::Register(_)) => true,
+        (Covenant::Reveal(_), Covenant::Register(_))
+        | (Covenant::Reveal(_), Covenant::Redeem(_)) => true,
+        (Covenant::Register(_), next)
+        | (Covenant::Update(_), next)
+        | (Covenant::Renew(_), next)
+        | (Covenant::Finalize(_), next) => match next {
+            Covenant::Update(_)
+            | Covenant::Renew(_)
+            | Covenant::Transfer(_)
+            | Covenant::Revoke(_) => same_owner,
+            _ => false,
+        },
+        //A pending transfer is either cancelled by its owner or finalized to the new address.
+        (Covenant::Transfer(transfer), next) => match next {
+            Covenant::Update(_) | Covenant::Renew(_) | Covenant::Revoke(_) => same_owner,
+            Covenant::Finalize(_) => {
+                output.value == coin.value() && output.address == transfer.address
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Changes made to the name tree by a block, so they can be rolled back on disconnect.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NameUndo {
+    //Each touched name with its encoded state before the block, or None if it was not in the
+    //tree.
+    items: Vec<(NameHash, Option<Buffer>)>,
+}
+
+impl NameUndo {
+    pub fn new() -> Self {
+        NameUndo::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn apply(self, tree: &mut Tree) -> Result<()> {
+        for (name_hash, value) in self.items.into_iter().rev() {
+            match value {
+                Some(value) => tree.insert(name_hash, value)?,
+                None => {
+                    tree.remove(&name_hash)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Encodable for NameUndo {
+    fn size(&self) -> usize {
+        let mut size = 4;
+
+        for (_, value) in self.items.iter() {
+            size += 33;
+            if let Some(value) = value {
+                size += 2 + value.len();
+            }
+        }
+
+        size
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        buffer.write_u32(self.items.len() as u32);
+
+        for (name_hash, value) in self.items.iter() {
+            buffer.write_hash((*name_hash).into());
+
+            match value {
+                Some(value) => {
+                    buffer.write_u8(1);
+                    buffer.write_u16(value.len() as u16);
+                    buffer.write_bytes(value);
+                }
+                None => buffer.write_u8(0),
+            }
+        }
+
+        buffer
+    }
+}
+
+impl Decodable for NameUndo {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> std::result::Result<Self, Self::Err> {
+        let count = buffer.read_u32()?;
+        let mut items = Vec::new();
+
+        for _ in 0..count {
+            let name_hash = NameHash::from(buffer.read_hash()?);
+
+            let value = match buffer.read_u8()? {
+                0 => None,
+                _ => {
+                    let len = buffer.read_u16()?;
+                    Some(Buffer::from(buffer.read_bytes(len as usize)?))
+                }
+            };
+
+            items.push((name_hash, value));
+        }
+
+        Ok(NameUndo { items })
+    }
+}
+
+/// Name states as a block's covenants change them. Nothing is written to the tree until the whole
+/// block has been checked.
+#[derive(Debug, Default)]
+pub struct NameView {
+    names: HashMap<NameHash, NameState>,
+    //Names in the order they were first touched, so writes and undo are deterministic.
+    order: Vec<NameHash>,
+}
+
+impl NameView {
+    pub fn new() -> Self {
+        NameView::default()
+    }
+
+    /// The state of a name, read from the tree the first time it is touched. Unknown names are
+    /// null.
+    pub fn get(&mut self, tree: &Tree, name_hash: &NameHash) -> Result<&mut NameState> {
+        if !self.names.contains_key(name_hash) {
+            let ns = match tree.get(name_hash)? {
+                Some(mut raw) => NameState::decode(&mut raw)
+                    .map_err(|_| ChainError::CorruptNameState(*name_hash))?,
+                None => NameState {
+                    name_hash: *name_hash,
+                    ..Default::default()
+                },
+            };
+
+            self.order.push(*name_hash);
+            self.names.insert(*name_hash, ns);
+        }
+
+        Ok(self
+            .names
+            .get_mut(name_hash)
+            .expect("name state was just loaded"))
+    }
+
+    /// Applies a transaction's covenants to the name states. `verify_renewal` decides whether a
+    /// block hash committed to by a renewal is on the main chain and within the renewal period.
+    pub fn connect_tx<F>(
+        &mut self,
+        tree: &Tree,
+        tx: &Transaction,
+        height: u32,
+        network: Network,
+        verify_renewal: F,
+    ) -> Result<()>
+    where
+        F: Fn(&Hash) -> bool,
+    {
+        let txid = tx.hash();
+
+        for (index, output) in tx.outputs.iter().enumerate() {
+            let covenant = &output.covenant;
+
+            let name_hash = match covenant.get_name_hash() {
+                Some(name_hash) => name_hash,
+                None => continue,
+            };
+
+            if !name_matches(covenant) {
+                return Err(VerifyError::invalid("bad-txns-invalid-covenant", 100).into());
+            }
+
+            let start = covenant.get_height().unwrap_or(0);
+            let outpoint = Outpoint::new(txid, index as u32);
+            //Linked covenants continue from the coin spent at the same index.
+            let prevout = tx.inputs.get(index).map(|input| input.prevout);
+
+            let ns = self.get(tree, &name_hash)?;
+
+            if ns.is_null() {
+                match covenant {
+                    Covenant::Claim(_) | Covenant::Open(_) => {
+                        let name = covenant
+                            .get_name()
+                            .expect("claims and opens carry the name");
+                        ns.set(name, height);
+                    }
+                    _ => return Err(VerifyError::invalid("bad-name-state", 100).into()),
+                }
+            }
+
+            ns.maybe_expire(height, network);
+
+            let state = ns.state(height, network);
+
+            match covenant {
+                Covenant::None => {}
+                Covenant::Claim(claim) => {
+                    //Claims are how the owners of reserved names take them, during the claim
+                    //period only. Any other name has to go to auction.
+                    if height >= network.claim_period() {
+                        return Err(VerifyError::invalid("bad-claim-period", 100).into());
+                    }
+
+                    if !claim.name.is_reserved(height, &network) {
+                        return Err(VerifyError::invalid("bad-claim-notreserved", 100).into());
+                    }
+
+                    if start != height {
+                        return Err(VerifyError::invalid("bad-claim-height", 100).into());
+                    }
+
+                    //Reserved names can be claimed again up until they are registered.
+                    let unclaimed = state == AuctionState::Opening && ns.height == height;
+                    if !unclaimed && (ns.claimed == 0 || ns.registered) {
+                        return Err(VerifyError::invalid("bad-claim-state", 100).into());
+                    }
+
+                    ns.height = height;
+                    ns.renewal = height;
+                    ns.claimed = height;
+                    ns.owner = outpoint;
+                    ns.value = output.value;
+                    ns.highest = output.value;
+                    ns.weak = claim.flags & 1 != 0;
+                }
+                Covenant::Open(open) => {
+                    //Reserved names are left to their owners to claim until the claim period ends.
+                    if open.name.is_reserved(height, &network) {
+                        return Err(VerifyError::invalid("bad-open-reserved", 100).into());
+                    }
+
+                    if !open.name.has_rollout(height, &network) {
+                        return Err(VerifyError::invalid("bad-open-rollout", 100).into());
+                    }
+
+                    //Only one auction can be running for a name at a time.
+                    if ns.height != height || ns.claimed != 0 {
+                        return Err(VerifyError::invalid("bad-open-multiple", 100).into());
+                    }
+                }
+                Covenant::Bid(_) => {
+                    if start != ns.height {
+                        return Err(VerifyError::invalid("bad-bid-height", 100).into());
+                    }
+
+                    if state != AuctionState::Bidding {
+                        return Err(VerifyError::invalid("bad-bid-state", 100).into());
+                    }
+                }
+                Covenant::Reveal(_) => {
+                    if start != ns.height {
+                        return Err(VerifyError::invalid("bad-reveal-nonlocal", 100).into());
+                    }
+
+                    if state != AuctionState::Reveal {
+                        return Err(VerifyError::invalid("bad-reveal-state", 100).into());
+                    }
+
+                    //Second price auction: the winner pays the runner up's bid.
+                    if ns.owner.is_null() || output.value.as_doos() > ns.highest.as_doos() {
+                        ns.value = ns.highest;
+                        ns.owner = outpoint;
+                        ns.highest = output.value;
+                    } else if output.value.as_doos() > ns.value.as_doos() {
+                        ns.value = output.value;
+                    }
+                }
+                Covenant::Redeem(_) => {
+                    if start != ns.height {
+                        return Err(VerifyError::invalid("bad-redeem-nonlocal", 100).into());
+                    }
+
+                    match state {
+                        AuctionState::Closed | AuctionState::Revoked => {}
+                        _ => return Err(VerifyError::invalid("bad-redeem-state", 100).into()),
+                    }
+
+                    //Only losers get their money back.
+                    if prevout == Some(ns.owner) {
+                        return Err(VerifyError::invalid("bad-redeem-owner", 100).into());
+                    }
+                }
+                Covenant::Register(register) => {
+                    check_owned(
+                        ns,
+                        start,
+                        state,
+                        prevout,
+                        "bad-register-nonlocal",
+                        "bad-register-state",
+                    )?;
+
+                    if !verify_renewal(&register.block_hash) {
+                        return Err(VerifyError::invalid("bad-register-renewal", 100).into());
+                    }
+
+                    //The winner locks up the second highest bid, not their own.
+                    if output.value != ns.value {
+                        return Err(VerifyError::invalid("bad-register-value", 100).into());
+                    }
+
+                    ns.owner = outpoint;
+                    if !register.record_data.is_empty() {
+                        ns.data = register.record_data.clone();
+                    }
+                    ns.registered = true;
+                    ns.renewal = height;
+                }
+                Covenant::Update(update) => {
+                    check_owned(
+                        ns,
+                        start,
+                        state,
+                        prevout,
+                        "bad-update-nonlocal",
+                        "bad-update-state",
+                    )?;
+
+                    ns.owner = outpoint;
+                    if !update.record_data.is_empty() {
+                        ns.data = update.record_data.clone();
+                    }
+                    ns.transfer = 0;
+                }
+                Covenant::Renew(renew) => {
+                    check_owned(
+                        ns,
+                        start,
+                        state,
+                        prevout,
+                        "bad-renewal-nonlocal",
+                        "bad-renewal-state",
+                    )?;
+
+                    if height < ns.renewal + network.tree_interval() {
+                        return Err(VerifyError::invalid("bad-renewal-premature", 100).into());
+                    }
+
+                    if !verify_renewal(&renew.block_hash) {
+                        return Err(VerifyError::invalid("bad-renewal", 100).into());
+                    }
+
+                    ns.owner = outpoint;
+                    ns.transfer = 0;
+                    ns.renewal = height;
+                    ns.renewals += 1;
+                }
+                Covenant::Transfer(_) => {
+                    check_owned(
+                        ns,
+                        start,
+                        state,
+                        prevout,
+                        "bad-transfer-nonlocal",
+                        "bad-transfer-state",
+                    )?;
+
+                    ns.owner = outpoint;
+                    ns.transfer = height;
+                }
+                Covenant::Finalize(finalize) => {
+                    check_owned(
+                        ns,
+                        start,
+                        state,
+                        prevout,
+                        "bad-finalize-nonlocal",
+                        "bad-finalize-state",
+                    )?;
+
+                    if ns.transfer == 0 {
+                        return Err(VerifyError::invalid("bad-finalize-state", 100).into());
+                    }
+
+                    if height < ns.transfer + network.transfer_lockup() {
+                        return Err(VerifyError::invalid("bad-finalize-maturity", 100).into());
+                    }
+
+                    let weak = finalize.flags & 1 != 0;
+                    if weak != ns.weak
+                        || finalize.claimed != ns.claimed
+                        || finalize.renewals != ns.renewals
+                    {
+                        return Err(VerifyError::invalid("bad-finalize-statetransfer", 100).into());
+                    }
+
+                    if !verify_renewal(&finalize.block_hash) {
+                        return Err(VerifyError::invalid("bad-finalize-renewal", 100).into());
+                    }
+
+                    ns.owner = outpoint;
+                    ns.transfer = 0;
+                    ns.renewal = height;
+                    ns.renewals += 1;
+                }
+                Covenant::Revoke(_) => {
+                    check_owned(
+                        ns,
+                        start,
+                        state,
+                        prevout,
+                        "bad-revoke-nonlocal",
+                        "bad-revoke-state",
+                    )?;
+
+                    ns.revoked = height;
+                    ns.transfer = 0;
+                    ns.data = Buffer::new();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the touched names to the tree, returning what is needed to undo the writes.
+    pub fn apply(self, tree: &mut Tree) -> Result<NameUndo> {
+        let mut undo = NameUndo::new();
+        let mut names = self.names;
+
+        for name_hash in self.order {
+            let ns = names
+                .remove(&name_hash)
+                .expect("touched names are in the view");
+
+            undo.items.push((name_hash, tree.get(&name_hash)?));
+
+            if ns.is_null() {
+                tree.remove(&name_hash)?;
+            } else {
+                tree.insert(name_hash, ns.encode())?;
+            }
+        }
+
+        Ok(undo)
+    }
+}
+
+//Covenants after the auction act on a closed name and must spend the current owner's coin.
+fn check_owned(
+    ns: &NameState,
+    start: u32,
+    state: AuctionState,
+    prevout: Option<Outpoint>,
+    nonlocal: &'static str,
+    bad_state: &'static str,
+) -> VerifyResult<()> {
+    if start != ns.height {
+        return Err(VerifyError::invalid(nonlocal, 100));
+    }
+
+    if state != AuctionState::Closed {
+        return Err(VerifyError::invalid(bad_state, 100));
+    }
+
+    if prevout != Some(ns.owner) {
+        return Err(VerifyError::invalid("bad-name-owner", 100));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{address, coin, output, spend_to};
+    use handshake_primitives::covenants::{
+        BidCovenant, ClaimCovenant, FinalizeCovenant, OpenCovenant, RegisterCovenant,
+        RevealCovenant, TransferCovenant,
+    };
+    use handshake_types::Name;
+
+    fn name() -> Name {
+        "handshake".parse().unwrap()
+    }
+
+    fn name_hash() -> NameHash {
+        name().hash()
+    }
+
+    fn bid(height: u32, value: u64, nonce: &Hash) -> Covenant {
+        Covenant::Bid(BidCovenant {
+            name_hash: name_hash(),
+            height,
+            name: name(),
+            hash: blind(Amount::from_doos(value), nonce),
+        })
+    }
+
+    fn reveal(height: u32, nonce: Hash) -> Covenant {
+        Covenant::Reveal(RevealCovenant {
+            name_hash: name_hash(),
+            height,
+            nonce,
+        })
+    }
+
+    fn register(height: u32) -> Covenant {
+        Covenant::Register(RegisterCovenant {
+            name_hash: name_hash(),
+            height,
+            record_data: Buffer::from(vec![1, 2, 3]),
+            block_hash: Hash::default(),
+        })
+    }
+
+    #[test]
+    fn test_check_covenants() {
+        let nonce = Hash::from([3; 32]);
+        let spent = [coin(1000, bid(5, 600, &nonce))];
+        let prevout = Outpoint::new(Hash::from([1; 32]), 0);
+
+        let good = spend_to(vec![prevout], vec![output(600, reveal(5, nonce))]);
+        assert!(check_covenants(&good, &spent).is_ok());
+
+        //The revealed value has to open the blind.
+        let wrong_value = spend_to(vec![prevout], vec![output(700, reveal(5, nonce))]);
+        assert!(check_covenants(&wrong_value, &spent).is_err());
+
+        let wrong_nonce = spend_to(vec![prevout], vec![output(600, reveal(5, Hash::default()))]);
+        assert!(check_covenants(&wrong_nonce, &spent).is_err());
+
+        //Reveals belong to the auction the bid was placed in.
+        let wrong_auction = spend_to(vec![prevout], vec![output(600, reveal(6, nonce))]);
+        assert!(check_covenants(&wrong_auction, &spent).is_err());
+
+        //A bid can't just be spent away.
+        let unlinked = spend_to(vec![prevout], vec![output(600, Covenant::None)]);
+        assert!(check_covenants(&unlinked, &spent).is_err());
+
+        //Nor can a name be moved along without spending its coin.
+        let plain = [coin(1000, Covenant::None)];
+        let forged = spend_to(vec![prevout], vec![output(600, register(5))]);
+        assert!(check_covenants(&forged, &plain).is_err());
+
+        let open = Covenant::Open(OpenCovenant {
+            name_hash: name_hash(),
+            height: 0,
+            name: name(),
+        });
+        let opening = spend_to(vec![prevout], vec![output(0, open)]);
+        assert!(check_covenants(&opening, &plain).is_ok());
+
+        //Registered names keep their value and address until finalized elsewhere.
+        let registered = [coin(600, register(5))];
+        let transfer = Covenant::Transfer(TransferCovenant {
+            name_hash: name_hash(),
+            height: 5,
+            address: address(),
+        });
+        let moved = spend_to(vec![prevout], vec![output(600, transfer.clone())]);
+        assert!(check_covenants(&moved, &registered).is_ok());
+
+        let drained = spend_to(vec![prevout], vec![output(500, transfer)]);
+        assert!(check_covenants(&drained, &registered).is_err());
+    }
+
+    fn connect(
+        tree: &mut Tree,
+        tx: &Transaction,
+        height: u32,
+        network: Network,
+    ) -> std::result::Result<NameUndo, &'static str> {
+        let mut view = NameView::new();
+        match view.connect_tx(tree, tx, height, network, |_| true) {
+            Ok(()) => Ok(view.apply(tree).unwrap()),
+            Err(ChainError::Verify(e)) => Err(e.reason),
+            Err(e) => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    fn state(tree: &Tree) -> NameState {
+        let mut raw = tree.get(&name_hash()).unwrap().unwrap();
+        NameState::decode(&mut raw).unwrap()
+    }
+
+    #[test]
+    fn test_auction_lifecycle() {
+        let network = Network::Regtest;
+        let mut tree = Tree::new();
+        let funds = Outpoint::new(Hash::from([1; 32]), 0);
+
+        let opened = 10;
+        let open = Covenant::Open(OpenCovenant {
+            name_hash: name_hash(),
+            height: 0,
+            name: name(),
+        });
+        connect(
+            &mut tree,
+            &spend_to(vec![funds], vec![output(0, open.clone())]),
+            opened,
+            network,
+        )
+        .unwrap();
+        assert_eq!(state(&tree).height, opened);
+
+        //A second open while the auction runs is refused.
+        let again = spend_to(vec![funds], vec![output(0, open)]);
+        assert_eq!(
+            connect(&mut tree, &again, opened + 1, network),
+            Err("bad-open-multiple")
+        );
+
+        let (low, high) = (Hash::from([4; 32]), Hash::from([5; 32]));
+        let bids = spend_to(
+            vec![funds, funds],
+            vec![
+                output(1000, bid(opened, 300, &low)),
+                output(1000, bid(opened, 500, &high)),
+            ],
+        );
+        assert_eq!(
+            connect(&mut tree, &bids, opened + 1, network),
+            Err("bad-bid-state")
+        );
+
+        let bidding = opened + network.open_period();
+        connect(&mut tree, &bids, bidding, network).unwrap();
+
+        let reveals = spend_to(
+            vec![Outpoint::new(bids.hash(), 0), Outpoint::new(bids.hash(), 1)],
+            vec![
+                output(300, reveal(opened, low)),
+                output(500, reveal(opened, high)),
+            ],
+        );
+        let revealing = bidding + network.bidding_period();
+        connect(&mut tree, &reveals, revealing, network).unwrap();
+
+        //The highest bidder wins and pays the second highest bid.
+        let ns = state(&tree);
+        assert_eq!(ns.owner, Outpoint::new(reveals.hash(), 1));
+        assert_eq!(ns.highest, Amount::from_doos(500));
+        assert_eq!(ns.value, Amount::from_doos(300));
+
+        let closed = revealing + network.reveal_period();
+        let winner = Outpoint::new(reveals.hash(), 1);
+
+        let overpaid = spend_to(vec![winner], vec![output(500, register(opened))]);
+        assert_eq!(
+            connect(&mut tree, &overpaid, closed, network),
+            Err("bad-register-value")
+        );
+
+        let loser = spend_to(
+            vec![Outpoint::new(reveals.hash(), 0)],
+            vec![output(300, register(opened))],
+        );
+        assert_eq!(
+            connect(&mut tree, &loser, closed, network),
+            Err("bad-name-owner")
+        );
+
+        let registered = spend_to(vec![winner], vec![output(300, register(opened))]);
+        let undo = connect(&mut tree, &registered, closed, network).unwrap();
+        let ns = state(&tree);
+        assert!(ns.registered);
+        assert_eq!(ns.data, Buffer::from(vec![1, 2, 3]));
+
+        //Undoing the register puts back the state the reveals left.
+        let mut encoded = undo.encode();
+        assert_eq!(encoded.len(), undo.size());
+        let undo = NameUndo::decode(&mut encoded).unwrap();
+
+        let root = tree.root_hash();
+        undo.apply(&mut tree).unwrap();
+        assert!(!state(&tree).registered);
+        connect(&mut tree, &registered, closed, network).unwrap();
+        assert_eq!(tree.root_hash(), root);
+
+        let transfer = Covenant::Transfer(TransferCovenant {
+            name_hash: name_hash(),
+            height: opened,
+            address: address(),
+        });
+        let transferred = spend_to(
+            vec![Outpoint::new(registered.hash(), 0)],
+            vec![output(300, transfer)],
+        );
+        let transferring = closed + 1;
+        connect(&mut tree, &transferred, transferring, network).unwrap();
+        assert_eq!(state(&tree).transfer, transferring);
+
+        let finalize = Covenant::Finalize(FinalizeCovenant {
+            name_hash: name_hash(),
+            height: opened,
+            name: name(),
+            flags: 0,
+            claimed: 0,
+            renewals: 0,
+            block_hash: Hash::default(),
+        });
+        let finalized = spend_to(
+            vec![Outpoint::new(transferred.hash(), 0)],
+            vec![output(300, finalize)],
+        );
+        let unlocked = transferring + network.transfer_lockup();
+        assert_eq!(
+            connect(&mut tree, &finalized, unlocked - 1, network),
+            Err("bad-finalize-maturity")
+        );
+
+        connect(&mut tree, &finalized, unlocked, network).unwrap();
+        let ns = state(&tree);
+        assert_eq!(ns.transfer, 0);
+        assert_eq!(ns.renewals, 1);
+        assert_eq!(ns.owner, Outpoint::new(finalized.hash(), 0));
+    }
+
+    #[test]
+    fn test_open_rules() {
+        let network = Network::Mainnet;
+        let mut tree = Tree::new();
+        let funds = Outpoint::new(Hash::from([1; 32]), 0);
+
+        let open = |name: Name| {
+            let open = Covenant::Open(OpenCovenant {
+                name_hash: name.hash(),
+                height: 0,
+                name,
+            });
+            spend_to(vec![funds], vec![output(0, open)])
+        };
+
+        let com: Name = "com".parse().unwrap();
+        assert_eq!(
+            connect(&mut tree, &open(com.clone()), 100_000, network),
+            Err("bad-open-reserved")
+        );
+
+        //Once the claim period is over anyone can open it.
+        let (_, released) = com.rollout_week(&network);
+        let height = network.claim_period().max(released);
+        assert!(connect(&mut tree, &open(com), height, network).is_ok());
+
+        let (_, released) = name().rollout_week(&network);
+        assert_eq!(
+            connect(&mut tree, &open(name()), released - 1, network),
+            Err("bad-open-rollout")
+        );
+        assert!(connect(&mut tree, &open(name()), released, network).is_ok());
+
+        //An OPEN for one name under another name's hash.
+        let google: Name = "google".parse().unwrap();
+        let spoofed = Covenant::Open(OpenCovenant {
+            name_hash: google.hash(),
+            height: 0,
+            name: name(),
+        });
+        let spoofed = spend_to(vec![funds], vec![output(0, spoofed)]);
+        assert!(check_covenants(&spoofed, &[coin(0, Covenant::None)]).is_err());
+        assert_eq!(
+            connect(&mut tree, &spoofed, released, network),
+            Err("bad-txns-invalid-covenant")
+        );
+    }
+
+    #[test]
+    fn test_claim_rules() {
+        let network = Network::Mainnet;
+        let mut tree = Tree::new();
+
+        let claim = |name: Name, height: u32| {
+            let claim = Covenant::Claim(ClaimCovenant {
+                name_hash: name.hash(),
+                height,
+                name,
+                flags: 0,
+                commit_hash: Hash::default(),
+                commit_height: 0,
+            });
+            spend_to(vec![Outpoint::default()], vec![output(0, claim)])
+        };
+
+        //Only reserved names can be claimed, and only during the claim period.
+        assert_eq!(
+            connect(&mut tree, &claim(name(), 100), 100, network),
+            Err("bad-claim-notreserved")
+        );
+
+        let com: Name = "com".parse().unwrap();
+        let end = network.claim_period();
+        assert_eq!(
+            connect(&mut tree, &claim(com.clone(), end), end, network),
+            Err("bad-claim-period")
+        );
+        assert!(connect(&mut tree, &claim(com, 100), 100, network).is_ok());
+    }
+}
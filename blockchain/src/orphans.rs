@@ -0,0 +1,230 @@
+use extended_primitives::Hash;
+use handshake_primitives::Block;
+use std::collections::HashMap;
+
+/// Most orphans held at once.
+pub const MAX_ORPHANS: usize = 20;
+
+/// Orphans are dropped an hour after they arrive.
+pub const ORPHAN_TTL: u64 = 60 * 60;
+
+/// A block whose parent we have not seen yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Orphan {
+    pub block: Block,
+    /// The peer that sent the block, or None if it came from us.
+    pub peer: Option<String>,
+    /// Network time the orphan arrived.
+    pub time: u64,
+}
+
+impl Orphan {
+    pub fn hash(&self) -> Hash {
+        self.block.header.hash()
+    }
+
+    pub fn prev_block(&self) -> Hash {
+        self.block.header.prev_block
+    }
+}
+
+/// Blocks waiting on their parent, keyed by the parent's hash. Only one orphan is kept per
+/// parent, a second block building on the same missing parent replaces the first.
+#[derive(Debug)]
+pub struct OrphanPool {
+    orphans: HashMap<Hash, Orphan>,
+    //Orphan hash to the parent it is waiting on.
+    prevs: HashMap<Hash, Hash>,
+    max: usize,
+}
+
+impl OrphanPool {
+    pub fn new(max: usize) -> Self {
+        OrphanPool {
+            orphans: HashMap::new(),
+            prevs: HashMap::new(),
+            max,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.orphans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty()
+    }
+
+    pub fn has(&self, hash: &Hash) -> bool {
+        self.prevs.contains_key(hash)
+    }
+
+    pub fn get(&self, hash: &Hash) -> Option<&Orphan> {
+        let prev = self.prevs.get(hash)?;
+        self.orphans.get(prev)
+    }
+
+    /// Stores an orphan, evicting expired orphans and then the oldest if the pool is full.
+    pub fn add(&mut self, block: Block, peer: Option<&str>, now: u64) {
+        let orphan = Orphan {
+            block,
+            peer: peer.map(str::to_owned),
+            time: now,
+        };
+
+        //A fork of an orphan we already hold replaces it.
+        self.remove_by_prev(&orphan.prev_block());
+
+        self.limit(now);
+
+        self.prevs.insert(orphan.hash(), orphan.prev_block());
+        self.orphans.insert(orphan.prev_block(), orphan);
+    }
+
+    /// Takes the orphan waiting on `prev`, once `prev` has arrived.
+    pub fn remove_by_prev(&mut self, prev: &Hash) -> Option<Orphan> {
+        let orphan = self.orphans.remove(prev)?;
+        self.prevs.remove(&orphan.hash());
+        Some(orphan)
+    }
+
+    pub fn remove(&mut self, hash: &Hash) -> Option<Orphan> {
+        let prev = self.prevs.get(hash).cloned()?;
+        self.remove_by_prev(&prev)
+    }
+
+    /// Drops every orphan a peer sent, returning how many there were.
+    pub fn remove_peer(&mut self, peer: &str) -> usize {
+        let prevs: Vec<Hash> = self
+            .orphans
+            .iter()
+            .filter(|(_, orphan)| orphan.peer.as_ref().map(String::as_str) == Some(peer))
+            .map(|(prev, _)| *prev)
+            .collect();
+
+        for prev in prevs.iter() {
+            self.remove_by_prev(prev);
+        }
+
+        prevs.len()
+    }
+
+    /// Follows a chain of orphans back from `hash` to the first one, whose parent is the block
+    /// sync has to fetch. None if `hash` is not an orphan.
+    pub fn get_root(&self, hash: &Hash) -> Option<Hash> {
+        let mut root = None;
+        let mut hash = *hash;
+
+        while let Some(prev) = self.prevs.get(&hash) {
+            root = Some(hash);
+            hash = *prev;
+        }
+
+        root
+    }
+
+    //Makes room for one more orphan.
+    fn limit(&mut self, now: u64) {
+        let expired: Vec<Hash> = self
+            .orphans
+            .iter()
+            .filter(|(_, orphan)| now >= orphan.time + ORPHAN_TTL)
+            .map(|(prev, _)| *prev)
+            .collect();
+
+        for prev in expired.iter() {
+            self.remove_by_prev(prev);
+        }
+
+        while self.orphans.len() >= self.max {
+            let oldest = self
+                .orphans
+                .iter()
+                .min_by_key(|(_, orphan)| orphan.time)
+                .map(|(prev, _)| *prev);
+
+            match oldest {
+                Some(prev) => {
+                    self.remove_by_prev(&prev);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        OrphanPool::new(MAX_ORPHANS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use handshake_primitives::BlockHeader;
+
+    fn block(prev: Hash, nonce: u32) -> Block {
+        Block {
+            header: BlockHeader {
+                prev_block: prev,
+                nonce,
+                ..Default::default()
+            },
+            txdata: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_orphan_root() {
+        let mut pool = OrphanPool::default();
+        let missing = Hash::from([1; 32]);
+
+        let a = block(missing, 0);
+        let b = block(a.header.hash(), 0);
+        let c = block(b.header.hash(), 0);
+
+        pool.add(a.clone(), Some("peer"), 0);
+        pool.add(b.clone(), Some("peer"), 0);
+        pool.add(c.clone(), None, 0);
+
+        assert_eq!(pool.get_root(&c.header.hash()), Some(a.header.hash()));
+        assert_eq!(pool.get_root(&missing), None);
+
+        let orphan = pool.remove_by_prev(&missing).unwrap();
+        assert_eq!(orphan.block, a);
+        assert_eq!(orphan.peer, Some("peer".to_string()));
+        assert_eq!(pool.get_root(&c.header.hash()), Some(b.header.hash()));
+
+        //A competing child of the same parent replaces the one we had.
+        let fork = block(a.header.hash(), 1);
+        pool.add(fork.clone(), None, 0);
+        assert!(!pool.has(&b.header.hash()));
+        assert!(pool.has(&fork.header.hash()));
+
+        assert_eq!(pool.remove_peer("peer"), 0);
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut pool = OrphanPool::new(3);
+
+        for n in 0..3u8 {
+            pool.add(block(Hash::from([n; 32]), 0), Some("a"), n as u64);
+        }
+        assert_eq!(pool.len(), 3);
+
+        //Full, so the oldest makes way.
+        pool.add(block(Hash::from([3; 32]), 0), Some("b"), 3);
+        assert_eq!(pool.len(), 3);
+        assert!(pool.remove_by_prev(&Hash::from([0; 32])).is_none());
+
+        //Everything from before the TTL goes at once.
+        pool.add(block(Hash::from([4; 32]), 0), Some("b"), 2 + ORPHAN_TTL);
+        assert_eq!(pool.len(), 2);
+
+        assert_eq!(pool.remove_peer("b"), 2);
+        assert!(pool.is_empty());
+    }
+}
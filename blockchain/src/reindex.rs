@@ -0,0 +1,242 @@
+//! Rebuilding a node's state from the blocks in its flat files, without the network. Everything
+//! the chain derives from blocks (the block index, coins, the name tree, undo data and the
+//! optional indexes) is thrown away and built again by connecting the stored blocks in order.
+
+use crate::chain::corrupt;
+use crate::{layout, Chain, ChainError, IndexOptions, Indexers, Result};
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::Decodable;
+use handshake_primitives::BlockHeader;
+use handshake_protocol::network::Network;
+use handshake_store::file_store::{self, Location};
+use handshake_store::urkel::tree;
+use handshake_store::{Batch, FileStore, FileType, KvStore};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Every block is verified in full when reindexing. There is no trusting blocks below a known
+/// hash yet: that would only skip script verification, which the chain doesn't do.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReindexOptions {
+    /// The indexes to build once the chain is rebuilt.
+    pub indexes: IndexOptions,
+}
+
+/// Passed to the progress callback after each block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReindexProgress {
+    /// Height of the rebuilt chain's tip.
+    pub height: u32,
+    /// Blocks processed so far.
+    pub blocks: usize,
+    /// Blocks found in the files that build on genesis.
+    pub total: usize,
+}
+
+/// Rebuilds the chain kept in `db` from the blocks in `files`. Blocks are connected in height
+/// order, with the usual checks, so the best valid chain in the files ends up as the tip; blocks
+/// that fail them are left out, along with everything built on them. A pruned node no longer has
+/// the blocks to start from and can't be reindexed.
+pub fn reindex<F>(
+    network: Network,
+    db: Arc<dyn KvStore>,
+    mut files: FileStore,
+    options: ReindexOptions,
+    mut progress: F,
+) -> Result<(Chain, Indexers)>
+where
+    F: FnMut(&ReindexProgress),
+{
+    if db.has(&layout::pruned())? {
+        return Err(ChainError::Pruned);
+    }
+
+    //Every block in the files, whether the index knew of it or not.
+    let mut blocks: HashMap<Hash, (Hash, Location)> = HashMap::new();
+    files.scan(FileType::Block, |location, data| {
+        if let Ok(header) = BlockHeader::decode(&mut Buffer::from(data.to_vec())) {
+            blocks.insert(header.hash(), (header.prev_block, location));
+        }
+    })?;
+
+    wipe(db.as_ref())?;
+    files.clear(FileType::Undo)?;
+
+    let mut children: HashMap<Hash, Vec<Hash>> = HashMap::new();
+    for (hash, (prev, _)) in blocks.iter() {
+        children.entry(*prev).or_default().push(*hash);
+    }
+
+    //Walking out from genesis a height at a time puts every block after its parent. Blocks that
+    //don't lead back to genesis are dropped.
+    let genesis = network.genesis_hash();
+    let mut order = Vec::new();
+    let mut level = vec![genesis];
+    let mut height = 0;
+
+    while !level.is_empty() {
+        let mut next = Vec::new();
+
+        for hash in level {
+            if let Some((_, location)) = blocks.get(&hash) {
+                //One at a time, since each record updates its file's bookkeeping in the store.
+                let mut batch = Batch::new();
+                files.index(FileType::Block, &hash, height, *location, &mut batch)?;
+                db.write(batch)?;
+
+                if hash != genesis {
+                    order.push(hash);
+                }
+            }
+
+            if let Some(hashes) = children.get(&hash) {
+                next.extend_from_slice(hashes);
+            }
+        }
+
+        level = next;
+        height += 1;
+    }
+
+    let mut chain = Chain::with_files(network, db.clone(), files)?;
+
+    let total = order.len();
+    for (index, hash) in order.iter().enumerate() {
+        let block = match chain.get_block(hash)? {
+            Some(block) => block,
+            None => return Err(corrupt("Reindexed block is missing")),
+        };
+
+        match chain.store_block(block) {
+            Ok(_) => {}
+            Err(ChainError::Store(e)) => return Err(e.into()),
+            Err(_) => {}
+        }
+
+        progress(&ReindexProgress {
+            height: chain.height(),
+            blocks: index + 1,
+            total,
+        });
+    }
+
+    let mut indexers = Indexers::open(db, options.indexes)?;
+    indexers.sync(&chain)?;
+
+    Ok((chain, indexers))
+}
+
+//Deletes everything the chain and its indexes keep in the store. Records that aren't derived
+//from blocks, like the schema version and the peer store, stay.
+fn wipe(db: &dyn KvStore) -> Result<()> {
+    let prefixes = [
+        layout::ENTRY,
+        layout::HEIGHT,
+        layout::BLOCK,
+        layout::UNDO,
+        layout::NAME_UNDO,
+        layout::COIN,
+        layout::STATE,
+        layout::PRUNED,
+        layout::INDEX,
+        tree::NODE_PREFIX,
+        tree::ROOT_PREFIX,
+        file_store::LOCATION_PREFIX,
+        file_store::FILE_PREFIX,
+        file_store::CONTENTS_PREFIX,
+    ];
+
+    let mut batch = Batch::new();
+    for prefix in prefixes.iter() {
+        for (key, _) in db.prefix(&[*prefix])? {
+            batch.delete(&key);
+        }
+    }
+
+    db.write(batch)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{coinbase, mine_block, spend};
+    use handshake_encoding::Encodable;
+    use handshake_primitives::Outpoint;
+    use handshake_store::MemoryStore;
+
+    fn options() -> ReindexOptions {
+        ReindexOptions {
+            indexes: IndexOptions {
+                tx: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_reindex() {
+        let dir = std::env::temp_dir().join(format!("handshake-reindex-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let db: Arc<dyn KvStore> = Arc::new(MemoryStore::new());
+        let network = Network::Regtest;
+        let files = FileStore::open(&dir, network.magic(), db.clone()).unwrap();
+        let mut chain = Chain::with_files(network, db.clone(), files).unwrap();
+
+        let genesis = chain.tip().clone();
+        let a1 = chain
+            .add_block(mine_block(&genesis, 0, Vec::new()))
+            .unwrap();
+        let a2 = chain.add_block(mine_block(&a1, 0, Vec::new())).unwrap();
+        let b2 = chain.add_block(mine_block(&a1, 1, Vec::new())).unwrap();
+        let reward = Outpoint::new(coinbase(1).hash(), 0);
+        let spending = spend(reward);
+        let a3 = chain
+            .add_block(mine_block(&a2, 0, vec![spending.clone()]))
+            .unwrap();
+        let root = chain.tree().root_hash();
+        drop(chain);
+
+        let files = FileStore::open(&dir, network.magic(), db.clone()).unwrap();
+        let mut seen = Vec::new();
+        let (chain, indexers) = reindex(network, db.clone(), files, options(), |progress| {
+            seen.push(*progress)
+        })
+        .unwrap();
+
+        assert_eq!(chain.tip(), &a3);
+        assert!(chain.has_entry(&b2.hash));
+        assert!(!chain.coins().has(&reward).unwrap());
+        assert_eq!(chain.tree().root_hash(), root);
+        assert_eq!(seen.len(), 4);
+        assert_eq!(seen[3].height, 3);
+        assert_eq!(seen[3].total, 4);
+
+        let record = indexers.tx.as_ref().unwrap().index().get(&spending.hash());
+        assert_eq!(record.unwrap().unwrap().height, 3);
+        drop(chain);
+
+        //A block spending an immature coinbase, which made it to the files without being
+        //indexed.
+        let mut files = FileStore::open(&dir, network.magic(), db.clone()).unwrap();
+        let bad = mine_block(&a3, 0, vec![spend(Outpoint::new(coinbase(3).hash(), 0))]);
+        files
+            .write(
+                FileType::Block,
+                &bad.header.hash(),
+                4,
+                &bad.encode(),
+                &mut Batch::new(),
+            )
+            .unwrap();
+
+        let files = FileStore::open(&dir, network.magic(), db.clone()).unwrap();
+        let (chain, _) = reindex(network, db.clone(), files, options(), |_| {}).unwrap();
+        assert_eq!(chain.tip(), &a3);
+        assert!(!chain.has_entry(&bad.header.hash()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+
+/// The record a block's writes end with. Whatever the store holds, this says which tip it is
+/// consistent with and where the name tree was last committed, so a restart can pick up from it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChainState {
+    pub tip: Hash,
+    /// The last committed tree root.
+    pub tree_root: Hash,
+    /// Height of the block the tree was last committed at. Name changes from later blocks are
+    /// only in memory, and are replayed from their blocks on startup.
+    pub commit_height: u32,
+}
+
+impl ChainState {
+    pub fn new(tip: Hash, tree_root: Hash, commit_height: u32) -> Self {
+        ChainState {
+            tip,
+            tree_root,
+            commit_height,
+        }
+    }
+}
+
+impl Encodable for ChainState {
+    fn size(&self) -> usize {
+        68
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        buffer.write_hash(self.tip);
+        buffer.write_hash(self.tree_root);
+        buffer.write_u32(self.commit_height);
+
+        buffer
+    }
+}
+
+impl Decodable for ChainState {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        let tip = buffer.read_hash()?;
+        let tree_root = buffer.read_hash()?;
+        let commit_height = buffer.read_u32()?;
+
+        Ok(ChainState {
+            tip,
+            tree_root,
+            commit_height,
+        })
+    }
+}
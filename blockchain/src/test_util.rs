@@ -0,0 +1,106 @@
+//! Fixtures shared by the crate's tests. Blocks and transactions built here go through the store,
+//! so they pay a real (if empty) pubkeyhash rather than the default address, which doesn't decode.
+
+use crate::{ChainEntry, CoinEntry};
+use extended_primitives::Buffer;
+use handshake_primitives::address::Payload;
+use handshake_primitives::{
+    Address, Block, BlockHeader, Covenant, Genesis, Input, Outpoint, Output, Transaction,
+};
+use handshake_protocol::network::Network;
+use handshake_types::Amount;
+
+pub(crate) fn address() -> Address {
+    address_from(0)
+}
+
+/// An address whose hash is `byte` repeated, for tests that tell outputs apart by address.
+pub(crate) fn address_from(byte: u8) -> Address {
+    Address::new(0, Payload::PubkeyHash(Buffer::from(vec![byte; 20])))
+}
+
+pub(crate) fn output(value: u64, covenant: Covenant) -> Output {
+    Output {
+        value: Amount::from_doos(value),
+        address: address(),
+        covenant,
+    }
+}
+
+/// An unspent, non-coinbase output created at height 1.
+pub(crate) fn coin(value: u64, covenant: Covenant) -> CoinEntry {
+    CoinEntry::new(output(value, covenant), 1, false)
+}
+
+pub(crate) fn coinbase(height: u32) -> Transaction {
+    coinbase_paying(height, 0)
+}
+
+pub(crate) fn coinbase_paying(height: u32, value: u64) -> Transaction {
+    let output = Output::new(Amount::from_doos(value), address());
+    Transaction::new(height, vec![Input::default()], vec![output])
+}
+
+pub(crate) fn spend(prevout: Outpoint) -> Transaction {
+    spend_paying(prevout, 0)
+}
+
+pub(crate) fn spend_paying(prevout: Outpoint, value: u64) -> Transaction {
+    spend_to(vec![prevout], vec![output(value, Covenant::None)])
+}
+
+/// Spends every one of `prevouts` into `outputs`. With no prevouts, the transaction has a
+/// coinbase input instead.
+pub(crate) fn spend_to(prevouts: Vec<Outpoint>, outputs: Vec<Output>) -> Transaction {
+    let inputs = if prevouts.is_empty() {
+        vec![Input::default()]
+    } else {
+        prevouts
+            .into_iter()
+            .map(|prevout| Input {
+                prevout,
+                ..Default::default()
+            })
+            .collect()
+    };
+
+    Transaction::new(0, inputs, outputs)
+}
+
+/// Mines `txdata`, coinbase included, on top of `prev`. Different salts give different blocks
+/// with the same parent.
+pub(crate) fn seal(prev: &ChainEntry, salt: u32, txdata: Vec<Transaction>) -> Block {
+    let mut block = Block {
+        header: BlockHeader::default(),
+        txdata,
+    };
+
+    block.header = BlockHeader {
+        prev_block: prev.hash,
+        time: prev.time + 600,
+        bits: 0x207fffff,
+        version: salt,
+        merkle_root: block.create_merkle_root(),
+        witness_root: block.create_witness_root(),
+        ..Default::default()
+    };
+
+    while !block.header.verify_pow() {
+        block.header.nonce += 1;
+    }
+
+    block
+}
+
+/// Seals `txdata` on the regtest genesis block, for checks that don't need a chain behind them.
+pub(crate) fn block(txdata: Vec<Transaction>) -> Block {
+    let genesis = ChainEntry::from_header(&Network::Regtest.genesis_block().header, None);
+    seal(&genesis, 0, txdata)
+}
+
+/// A block on `prev` with an empty coinbase followed by `txs`.
+pub(crate) fn mine_block(prev: &ChainEntry, salt: u32, txs: Vec<Transaction>) -> Block {
+    let mut txdata = vec![coinbase(prev.height + 1)];
+    txdata.extend(txs);
+    seal(prev, salt, txdata)
+}
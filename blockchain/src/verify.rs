@@ -0,0 +1,498 @@
+use crate::coins::{CoinEntry, CoinView};
+use crate::names::check_covenants;
+use crate::Result;
+use handshake_primitives::{Block, Outpoint, Transaction};
+use handshake_protocol::consensus::{
+    get_reward, max_coin, MAX_BLOCK_OPENS, MAX_BLOCK_RENEWALS, MAX_BLOCK_SIGOPS, MAX_BLOCK_SIZE,
+    MAX_BLOCK_UPDATES, MAX_BLOCK_WEIGHT,
+};
+use handshake_protocol::network::Network;
+use handshake_types::Amount;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Reject codes sent to peers alongside the reason a block or transaction was refused.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RejectCode {
+    Invalid = 0x10,
+    Obsolete = 0x11,
+    Duplicate = 0x12,
+    Nonstandard = 0x40,
+    Dust = 0x41,
+    InsufficientFee = 0x42,
+    Checkpoint = 0x43,
+}
+
+/// A consensus failure. The reason strings and ban scores match hsd's, so peers see the same
+/// rejects from us as they would from an hsd node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    pub code: RejectCode,
+    pub reason: &'static str,
+    pub score: u32,
+}
+
+impl VerifyError {
+    pub fn invalid(reason: &'static str, score: u32) -> Self {
+        VerifyError {
+            code: RejectCode::Invalid,
+            reason,
+            score,
+        }
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Verification failure: {} (code={:?} score={})",
+            self.reason, self.code, self.score
+        )
+    }
+}
+
+pub type VerifyResult<T> = std::result::Result<T, VerifyError>;
+
+/// Context-free checks on a block body: commitments, coinbase placement, size and per-block
+/// covenant limits.
+pub fn check_block(block: &Block) -> VerifyResult<()> {
+    if block.txdata.is_empty() || block.get_base_size() > MAX_BLOCK_SIZE {
+        return Err(VerifyError::invalid("bad-blk-length", 100));
+    }
+
+    if block.get_weight() > MAX_BLOCK_WEIGHT {
+        return Err(VerifyError::invalid("bad-blk-weight", 100));
+    }
+
+    if block.header.merkle_root != block.create_merkle_root() {
+        return Err(VerifyError::invalid("bad-txnmrklroot", 100));
+    }
+
+    if block.header.witness_root != block.create_witness_root() {
+        return Err(VerifyError::invalid("bad-witnessroot", 100));
+    }
+
+    if !block.txdata[0].is_coinbase() {
+        return Err(VerifyError::invalid("bad-cb-missing", 100));
+    }
+
+    let mut opens = 0;
+    let mut updates = 0;
+    let mut renewals = 0;
+
+    for (i, tx) in block.txdata.iter().enumerate() {
+        if i > 0 && tx.is_coinbase() {
+            return Err(VerifyError::invalid("bad-cb-multiple", 100));
+        }
+
+        check_sanity(tx)?;
+
+        opens += tx.count_opens();
+        if opens > MAX_BLOCK_OPENS {
+            return Err(VerifyError::invalid("bad-blk-opens", 100));
+        }
+
+        updates += tx.count_updates();
+        if updates > MAX_BLOCK_UPDATES {
+            return Err(VerifyError::invalid("bad-blk-updates", 100));
+        }
+
+        renewals += tx.count_renewals();
+        if renewals > MAX_BLOCK_RENEWALS {
+            return Err(VerifyError::invalid("bad-blk-renewals", 100));
+        }
+    }
+
+    Ok(())
+}
+
+/// Context-free checks on a single transaction.
+pub fn check_sanity(tx: &Transaction) -> VerifyResult<()> {
+    if tx.inputs.is_empty() {
+        return Err(VerifyError::invalid("bad-txns-vin-empty", 100));
+    }
+
+    if tx.outputs.is_empty() {
+        return Err(VerifyError::invalid("bad-txns-vout-empty", 100));
+    }
+
+    if tx.get_base_size() > MAX_BLOCK_SIZE {
+        return Err(VerifyError::invalid("bad-txns-oversize", 100));
+    }
+
+    let mut total = Amount::ZERO;
+
+    for output in tx.outputs.iter() {
+        if output.value.as_doos() > max_coin().as_doos() {
+            return Err(VerifyError::invalid("bad-txns-vout-toolarge", 100));
+        }
+
+        total = match total.checked_add(output.value) {
+            Some(total) if total.as_doos() <= max_coin().as_doos() => total,
+            _ => return Err(VerifyError::invalid("bad-txns-txouttotal-toolarge", 100)),
+        };
+    }
+
+    //The coinbase and airdrop claims all use null prevouts, so only regular spends are checked.
+    if !tx.is_coinbase() {
+        let mut prevouts = HashSet::new();
+
+        for input in tx.inputs.iter() {
+            if !prevouts.insert(input.prevout) {
+                return Err(VerifyError::invalid("bad-txns-inputs-duplicate", 100));
+            }
+
+            if input.prevout.is_null() {
+                return Err(VerifyError::invalid("bad-txns-prevout-null", 10));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that depend on the block's position: the coinbase must commit to the height, and every
+/// transaction must be final at that height and the previous block's median time past.
+pub fn check_context(block: &Block, height: u32, median_time: u64) -> VerifyResult<()> {
+    if block.txdata[0].locktime != height {
+        return Err(VerifyError::invalid("bad-cb-height", 100));
+    }
+
+    //The coinbase commits to its height through the locktime, so it is exempt from finality.
+    for tx in block.txdata.iter().skip(1) {
+        if !tx.is_final(height, median_time) {
+            return Err(VerifyError::invalid("bad-txns-nonfinal", 10));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the block's spends against the coin view: every input must exist, be unspent and (for
+/// coinbase outputs) be mature, the block must stay under the sigop limit, no transaction may spend
+/// more than it has, and the coinbase may claim at most the subsidy plus fees. Outputs created
+/// earlier in the block are tracked here, so `view` should be the state as of the previous block.
+/// Returns the total fees.
+pub fn check_inputs(
+    block: &Block,
+    height: u32,
+    network: Network,
+    view: &CoinView,
+) -> Result<Amount> {
+    let mut created: HashMap<Outpoint, CoinEntry> = HashMap::new();
+    let mut spent = HashSet::new();
+    let mut sigops = 0;
+    let mut fees = Amount::ZERO;
+
+    for tx in block.txdata.iter() {
+        if !tx.is_coinbase() {
+            let mut value = Amount::ZERO;
+            let mut coins = Vec::with_capacity(tx.inputs.len());
+
+            for input in tx.inputs.iter() {
+                if !spent.insert(input.prevout) {
+                    return Err(VerifyError::invalid("bad-txns-inputs-missingorspent", 0).into());
+                }
+
+                let coin = match created.remove(&input.prevout) {
+                    Some(coin) => coin,
+                    None => match view.get(&input.prevout)? {
+                        Some(coin) => coin,
+                        None => {
+                            return Err(
+                                VerifyError::invalid("bad-txns-inputs-missingorspent", 0).into()
+                            )
+                        }
+                    },
+                };
+
+                if !coin.is_mature(height, network.coinbase_maturity()) {
+                    return Err(
+                        VerifyError::invalid("bad-txns-premature-spend-of-coinbase", 0).into(),
+                    );
+                }
+
+                sigops += coin.address().get_sigops(&input.witness);
+                if sigops > MAX_BLOCK_SIGOPS {
+                    return Err(VerifyError::invalid("bad-blk-sigops", 100).into());
+                }
+
+                value = match value.checked_add(coin.value()) {
+                    Some(value) if value.as_doos() <= max_coin().as_doos() => value,
+                    _ => {
+                        return Err(
+                            VerifyError::invalid("bad-txns-inputvalues-outofrange", 100).into()
+                        )
+                    }
+                };
+
+                coins.push(coin);
+            }
+
+            check_covenants(tx, &coins)?;
+
+            //Sanity checks already bounded the output total.
+            let out = tx.get_output_value().unwrap_or(Amount::ZERO);
+
+            let fee = match value.checked_sub(out) {
+                Some(fee) => fee,
+                None => return Err(VerifyError::invalid("bad-txns-in-belowout", 100).into()),
+            };
+
+            fees = match fees.checked_add(fee) {
+                Some(fees) if fees.as_doos() <= max_coin().as_doos() => fees,
+                _ => {
+                    return Err(
+                        VerifyError::invalid("bad-txns-accumulated-fee-outofrange", 100).into(),
+                    )
+                }
+            };
+        }
+
+        if tx.is_coinbase() {
+            check_covenants(tx, &[])?;
+        }
+
+        let txid = tx.hash();
+        let coinbase = tx.is_coinbase();
+        for (index, output) in tx.outputs.iter().enumerate() {
+            let coin = CoinEntry::new(output.clone(), height, coinbase);
+            created.insert(Outpoint::new(txid, index as u32), coin);
+        }
+    }
+
+    check_reward(block, height, network, fees)?;
+
+    Ok(fees)
+}
+
+/// The coinbase may pay out at most the block subsidy plus the fees of the block's transactions.
+pub fn check_reward(
+    block: &Block,
+    height: u32,
+    network: Network,
+    fees: Amount,
+) -> VerifyResult<()> {
+    let reward = get_reward(height, network.halvening_interval());
+
+    let limit = match reward.checked_add(fees) {
+        Some(limit) => limit,
+        None => return Err(VerifyError::invalid("bad-cb-amount", 100)),
+    };
+
+    let claimed = block.txdata[0].get_output_value().unwrap_or(Amount::ZERO);
+
+    if claimed.as_doos() > limit.as_doos() {
+        return Err(VerifyError::invalid("bad-cb-amount", 100));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{block, coinbase_paying, output, spend_paying, spend_to};
+    use crate::ChainError;
+    use extended_primitives::Hash;
+    use handshake_primitives::covenants::OpenCovenant;
+    use handshake_primitives::Covenant;
+    use handshake_types::NameHash;
+
+    fn prevout(n: u8) -> Outpoint {
+        Outpoint::new(Hash::from([n; 32]), 0)
+    }
+
+    #[test]
+    fn test_check_block() {
+        let valid = block(vec![coinbase_paying(1, 0)]);
+        assert_eq!(check_block(&valid), Ok(()));
+
+        let mut bad_root = valid.clone();
+        bad_root.header.merkle_root = Hash::default();
+        assert_eq!(
+            check_block(&bad_root).unwrap_err().reason,
+            "bad-txnmrklroot"
+        );
+
+        let mut bad_witness = valid.clone();
+        bad_witness.header.witness_root = Hash::default();
+        assert_eq!(
+            check_block(&bad_witness).unwrap_err().reason,
+            "bad-witnessroot"
+        );
+
+        let missing = block(vec![spend_paying(prevout(1), 0)]);
+        assert_eq!(check_block(&missing).unwrap_err().reason, "bad-cb-missing");
+
+        let multiple = block(vec![coinbase_paying(1, 0), coinbase_paying(1, 1)]);
+        assert_eq!(
+            check_block(&multiple).unwrap_err().reason,
+            "bad-cb-multiple"
+        );
+
+        let duplicate = block(vec![
+            coinbase_paying(1, 0),
+            spend_to(
+                vec![prevout(1), prevout(1)],
+                vec![output(0, Covenant::None)],
+            ),
+        ]);
+        let err = check_block(&duplicate).unwrap_err();
+        assert_eq!(err.reason, "bad-txns-inputs-duplicate");
+        assert_eq!(err.score, 100);
+
+        let null = block(vec![
+            coinbase_paying(1, 0),
+            spend_to(
+                vec![prevout(1), Outpoint::default()],
+                vec![output(0, Covenant::None)],
+            ),
+        ]);
+        let err = check_block(&null).unwrap_err();
+        assert_eq!(err.reason, "bad-txns-prevout-null");
+        assert_eq!(err.score, 10);
+    }
+
+    #[test]
+    fn test_covenant_limits() {
+        let mut cb = coinbase_paying(1, 0);
+        let open = output(
+            0,
+            Covenant::Open(OpenCovenant {
+                name_hash: NameHash::default(),
+                height: 0,
+                name: "handshake".parse().unwrap(),
+            }),
+        );
+
+        cb.outputs = vec![open; MAX_BLOCK_OPENS];
+        assert_eq!(check_block(&block(vec![cb.clone()])), Ok(()));
+
+        cb.outputs.push(cb.outputs[0].clone());
+        assert_eq!(
+            check_block(&block(vec![cb])).unwrap_err().reason,
+            "bad-blk-opens"
+        );
+    }
+
+    #[test]
+    fn test_check_context() {
+        let mut locked = spend_paying(prevout(1), 0);
+        locked.locktime = 5;
+        locked.inputs[0].sequence = 0;
+
+        let early = block(vec![coinbase_paying(5, 0), locked.clone()]);
+        assert_eq!(
+            check_context(&early, 4, 0).unwrap_err().reason,
+            "bad-cb-height"
+        );
+        assert_eq!(
+            check_context(&early, 5, 0).unwrap_err().reason,
+            "bad-txns-nonfinal"
+        );
+
+        let later = block(vec![coinbase_paying(6, 0), locked]);
+        assert_eq!(check_context(&later, 6, 0), Ok(()));
+    }
+
+    fn coin(value: u64, coinbase: bool) -> CoinEntry {
+        CoinEntry::new(output(value, Covenant::None), 0, coinbase)
+    }
+
+    //The reject reason of a failed input check.
+    fn reason(result: Result<Amount>) -> &'static str {
+        match result {
+            Err(ChainError::Verify(e)) => e.reason,
+            other => panic!("expected a verify error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_check_inputs() {
+        let reward = get_reward(1, Network::Regtest.halvening_interval()).as_doos();
+        let mut view = CoinView::new();
+        view.add(prevout(1), coin(1000, false)).unwrap();
+        view.add(prevout(2), coin(500, false)).unwrap();
+
+        //Spend one coin, then spend the change in the same block.
+        let first = spend_paying(prevout(1), 900);
+        let second = spend_paying(Outpoint::new(first.hash(), 0), 850);
+        let valid = block(vec![
+            coinbase_paying(1, reward + 150),
+            first.clone(),
+            second,
+        ]);
+        assert_eq!(
+            check_inputs(&valid, 1, Network::Regtest, &view),
+            Ok(Amount::from_doos(150))
+        );
+
+        let greedy = block(vec![coinbase_paying(1, reward + 101), first.clone()]);
+        assert_eq!(
+            reason(check_inputs(&greedy, 1, Network::Regtest, &view)),
+            "bad-cb-amount"
+        );
+
+        let double = block(vec![
+            coinbase_paying(1, 0),
+            first,
+            spend_paying(prevout(1), 0),
+        ]);
+        assert_eq!(
+            reason(check_inputs(&double, 1, Network::Regtest, &view)),
+            "bad-txns-inputs-missingorspent"
+        );
+
+        let missing = block(vec![coinbase_paying(1, 0), spend_paying(prevout(3), 0)]);
+        assert_eq!(
+            reason(check_inputs(&missing, 1, Network::Regtest, &view)),
+            "bad-txns-inputs-missingorspent"
+        );
+
+        let overspend = block(vec![coinbase_paying(1, 0), spend_paying(prevout(2), 501)]);
+        assert_eq!(
+            reason(check_inputs(&overspend, 1, Network::Regtest, &view)),
+            "bad-txns-in-belowout"
+        );
+    }
+
+    #[test]
+    fn test_sigops_limit() {
+        let prevouts: Vec<Outpoint> = (0..=MAX_BLOCK_SIGOPS as u32)
+            .map(|i| Outpoint::new(Hash::default(), i))
+            .collect();
+        let mut view = CoinView::new();
+        for prevout in prevouts.iter() {
+            view.add(*prevout, coin(1, false)).unwrap();
+        }
+
+        let heavy = block(vec![
+            coinbase_paying(1, 0),
+            spend_to(prevouts, vec![output(0, Covenant::None)]),
+        ]);
+        assert_eq!(
+            reason(check_inputs(&heavy, 1, Network::Regtest, &view)),
+            "bad-blk-sigops"
+        );
+    }
+
+    #[test]
+    fn test_coinbase_maturity() {
+        let mut view = CoinView::new();
+        view.add(prevout(1), coin(1000, true)).unwrap();
+
+        let maturity = Network::Regtest.coinbase_maturity();
+        let spend_at = |height: u32| {
+            let txdata = vec![coinbase_paying(height, 0), spend_paying(prevout(1), 1000)];
+            check_inputs(&block(txdata), height, Network::Regtest, &view)
+        };
+
+        assert_eq!(
+            reason(spend_at(maturity - 1)),
+            "bad-txns-premature-spend-of-coinbase"
+        );
+        assert!(spend_at(maturity).is_ok());
+    }
+}
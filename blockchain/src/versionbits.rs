@@ -0,0 +1,105 @@
+use extended_primitives::Hash;
+use handshake_protocol::consensus::{
+    MANDATORY_LOCKTIME_FLAGS, MANDATORY_VERIFY_COVENANT_FLAGS, MANDATORY_VERIFY_FLAGS,
+    VERIFY_COVENANTS_HARDENED, VERSION_TOP_BITS, VERSION_TOP_MASK,
+};
+use std::collections::HashMap;
+
+/// Where a deployment is in its BIP9 lifecycle. States only change at the end of a miner window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThresholdState {
+    /// The start time has not been reached.
+    Defined,
+    /// Miners are signalling, waiting on the threshold to be met within a window.
+    Started,
+    /// The threshold was met, the deployment activates after one more window.
+    LockedIn,
+    Active,
+    /// The timeout passed before the deployment locked in.
+    Failed,
+}
+
+/// Whether `version` signals for the deployment on `bit`.
+pub fn has_bit(version: u32, bit: u8) -> bool {
+    version & VERSION_TOP_MASK == VERSION_TOP_BITS && version & (1 << bit) != 0
+}
+
+/// Deployment states computed so far, keyed by deployment bit and the hash of the last entry of
+/// a window. An entry's state only depends on its ancestors, so entries stay valid across reorgs.
+#[derive(Debug, Default)]
+pub struct StateCache {
+    states: HashMap<(u8, Hash), ThresholdState>,
+}
+
+impl StateCache {
+    pub fn new() -> Self {
+        StateCache::default()
+    }
+
+    pub fn get(&self, bit: u8, hash: &Hash) -> Option<ThresholdState> {
+        self.states.get(&(bit, *hash)).cloned()
+    }
+
+    pub fn set(&mut self, bit: u8, hash: Hash, state: ThresholdState) {
+        self.states.insert((bit, hash), state);
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+/// The verification flags in force for a block, given the deployments active at its parent.
+///
+/// Nothing in the chain checks blocks against these yet: there is no script interpreter, and the
+/// hardening flag only matters to claim proofs, which aren't verified either. Until then they are
+/// output-only, for miners to carry into `BlockTemplate::flags` (see
+/// `BlockTemplateBuilder::with_flags`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeploymentState {
+    /// Script verification flags.
+    pub flags: u32,
+    /// Locktime and sequence lock flags.
+    pub lock_flags: u32,
+    /// Covenant verification flags.
+    pub name_flags: u32,
+}
+
+impl DeploymentState {
+    pub fn new() -> Self {
+        DeploymentState {
+            flags: MANDATORY_VERIFY_FLAGS,
+            lock_flags: MANDATORY_LOCKTIME_FLAGS,
+            name_flags: MANDATORY_VERIFY_COVENANT_FLAGS,
+        }
+    }
+
+    pub fn has_hardening(&self) -> bool {
+        self.name_flags & VERIFY_COVENANTS_HARDENED != 0
+    }
+}
+
+impl Default for DeploymentState {
+    fn default() -> Self {
+        DeploymentState::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_bit() {
+        assert!(has_bit(1 << 28, 28));
+        assert!(has_bit((1 << 28) | 1, 0));
+        assert!(!has_bit(1 << 28, 0));
+
+        //Versions outside the top bits signal nothing.
+        assert!(!has_bit(0x2000_0001, 0));
+    }
+}
@@ -1,6 +1,7 @@
 use brontide;
 use extended_primitives;
 use handshake_protocol;
+use handshake_store::StoreError;
 use hex;
 use std::fmt;
 use std::net::AddrParseError;
@@ -17,6 +18,7 @@ pub enum Error {
     InvalidHostname(AddrParseError),
     Hex(hex::FromHexError),
     FutureIO(futures::io::Error),
+    Store(StoreError),
     LockError,
     Base32,
     InvalidIdentityKey,
@@ -48,6 +50,12 @@ impl From<extended_primitives::BufferError> for Error {
     }
 }
 
+impl From<StoreError> for Error {
+    fn from(e: StoreError) -> Self {
+        Error::Store(e)
+    }
+}
+
 impl From<hex::FromHexError> for Error {
     fn from(e: hex::FromHexError) -> Self {
         Error::Hex(e)
@@ -69,6 +77,7 @@ impl fmt::Display for Error {
             Error::InvalidHostname(ref e) => write!(f, "Invalid Hostname error: {}", e),
             Error::FutureIO(ref e) => write!(f, "Futures IO error: {}", e),
             Error::Hex(ref e) => write!(f, "Hex error: {}", e),
+            Error::Store(ref e) => write!(f, "Store error: {}", e),
             Error::Base32 => write!(f, "Base32 error"),
             Error::InvalidIdentityKey => write!(f, "Invalid Identity Key"),
             Error::InvalidNetAddress => write!(f, "Invalid Network Address"),
@@ -228,6 +228,12 @@ impl VersionPacket {
         }
     }
 
+    /// Advertises `services` rather than the full node default, e.g. when pruned.
+    pub fn with_services(mut self, services: Services) -> Self {
+        self.services = services;
+        self
+    }
+
     fn decode(mut packet: Buffer) -> Result<Self> {
         let version = packet.read_u32()?;
         let services = packet.read_u32()?;
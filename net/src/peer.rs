@@ -19,6 +19,13 @@ use handshake_types::difficulty::Difficulty;
 use romio::TcpStream;
 use std::sync::{Arc, RwLock};
 
+//The version packet we open the handshake with, advertising `services`.
+pub(crate) fn version_packet(address: NetAddress, services: Services) -> VersionPacket {
+    //Need to pass in height dynamically. TODO
+    //Also need to pass in no_relay dynamically TODO
+    VersionPacket::new(address, 0, false).with_services(services)
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Direction {
     Outbound = 0,
@@ -89,6 +96,8 @@ pub struct Peer {
     pub tx: Mutex<UnboundedSender<Packet>>,
     pub ping_stats: Mutex<PingStats>,
     pub prefer_headers: Mutex<bool>,
+    //The services we advertise to this peer.
+    pub local_services: Services,
     // // set of all hashes known to this peer (so no need to send)
     // tracking_adapter: TrackingAdapter,
     // tracker: Arc<conn::Tracker>,
@@ -106,6 +115,7 @@ impl Peer {
         addr: NetAddress,
         key: [u8; 32],
         network: Network,
+        services: Services,
         tx: UnboundedSender<Packet>,
     ) -> Result<Peer> {
         //TODO catch error, don't unwrap.
@@ -158,6 +168,7 @@ impl Peer {
             tx: Mutex::new(tx),
             ping_stats: Mutex::new(ping_stats),
             prefer_headers: Mutex::new(false),
+            local_services: services,
         })
     }
 
@@ -407,9 +418,7 @@ impl Peer {
     }
 
     pub async fn send_version(&self) -> Result<()> {
-        //Need to pass in height dynamically. TODO
-        //Also need to pass in no_relay dynamically TODO
-        let packet = Packet::Version(VersionPacket::new(self.info.address, 0, false));
+        let packet = Packet::Version(version_packet(self.info.address, self.local_services));
         //Each packet might have a different timeout requirement -> We should probably set this in
         //the packet struct itself.
         self.send(packet).await?;
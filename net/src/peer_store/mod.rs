@@ -1,8 +1,11 @@
 use crate::NetAddress;
 use crate::Result;
-use extended_primitives::Uint256;
+use extended_primitives::{Buffer, Uint256};
 use futures::lock::Mutex;
+use handshake_protocol::encoding::{Decodable, Encodable};
 use handshake_protocol::network::Network;
+use handshake_store::kv::key;
+use handshake_store::{Batch, KvRead, KvStore, MemoryStore};
 use handshake_types::Time;
 use log::info;
 use rand::{thread_rng, Rng};
@@ -20,15 +23,19 @@ use common::{
     TRIED_BUCKET_COUNT, TRIED_BUCKET_COUNT_LOG2,
 };
 
+/// Store namespace for saved addresses, keyed by socket address.
+const ADDRESS_PREFIX: u8 = b'a';
+
+fn address_key(address: &NetAddress) -> Vec<u8> {
+    key(ADDRESS_PREFIX, &[address.address.to_string().as_bytes()])
+}
+
 //TODO remove all get_muts when they are returning arcs. No need.
 //TODO clean up all the locks and await. I think most things should be wrapped into a getter
 //function that awaits the lock for you, and that way it's a lot cleaner.
 //TODO we can do the bitcoin model where the whole function is locked, so we have an unscore
 //function that does the dirty work, and then a non-underscore function that secures the lock.
 
-//TODO PeerStore should just accept a config of a trait that implements Datastore
-//That way anyone or any plugin can just provide their own store and it will get passed down to
-//this. SO if someone wants to store peers in leveldb. They can do that.
 //TODO put name in here as an option.
 //Change Peer Store config to Peer Store Builder....
 //Then we can set these as defaults.
@@ -53,11 +60,17 @@ pub struct PeerStore {
     tried_collisions: Mutex<Vec<u32>>,
     //Should almost never change so we don't need a mutex here.
     key: Uint256,
+    //Where addresses are saved between runs. Any store works, e.g. the chain's.
+    db: Arc<dyn KvStore>,
 }
 
 //I think the goal is to keep this file as a json file to allow easy parsing.
 impl PeerStore {
     pub fn new() -> PeerStore {
+        PeerStore::with_store(Arc::new(MemoryStore::new()))
+    }
+
+    pub fn with_store(db: Arc<dyn KvStore>) -> PeerStore {
         let id_count = Mutex::new(0);
         let map_info = Mutex::new(HashMap::new());
         let map_address = Mutex::new(HashMap::new());
@@ -85,7 +98,38 @@ impl PeerStore {
             last_good,
             tried_collisions,
             key,
+            db,
+        }
+    }
+
+    /// Saves every address we know of to the store, replacing whatever was saved before.
+    pub async fn flush(&self) -> Result<()> {
+        let mut batch = Batch::new();
+
+        for (saved, _) in self.db.prefix(&[ADDRESS_PREFIX])? {
+            batch.delete(&saved);
+        }
+
+        let map_info = self.map_info.lock().await;
+        for data in map_info.values() {
+            let data = data.lock().await;
+            batch.put(&address_key(&data.address), &data.address.encode());
+        }
+
+        self.db.write(batch)?;
+
+        Ok(())
+    }
+
+    /// The addresses saved by the last flush.
+    pub fn load(&self) -> Result<Vec<NetAddress>> {
+        let mut addresses = Vec::new();
+
+        for (_, raw) in self.db.prefix(&[ADDRESS_PREFIX])? {
+            addresses.push(NetAddress::decode(&mut Buffer::from(raw))?);
         }
+
+        Ok(addresses)
     }
 
     //Returns an address that is attempting to be evicted by another address.
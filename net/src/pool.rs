@@ -14,6 +14,7 @@ use handshake_protocol::network::Network;
 use futures::channel::mpsc::{UnboundedReceiver, unbounded, UnboundedSender};
 use futures::stream::StreamExt;
 use crate::packets::Packet;
+use crate::types::Services;
 
 //TODO cleanup imports
 // use crate::blockchain::chain::Chain;
@@ -25,6 +26,8 @@ use crate::packets::Packet;
 pub struct PoolConfig {
     pub max_outbound: u32,
     pub max_inbound: u32,
+    //What we advertise to peers. `Services::local(chain.is_pruned())`.
+    pub services: Services,
 }
 
 //TODO possible name this p2p server.
@@ -213,8 +216,9 @@ impl Pool {
                 //TODO need to impl network (or remove it from this function)
                 //Might not want to throw the error here, and just continue.
                 let tx = self.tx.clone();
+                let services = self.config.services;
                 juliex::spawn(async move {
-                let mut peer = Peer::connect(address_connect.unwrap(), [1; 32], Network::Testnet, tx).await.unwrap();
+                let mut peer = Peer::connect(address_connect.unwrap(), [1; 32], Network::Testnet, services, tx).await.unwrap();
                     peer.handle_messages().await;
                 });
             }
@@ -442,13 +446,16 @@ impl Pool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::peer::version_packet;
     use futures::executor::block_on;
+    use handshake_protocol::encoding::Encodable;
 
     #[test]
     fn test_pool_run() {
         let config = PoolConfig {
             max_outbound: 8,
             max_inbound: 8,
+            services: Services::LOCAL_SERVICES,
         };
 
         let pool = Pool::new(config).unwrap();
@@ -456,4 +463,21 @@ mod tests {
 
         block_on(async { pool.start().await});
     }
+
+    #[test]
+    fn test_pruned_version() {
+        let config = PoolConfig {
+            max_outbound: 8,
+            max_inbound: 8,
+            services: Services::local(true),
+        };
+
+        let address = NetAddress::new("127.0.0.1:12038".parse().unwrap(), [0; 33].into());
+        let mut raw = version_packet(address, config.services).encode();
+
+        //The services follow the protocol version.
+        raw.read_u32().unwrap();
+        let services = Services::from_bits_truncate(raw.read_u32().unwrap());
+        assert!(!services.contains(Services::NETWORK));
+    }
 }
@@ -13,5 +13,18 @@ bitflags! {
         const FULL_NODE = 0 | Services::NETWORK.bits;
         const REQUIRED_SERVICES = 0 | Services::NETWORK.bits;
         const LOCAL_SERVICES = 0 | Services::NETWORK.bits;
+        /// What a pruned node offers: everything but NETWORK, since it can't serve old blocks.
+        const PRUNED_SERVICES = Services::LOCAL_SERVICES.bits & !Services::NETWORK.bits;
+    }
+}
+
+impl Services {
+    /// The services we advertise, depending on whether our chain is pruned.
+    pub fn local(pruned: bool) -> Services {
+        if pruned {
+            Services::PRUNED_SERVICES
+        } else {
+            Services::LOCAL_SERVICES
+        }
     }
 }
@@ -1,6 +1,7 @@
 use bech32::{u5, FromBase32, ToBase32};
 use extended_primitives::Buffer;
 use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_script::Witness;
 use std::fmt;
 use std::str::FromStr;
 
@@ -124,6 +125,23 @@ impl Address {
         self.is_null_data()
     }
 
+    /// Signature operations needed to spend this address with the given witness. Pubkeyhash
+    /// spends are a single checksig, scripthash spends count the redeem script.
+    pub fn get_sigops(&self, witness: &Witness) -> usize {
+        if self.version != 0 {
+            return 0;
+        }
+
+        match self.hash {
+            Payload::PubkeyHash(_) => 1,
+            Payload::ScriptHash(_) => match witness.get_redeem() {
+                Some(redeem) => redeem.get_sigops(true),
+                None => 0,
+            },
+            Payload::Unknown(_) => 0,
+        }
+    }
+
     pub fn to_bech32(&self) -> String {
         //Also todo this should probably just be in toString, and should use writers so that we
         //don't allocate.
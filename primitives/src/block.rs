@@ -1,8 +1,10 @@
 use crate::BlockHeader;
 use crate::Transaction;
 use encodings::hex::{FromHex, ToHex};
-use extended_primitives::Buffer;
+use extended_primitives::{Buffer, Hash, VarInt};
 use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_protocol::consensus::WITNESS_SCALE_FACTOR;
+use handshake_types::MerkleTree;
 
 /// A Handshake block, which is a collection of transactions with an attached
 /// proof of work.
@@ -14,6 +16,36 @@ pub struct Block {
     pub txdata: Vec<Transaction>,
 }
 
+impl Block {
+    pub fn create_merkle_root(&self) -> Hash {
+        let leaves = self.txdata.iter().map(|tx| tx.hash()).collect();
+        MerkleTree::from_leaves(leaves).get_root()
+    }
+
+    pub fn create_witness_root(&self) -> Hash {
+        let leaves = self.txdata.iter().map(|tx| tx.witness_hash()).collect();
+        MerkleTree::from_leaves(leaves).get_root()
+    }
+
+    pub fn get_base_size(&self) -> usize {
+        let mut size = self.header.size();
+        size += VarInt::from(self.txdata.len()).encoded_size() as usize;
+
+        for tx in self.txdata.iter() {
+            size += tx.get_base_size();
+        }
+
+        size
+    }
+
+    pub fn get_weight(&self) -> usize {
+        let base = self.get_base_size();
+        let witness: usize = self.txdata.iter().map(|tx| tx.get_witness_size()).sum();
+
+        base * (WITNESS_SCALE_FACTOR - 1) + base + witness
+    }
+}
+
 impl Encodable for Block {
     fn size(&self) -> usize {
         //TODO relies on tx's get size which is not done.
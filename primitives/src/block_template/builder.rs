@@ -5,7 +5,10 @@ use crate::{Address, BlockTemplate, Input, Output, Transaction};
 use encodings::FromHex;
 use extended_primitives::{Buffer, Hash, Uint256};
 use handshake_encoding::Decodable;
-use handshake_protocol::consensus::get_reward;
+use handshake_protocol::consensus::{
+    get_median_time, get_next_bits, get_reward, target_from_compact_bits, HeaderInfo,
+};
+use handshake_protocol::network::Network;
 use handshake_types::{Amount, MerkleTree, Time};
 //@todo make a builder for block template, since so many of the options are likely not easy to do
 //via just a simple new function. Have "new" cover the most basic of options, and then the builder
@@ -21,7 +24,7 @@ pub struct BlockTemplateBuilder {
     pub bits: u32,
     pub target: Uint256,
     pub median_time: Time,
-    //@todo see: https://github.com/handshake-org/hsd/blob/master/lib/blockchain/chain.js#L3480
+    /// Script verification flags in force for the block, see `with_flags`.
     pub flags: u32,
     // To show who the block is mined by: eg. "Mined by Bitamin" see: https://github.com/handshake-org/hsd/blob/master/lib/mining/miner.js#L472
     //@todo Should default to "mined by RSD"
@@ -108,6 +111,36 @@ impl BlockTemplateBuilder {
         self
     }
 
+    /// Sets bits and target from the chain being built on. `ancestors` should yield the previous
+    /// block first, walking back towards genesis.
+    pub fn with_next_bits<I, E>(mut self, network: Network, ancestors: I) -> Self
+    where
+        I: IntoIterator<Item = E>,
+        E: HeaderInfo,
+    {
+        self.bits = get_next_bits(network, self.time.to_seconds(), ancestors);
+        self.target = target_from_compact_bits(self.bits).unwrap_or_else(|x| x);
+        self
+    }
+
+    /// Sets the median time past from the chain being built on. `ancestors` should yield the
+    /// previous block first. The template's time must be above this value.
+    pub fn with_median_time<I, E>(mut self, ancestors: I) -> Self
+    where
+        I: IntoIterator<Item = E>,
+        E: HeaderInfo,
+    {
+        self.median_time = Time::from(get_median_time(ancestors));
+        self
+    }
+
+    /// Sets the script verification flags the block will be checked with, the `flags` of the
+    /// chain's deployment state for the block being built on.
+    pub fn with_flags(mut self, flags: u32) -> Self {
+        self.flags = flags;
+        self
+    }
+
     pub fn with_address(mut self, address: Address) -> Self {
         self.address = address;
         self
@@ -30,7 +30,7 @@ pub struct BlockTemplate {
     pub bits: u32,
     pub target: Uint256,
     pub median_time: Time,
-    //@todo see: https://github.com/handshake-org/hsd/blob/master/lib/blockchain/chain.js#L3480
+    /// Script verification flags in force for the block, from the chain's deployment state.
     pub flags: u32,
     // To show who the block is mined by: eg. "Mined by Bitamin" see: https://github.com/handshake-org/hsd/blob/master/lib/mining/miner.js#L472
     //@todo Should default to "mined by RSD"
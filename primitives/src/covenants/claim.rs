@@ -21,7 +21,7 @@ impl ClaimCovenant {
         let mut items = Vec::new();
 
         let mut buffer = Buffer::new();
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
         items.push(buffer);
 
         let mut buffer = Buffer::new();
@@ -50,7 +50,7 @@ impl ClaimCovenant {
     //@todo this should really return a result.
     pub fn from_items(mut items: Vec<Buffer>) -> ClaimCovenant {
         let name_len = items[2].len();
-        let name_hash = items[0].read_hash().unwrap();
+        let name_hash = NameHash::from(items[0].read_hash().unwrap());
         let height = items[1].read_u32().unwrap();
         let name = items[2].read_string(name_len).unwrap();
         let flags = items[3].read_u8().unwrap();
@@ -103,7 +103,7 @@ impl Encodable for ClaimCovenant {
         //Name Hash
         //Hashes are 32 bytes
         buffer.write_varint(32);
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
 
         //Height
         buffer.write_varint(4);
@@ -137,7 +137,7 @@ impl Decodable for ClaimCovenant {
         buffer.read_varint()?;
 
         buffer.read_varint()?;
-        let name_hash = buffer.read_hash()?;
+        let name_hash = NameHash::from(buffer.read_hash()?);
 
         buffer.read_varint()?;
         let height = buffer.read_u32()?;
@@ -87,6 +87,24 @@ impl Covenant {
         }
     }
 
+    //The height of the auction the covenant belongs to. Every name covenant carries one.
+    pub fn get_height(&self) -> Option<u32> {
+        match self {
+            Covenant::None => None,
+            Covenant::Claim(cov) => Some(cov.height),
+            Covenant::Bid(cov) => Some(cov.height),
+            Covenant::Open(cov) => Some(cov.height),
+            Covenant::Reveal(cov) => Some(cov.height),
+            Covenant::Redeem(cov) => Some(cov.height),
+            Covenant::Register(cov) => Some(cov.height),
+            Covenant::Update(cov) => Some(cov.height),
+            Covenant::Renew(cov) => Some(cov.height),
+            Covenant::Transfer(cov) => Some(cov.height),
+            Covenant::Finalize(cov) => Some(cov.height),
+            Covenant::Revoke(cov) => Some(cov.height),
+        }
+    }
+
     pub fn is_dustworthy(&self) -> bool {
         match self {
             Covenant::None => true,
@@ -22,7 +22,7 @@ impl FinalizeCovenant {
         let mut items = Vec::new();
 
         let mut buffer = Buffer::new();
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
         items.push(buffer);
 
         let mut buffer = Buffer::new();
@@ -53,7 +53,7 @@ impl FinalizeCovenant {
     }
 
     pub fn from_items(mut items: Vec<Buffer>) -> FinalizeCovenant {
-        let name_hash = items[0].read_hash().unwrap();
+        let name_hash = NameHash::from(items[0].read_hash().unwrap());
         let height = items[1].read_u32().unwrap();
         //Name
         let name_length = items[2].len();
@@ -113,7 +113,7 @@ impl Encodable for FinalizeCovenant {
         //Name Hash
         //Hashes are 32 bytes
         buffer.write_varint(32);
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
 
         //Height
         buffer.write_varint(4);
@@ -151,7 +151,7 @@ impl Decodable for FinalizeCovenant {
         buffer.read_varint()?;
 
         buffer.read_varint()?;
-        let name_hash = buffer.read_hash()?;
+        let name_hash = NameHash::from(buffer.read_hash()?);
 
         buffer.read_varint()?;
         let height = buffer.read_u32()?;
@@ -22,7 +22,7 @@ impl OpenCovenant {
         let mut items = Vec::new();
 
         let mut buffer = Buffer::new();
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
         items.push(buffer);
 
         let mut buffer = Buffer::new();
@@ -37,7 +37,7 @@ impl OpenCovenant {
     }
 
     pub fn from_items(mut items: Vec<Buffer>) -> OpenCovenant {
-        let name_hash = items[0].read_hash().unwrap();
+        let name_hash = NameHash::from(items[0].read_hash().unwrap());
         let height = items[1].read_u32().unwrap();
         let name_length = items[2].len();
         let name = items[2].read_string(name_length).unwrap();
@@ -76,7 +76,7 @@ impl Encodable for OpenCovenant {
 
         //Name Hash
         buffer.write_varint(32);
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
 
         //Height
         buffer.write_varint(4);
@@ -98,7 +98,7 @@ impl Decodable for OpenCovenant {
         buffer.read_varint()?;
 
         buffer.read_varint()?;
-        let name_hash = buffer.read_hash()?;
+        let name_hash = NameHash::from(buffer.read_hash()?);
 
         buffer.read_varint()?;
         let height = buffer.read_u32()?;
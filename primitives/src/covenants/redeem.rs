@@ -17,7 +17,7 @@ impl RedeemCovenant {
         let mut items = Vec::new();
 
         let mut buffer = Buffer::new();
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
         items.push(buffer);
 
         let mut buffer = Buffer::new();
@@ -28,7 +28,7 @@ impl RedeemCovenant {
     }
 
     pub fn from_items(mut items: Vec<Buffer>) -> RedeemCovenant {
-        let name_hash = items[0].read_hash().unwrap();
+        let name_hash = NameHash::from(items[0].read_hash().unwrap());
         let height = items[1].read_u32().unwrap();
 
         RedeemCovenant { name_hash, height }
@@ -58,7 +58,7 @@ impl Encodable for RedeemCovenant {
         //Name Hash
         //Hashes are 32 bytes
         buffer.write_varint(32);
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
 
         //Height
         buffer.write_varint(4);
@@ -76,7 +76,7 @@ impl Decodable for RedeemCovenant {
         buffer.read_varint()?;
 
         buffer.read_varint()?;
-        let name_hash = buffer.read_hash()?;
+        let name_hash = NameHash::from(buffer.read_hash()?);
 
         buffer.read_varint()?;
         let height = buffer.read_u32()?;
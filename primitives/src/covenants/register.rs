@@ -23,7 +23,7 @@ impl RegisterCovenant {
         let mut items = Vec::new();
 
         let mut buffer = Buffer::new();
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
         items.push(buffer);
 
         let mut buffer = Buffer::new();
@@ -42,7 +42,7 @@ impl RegisterCovenant {
     }
 
     pub fn from_items(mut items: Vec<Buffer>) -> RegisterCovenant {
-        let name_hash = items[0].read_hash().unwrap();
+        let name_hash = NameHash::from(items[0].read_hash().unwrap());
         let height = items[1].read_u32().unwrap();
         let record_length = items[2].len();
         let record_data = Buffer::from(items[2].read_bytes(record_length).unwrap());
@@ -87,7 +87,7 @@ impl Encodable for RegisterCovenant {
         //Name Hash
         //Hashes are 32 bytes
         buffer.write_varint(32);
-        buffer.write_hash(self.name_hash);
+        buffer.write_hash(self.name_hash.into());
 
         //Height
         buffer.write_varint(4);
@@ -112,7 +112,7 @@ impl Decodable for RegisterCovenant {
         buffer.read_varint()?;
 
         buffer.read_varint()?;
-        let name_hash = buffer.read_hash()?;
+        let name_hash = NameHash::from(buffer.read_hash()?);
 
         buffer.read_varint()?;
         let height = buffer.read_u32()?;
@@ -0,0 +1,59 @@
+use crate::Block;
+use encodings::hex::FromHex;
+use handshake_protocol::genesis::{
+    MAINNET_GENESIS, REGTEST_GENESIS, SIMNET_GENESIS, TESTNET_GENESIS,
+};
+use handshake_protocol::network::Network;
+
+/// Access to a network's genesis block. The protocol crate can't depend on `Block`, so this is
+/// implemented here for `Network`.
+pub trait Genesis {
+    fn genesis_block(&self) -> Block;
+}
+
+impl Genesis for Network {
+    fn genesis_block(&self) -> Block {
+        let hex = match *self {
+            Network::Mainnet => MAINNET_GENESIS,
+            Network::Testnet => TESTNET_GENESIS,
+            Network::Regtest => REGTEST_GENESIS,
+            Network::Simnet => SIMNET_GENESIS,
+        };
+
+        Block::from_hex(hex).expect("genesis blocks must decode")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extended_primitives::Hash;
+
+    #[test]
+    fn test_genesis_hashes() {
+        let networks = [
+            Network::Mainnet,
+            Network::Testnet,
+            Network::Regtest,
+            Network::Simnet,
+        ];
+
+        for network in networks.iter() {
+            let block = network.genesis_block();
+
+            assert_eq!(block.header.hash(), network.genesis_hash());
+            assert_eq!(block.header.prev_block, Hash::default());
+            assert_eq!(block.header.bits, network.pow_bits());
+            assert_eq!(block.header.merkle_root, block.create_merkle_root());
+
+            assert_eq!(block.txdata.len(), 1);
+            assert!(block.txdata[0].is_coinbase());
+        }
+
+        assert_eq!(
+            Network::Mainnet.genesis_hash(),
+            Hash::from_hex("5b6ef2d3c1f3cdcadfd9a030ba1811efdd17740f14e166489760741d075992e0")
+                .unwrap()
+        );
+    }
+}
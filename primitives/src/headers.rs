@@ -4,7 +4,7 @@ use cryptoxide::digest::Digest;
 use encodings::hex::{FromHex, FromHexError, ToHex};
 use extended_primitives::{Buffer, Hash};
 use handshake_encoding::{Decodable, DecodingError, Encodable};
-use handshake_protocol::consensus::consensus_verify_pow;
+use handshake_protocol::consensus::{consensus_verify_pow, HeaderInfo};
 use sha3::{Digest as _Digest, Sha3_256};
 
 /// A block header, which contains all the block's information except
@@ -239,6 +239,16 @@ impl Decodable for BlockHeader {
     }
 }
 
+impl HeaderInfo for BlockHeader {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+}
+
 impl ToHex for BlockHeader {
     fn to_hex(&self) -> String {
         self.encode().to_hex()
@@ -3,8 +3,10 @@ pub mod block;
 pub mod block_template;
 pub mod claim;
 pub mod covenants;
+pub mod genesis;
 pub mod headers;
 pub mod inventory;
+pub mod name_state;
 pub mod transaction;
 
 pub use crate::address::Address;
@@ -12,8 +14,10 @@ pub use crate::block::Block;
 pub use crate::block_template::BlockTemplate;
 pub use crate::claim::Claim;
 pub use crate::covenants::Covenant;
+pub use crate::genesis::Genesis;
 pub use crate::headers::BlockHeader;
 pub use crate::inventory::Inventory;
+pub use crate::name_state::{AuctionState, NameState};
 pub use crate::transaction::{Input, Outpoint, Output, Transaction};
 
 //@todo we are starting to get a few too many primitives in here, so I think this calls for some
@@ -0,0 +1,405 @@
+use crate::Outpoint;
+use extended_primitives::{Buffer, VarInt};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_protocol::network::Network;
+use handshake_types::{Amount, Name, NameHash};
+
+/// Where a name is in its auction lifecycle at a given height.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuctionState {
+    Opening,
+    Bidding,
+    Reveal,
+    Closed,
+    Revoked,
+    Expired,
+}
+
+//Flags for the optional fields at the end of the encoding.
+const FLAG_TRANSFER: u8 = 1 << 0;
+const FLAG_REVOKED: u8 = 1 << 1;
+const FLAG_CLAIMED: u8 = 1 << 2;
+const FLAG_RENEWALS: u8 = 1 << 3;
+const FLAG_REGISTERED: u8 = 1 << 4;
+const FLAG_EXPIRED: u8 = 1 << 5;
+const FLAG_WEAK: u8 = 1 << 6;
+
+/// The state of a name as stored in the name tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameState {
+    pub name: Name,
+    /// Not encoded, the name hash is the key the state is stored under.
+    pub name_hash: NameHash,
+    /// Height the current auction was opened (or the name was claimed).
+    pub height: u32,
+    /// Height of the last renewal.
+    pub renewal: u32,
+    pub owner: Outpoint,
+    /// The price the winner paid, i.e. the second highest bid.
+    pub value: Amount,
+    pub highest: Amount,
+    /// Resource data set by REGISTER and UPDATE.
+    pub data: Buffer,
+    /// Height of a pending transfer, or 0.
+    pub transfer: u32,
+    /// Height the name was revoked, or 0.
+    pub revoked: u32,
+    /// Height the name was claimed with a reserved name proof, or 0.
+    pub claimed: u32,
+    pub renewals: u32,
+    pub registered: bool,
+    pub expired: bool,
+    /// Claimed with a weak (non-DNSSEC-strong) proof.
+    pub weak: bool,
+}
+
+impl NameState {
+    pub fn new(name: Name, name_hash: NameHash) -> Self {
+        NameState {
+            name,
+            name_hash,
+            ..Default::default()
+        }
+    }
+
+    /// Starts tracking a name that has just been opened or claimed.
+    pub fn set(&mut self, name: Name, height: u32) {
+        self.name = name;
+        self.height = height;
+        self.renewal = height;
+    }
+
+    /// Clears everything but the name, starting a new auction at `height`.
+    pub fn reset(&mut self, height: u32) {
+        *self = NameState {
+            height,
+            renewal: height,
+            ..NameState::new(self.name.clone(), self.name_hash)
+        };
+    }
+
+    /// Resets the name if it has expired. The resource data survives an expiry, but not a revoke.
+    pub fn maybe_expire(&mut self, height: u32, network: Network) -> bool {
+        if !self.is_expired(height, network) {
+            return false;
+        }
+
+        let data = self.data.clone();
+        let revoked = self.revoked != 0;
+
+        self.reset(height);
+        self.expired = true;
+
+        if !revoked {
+            self.data = data;
+        }
+
+        true
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.height == 0 && self.renewal == 0 && self.owner.is_null() && self.claimed == 0
+    }
+
+    /// The auction phase at `height`, not taking expiry into account.
+    fn phase(&self, height: u32, network: Network) -> AuctionState {
+        if self.revoked != 0 {
+            return AuctionState::Revoked;
+        }
+
+        //Claimed names skip the auction.
+        if self.claimed != 0 {
+            return AuctionState::Closed;
+        }
+
+        let open_end = self.height + network.open_period();
+        let bid_end = open_end + network.bidding_period();
+        let reveal_end = bid_end + network.reveal_period();
+
+        if height < open_end {
+            AuctionState::Opening
+        } else if height < bid_end {
+            AuctionState::Bidding
+        } else if height < reveal_end {
+            AuctionState::Reveal
+        } else {
+            AuctionState::Closed
+        }
+    }
+
+    pub fn state(&self, height: u32, network: Network) -> AuctionState {
+        if self.is_expired(height, network) {
+            return AuctionState::Expired;
+        }
+
+        self.phase(height, network)
+    }
+
+    pub fn is_closed(&self, height: u32, network: Network) -> bool {
+        self.state(height, network) == AuctionState::Closed
+    }
+
+    /// A name expires once a revoke has matured, once it goes a renewal window without being
+    /// renewed, or if its auction closed without anyone revealing a bid.
+    pub fn is_expired(&self, height: u32, network: Network) -> bool {
+        if self.revoked != 0 {
+            return height >= self.revoked + network.auction_maturity();
+        }
+
+        if self.phase(height, network) != AuctionState::Closed {
+            return false;
+        }
+
+        if height >= self.renewal + network.renewal_window() {
+            return true;
+        }
+
+        self.owner.is_null()
+    }
+}
+
+impl Default for NameState {
+    fn default() -> Self {
+        NameState {
+            name: Name::default(),
+            name_hash: NameHash::default(),
+            height: 0,
+            renewal: 0,
+            owner: Outpoint::default(),
+            value: Amount::ZERO,
+            highest: Amount::ZERO,
+            data: Buffer::new(),
+            transfer: 0,
+            revoked: 0,
+            claimed: 0,
+            renewals: 0,
+            registered: false,
+            expired: false,
+            weak: false,
+        }
+    }
+}
+
+impl Encodable for NameState {
+    fn size(&self) -> usize {
+        let mut size = 0;
+
+        size += 1 + self.name.len();
+        size += 2 + self.data.len();
+        size += 4;
+        size += 4;
+        size += 32;
+        size += VarInt::from(self.owner.index as u64).encoded_size() as usize;
+        size += VarInt::from(self.value.as_doos()).encoded_size() as usize;
+        size += VarInt::from(self.highest.as_doos()).encoded_size() as usize;
+        size += 1;
+
+        if self.transfer != 0 {
+            size += 4;
+        }
+
+        if self.revoked != 0 {
+            size += 4;
+        }
+
+        if self.claimed != 0 {
+            size += 4;
+        }
+
+        if self.renewals != 0 {
+            size += VarInt::from(self.renewals as u64).encoded_size() as usize;
+        }
+
+        size
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        let mut field = 0;
+
+        if self.transfer != 0 {
+            field |= FLAG_TRANSFER;
+        }
+
+        if self.revoked != 0 {
+            field |= FLAG_REVOKED;
+        }
+
+        if self.claimed != 0 {
+            field |= FLAG_CLAIMED;
+        }
+
+        if self.renewals != 0 {
+            field |= FLAG_RENEWALS;
+        }
+
+        if self.registered {
+            field |= FLAG_REGISTERED;
+        }
+
+        if self.expired {
+            field |= FLAG_EXPIRED;
+        }
+
+        if self.weak {
+            field |= FLAG_WEAK;
+        }
+
+        buffer.write_u8(self.name.len() as u8);
+        buffer.write_str(&self.name);
+        buffer.write_u16(self.data.len() as u16);
+        buffer.write_bytes(&self.data);
+        buffer.write_u32(self.height);
+        buffer.write_u32(self.renewal);
+        buffer.write_hash(self.owner.txid);
+        buffer.write_varint(self.owner.index as usize);
+        buffer.write_varint(self.value.as_doos() as usize);
+        buffer.write_varint(self.highest.as_doos() as usize);
+        buffer.write_u8(field);
+
+        if self.transfer != 0 {
+            buffer.write_u32(self.transfer);
+        }
+
+        if self.revoked != 0 {
+            buffer.write_u32(self.revoked);
+        }
+
+        if self.claimed != 0 {
+            buffer.write_u32(self.claimed);
+        }
+
+        if self.renewals != 0 {
+            buffer.write_varint(self.renewals as usize);
+        }
+
+        buffer
+    }
+}
+
+impl Decodable for NameState {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        let name_length = buffer.read_u8()?;
+        let name = buffer.read_string(name_length as usize)?;
+        let name: Name = name
+            .parse()
+            .map_err(|_| DecodingError::InvalidData("Invalid name".to_string()))?;
+
+        let data_length = buffer.read_u16()?;
+        let data = Buffer::from(buffer.read_bytes(data_length as usize)?);
+
+        let height = buffer.read_u32()?;
+        let renewal = buffer.read_u32()?;
+        let txid = buffer.read_hash()?;
+        let index = buffer.read_varint()?.as_u64() as u32;
+        let value = Amount::from_doos(buffer.read_varint()?.as_u64());
+        let highest = Amount::from_doos(buffer.read_varint()?.as_u64());
+        let field = buffer.read_u8()?;
+
+        let mut state = NameState {
+            name_hash: NameHash::from_name(&name),
+            name,
+            height,
+            renewal,
+            owner: Outpoint::new(txid, index),
+            value,
+            highest,
+            data,
+            registered: field & FLAG_REGISTERED != 0,
+            expired: field & FLAG_EXPIRED != 0,
+            weak: field & FLAG_WEAK != 0,
+            ..Default::default()
+        };
+
+        if field & FLAG_TRANSFER != 0 {
+            state.transfer = buffer.read_u32()?;
+        }
+
+        if field & FLAG_REVOKED != 0 {
+            state.revoked = buffer.read_u32()?;
+        }
+
+        if field & FLAG_CLAIMED != 0 {
+            state.claimed = buffer.read_u32()?;
+        }
+
+        if field & FLAG_RENEWALS != 0 {
+            state.renewals = buffer.read_varint()?.as_u64() as u32;
+        }
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use extended_primitives::Hash;
+
+    fn state() -> NameState {
+        let name: Name = "handshake".parse().unwrap();
+
+        NameState {
+            height: 100,
+            renewal: 100,
+            ..NameState::new(name.clone(), NameHash::from_name(&name))
+        }
+    }
+
+    #[test]
+    fn test_encoding() {
+        let mut ns = state();
+        let mut encoded = ns.encode();
+        assert_eq!(encoded.len(), ns.size());
+        assert_eq!(NameState::decode(&mut encoded).unwrap(), ns);
+
+        ns.owner = Outpoint::new(Hash::from([7; 32]), 1);
+        ns.value = Amount::from_doos(1_000_000);
+        ns.highest = Amount::from_doos(5_000_000);
+        ns.data = Buffer::from(vec![0, 1, 2, 3]);
+        ns.transfer = 150;
+        ns.claimed = 120;
+        ns.renewals = 3;
+        ns.registered = true;
+        ns.weak = true;
+
+        let mut encoded = ns.encode();
+        assert_eq!(encoded.len(), ns.size());
+        assert_eq!(NameState::decode(&mut encoded).unwrap(), ns);
+    }
+
+    #[test]
+    fn test_auction_state() {
+        let network = Network::Regtest;
+        let mut ns = state();
+
+        let bidding = 100 + network.open_period();
+        let reveal = bidding + network.bidding_period();
+        let closed = reveal + network.reveal_period();
+
+        assert_eq!(ns.state(100, network), AuctionState::Opening);
+        assert_eq!(ns.state(bidding - 1, network), AuctionState::Opening);
+        assert_eq!(ns.state(bidding, network), AuctionState::Bidding);
+        assert_eq!(ns.state(reveal, network), AuctionState::Reveal);
+
+        //Nobody revealed, so the auction expires as soon as it closes.
+        assert_eq!(ns.state(closed, network), AuctionState::Expired);
+
+        ns.owner = Outpoint::new(Hash::from([7; 32]), 0);
+        assert_eq!(ns.state(closed, network), AuctionState::Closed);
+
+        let lapsed = ns.renewal + network.renewal_window();
+        assert_eq!(ns.state(lapsed - 1, network), AuctionState::Closed);
+        assert_eq!(ns.state(lapsed, network), AuctionState::Expired);
+
+        ns.revoked = closed + 1;
+        assert_eq!(ns.state(closed + 1, network), AuctionState::Revoked);
+        assert_eq!(
+            ns.state(closed + 1 + network.auction_maturity(), network),
+            AuctionState::Expired
+        );
+    }
+}
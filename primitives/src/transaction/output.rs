@@ -5,9 +5,9 @@ use handshake_types::Amount;
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Output {
-    value: Amount,
-    address: Address,
-    covenant: Covenant,
+    pub value: Amount,
+    pub address: Address,
+    pub covenant: Covenant,
 }
 
 //TODO get size, is_dust, format, equal + peq, to hex from hex, to buffer, from buffer.
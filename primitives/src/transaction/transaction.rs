@@ -1,10 +1,12 @@
-use crate::{Input, Output};
+use crate::{Covenant, Input, Output};
 use cryptoxide::blake2b::Blake2b;
 use cryptoxide::digest::Digest;
 use encodings::hex::{FromHex, FromHexError, ToHex};
 use extended_primitives::{Buffer, Hash, VarInt};
 use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_protocol::consensus::{LOCKTIME_FLAG, LOCKTIME_MASK, WITNESS_SCALE_FACTOR};
 use handshake_script::Witness;
+use handshake_types::Amount;
 
 #[derive(Clone, PartialEq, Debug, Default)]
 pub struct Transaction {
@@ -67,6 +69,77 @@ impl Transaction {
         *self == Default::default()
     }
 
+    pub fn is_coinbase(&self) -> bool {
+        match self.inputs.first() {
+            Some(input) => input.prevout.is_null(),
+            None => false,
+        }
+    }
+
+    /// Whether the transaction's locktime allows it into a block at `height` whose median time
+    /// past is `time`. A locktime with the flag bit set is a timestamp, otherwise a height.
+    pub fn is_final(&self, height: u32, time: u64) -> bool {
+        if self.locktime == 0 {
+            return true;
+        }
+
+        if self.locktime & LOCKTIME_FLAG != 0 {
+            if u64::from(self.locktime & LOCKTIME_MASK) < time {
+                return true;
+            }
+        } else if self.locktime < height {
+            return true;
+        }
+
+        self.inputs
+            .iter()
+            .all(|input| input.sequence == u32::max_value())
+    }
+
+    /// Number of outputs opening a name auction.
+    pub fn count_opens(&self) -> usize {
+        self.outputs
+            .iter()
+            .filter(|output| match output.covenant {
+                Covenant::Open(_) => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    /// Number of outputs that change a name's resource data or ownership.
+    pub fn count_updates(&self) -> usize {
+        self.outputs
+            .iter()
+            .filter(|output| match output.covenant {
+                Covenant::Register(_)
+                | Covenant::Update(_)
+                | Covenant::Transfer(_)
+                | Covenant::Finalize(_)
+                | Covenant::Revoke(_) => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    /// Number of outputs that renew a name.
+    pub fn count_renewals(&self) -> usize {
+        self.outputs
+            .iter()
+            .filter(|output| match output.covenant {
+                Covenant::Register(_) | Covenant::Renew(_) | Covenant::Finalize(_) => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    /// Sum of the output values, or None if it overflows.
+    pub fn get_output_value(&self) -> Option<Amount> {
+        self.outputs.iter().try_fold(Amount::ZERO, |total, output| {
+            total.checked_add(output.value)
+        })
+    }
+
     pub fn get_base_size(&self) -> usize {
         let mut size = 0;
         size += 4;
@@ -86,6 +159,16 @@ impl Transaction {
         size
     }
 
+    /// Base size plus witness size, i.e. the length of the full serialization.
+    pub fn get_size(&self) -> usize {
+        self.get_base_size() + self.get_witness_size()
+    }
+
+    pub fn get_weight(&self) -> usize {
+        let base = self.get_base_size();
+        base * (WITNESS_SCALE_FACTOR - 1) + self.get_size()
+    }
+
     pub fn get_witness_size(&self) -> usize {
         let mut size = 0;
 
@@ -220,5 +303,27 @@ mod test {
 
         assert_eq!(base_size, 159);
         assert_eq!(witness_size, 101);
+        assert_eq!(tx.get_size(), 260);
+        assert_eq!(tx.get_weight(), 737);
+    }
+
+    #[test]
+    fn test_tx_final() {
+        let input = Input {
+            sequence: 0,
+            ..Default::default()
+        };
+        let mut tx = Transaction::new(100, vec![input], Vec::new());
+
+        assert!(!tx.is_final(100, 0));
+        assert!(tx.is_final(101, 0));
+
+        tx.locktime = LOCKTIME_FLAG | 1_580_000_000;
+        assert!(!tx.is_final(101, 1_580_000_000));
+        assert!(tx.is_final(101, 1_580_000_001));
+
+        tx.inputs[0].sequence = u32::max_value();
+        assert!(tx.is_final(0, 0));
+        assert!(!tx.is_coinbase());
     }
 }
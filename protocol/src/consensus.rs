@@ -1,26 +1,94 @@
+use crate::network::Network;
 use extended_primitives::{Hash, Uint256};
-use handshake_types::Amount;
+use handshake_types::{Amount, Compact};
 use std::result::Result;
 
 pub const BASE_REWARD: u32 = 2_000;
 
+/// Number of previous blocks used to calculate the median time past.
+pub const MEDIAN_TIMESPAN: usize = 11;
+
+/// How far ahead of network-adjusted time a block's timestamp may be (2 hours).
+pub const MAX_FUTURE_BLOCK_TIME: u64 = 2 * 60 * 60;
+
+/// Maximum size of a block's base (non-witness) serialization.
+pub const MAX_BLOCK_SIZE: usize = 1_000_000;
+
+/// Maximum block weight, where base bytes count `WITNESS_SCALE_FACTOR` times.
+pub const MAX_BLOCK_WEIGHT: usize = 4_000_000;
+
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+
+/// Maximum signature operations per block.
+pub const MAX_BLOCK_SIGOPS: usize = 80_000;
+
+/// Maximum OPEN covenants per block.
+pub const MAX_BLOCK_OPENS: usize = 300;
+
+/// Maximum covenants per block that update a name's resource data.
+pub const MAX_BLOCK_UPDATES: usize = 600;
+
+/// Maximum covenants per block that renew a name.
+pub const MAX_BLOCK_RENEWALS: usize = 600;
+
+/// Locktimes with this bit set are timestamps rather than heights.
+pub const LOCKTIME_FLAG: u32 = 1 << 31;
+
+pub const LOCKTIME_MASK: u32 = LOCKTIME_FLAG - 1;
+
+/// The top bits a block version must carry for its low bits to count as deployment signals.
+pub const VERSION_TOP_BITS: u32 = 0x0000_0000;
+
+pub const VERSION_TOP_MASK: u32 = 0xe000_0000;
+
+/// Script verification flags every block is checked with.
+pub const MANDATORY_VERIFY_FLAGS: u32 = 0;
+
+/// Enforce relative locktimes in input sequences.
+pub const LOCKTIME_VERIFY_SEQUENCE: u32 = 1 << 0;
+
+/// Measure locktimes against the median time past rather than the block's own timestamp.
+pub const LOCKTIME_MEDIAN_TIME_PAST: u32 = 1 << 1;
+
+pub const MANDATORY_LOCKTIME_FLAGS: u32 = LOCKTIME_VERIFY_SEQUENCE | LOCKTIME_MEDIAN_TIME_PAST;
+
+/// Reject name claims backed by weak (RSA-1024) DNSSEC keys. Set once hardening is active.
+pub const VERIFY_COVENANTS_HARDENED: u32 = 1 << 0;
+
+pub const MANDATORY_VERIFY_COVENANT_FLAGS: u32 = 0;
+
+/// The parts of a chain entry that the difficulty and time rules need. Lets those rules walk the
+/// chain's own entries (or plain headers) without the protocol crate knowing about the chain.
+pub trait HeaderInfo {
+    fn time(&self) -> u64;
+    fn bits(&self) -> u32;
+}
+
+impl<'a, T: HeaderInfo> HeaderInfo for &'a T {
+    fn time(&self) -> u64 {
+        (*self).time()
+    }
+
+    fn bits(&self) -> u32 {
+        (*self).bits()
+    }
+}
+
 pub fn max_coin() -> Amount {
     Amount::from_doos(2_040_000_000_000_000)
 }
 
+/// The block subsidy at `height`: `BASE_REWARD` coins, halved every `interval` blocks. Halving
+/// is done in doos, so nothing is rounded away until the subsidy drops below one doo.
 pub fn get_reward(height: u32, interval: u32) -> Amount {
-    //@todo test this at max bounds to double check these casts.
-    let halvings = (height as f32 / interval as f32).floor() as u32;
+    let halvings = height / interval;
 
-    if halvings >= 53 {
+    if halvings >= 52 {
         return Amount::ZERO;
     }
 
-    let exponent = 2u32.pow(halvings);
-
-    let amount = (BASE_REWARD as f32 / exponent as f32).floor() * 1_000_000f32;
-
-    Amount::from_doos(amount as u64)
+    let base = BASE_REWARD as u64 * Amount::ONE_HNS.as_doos();
+    Amount::from_doos(base >> halvings)
 }
 
 //Make this a compact type. TODO
@@ -70,3 +138,251 @@ pub fn target_from_compact_bits(bits: u32) -> Result<Uint256, Uint256> {
         Ok(result)
     }
 }
+
+/// Median of the timestamps of up to `MEDIAN_TIMESPAN` entries, starting at the first entry
+/// yielded and walking back. Returns 0 for an empty iterator.
+pub fn get_median_time<I, E>(entries: I) -> u64
+where
+    I: IntoIterator<Item = E>,
+    E: HeaderInfo,
+{
+    let mut times: Vec<u64> = entries
+        .into_iter()
+        .take(MEDIAN_TIMESPAN)
+        .map(|entry| entry.time())
+        .collect();
+
+    if times.is_empty() {
+        return 0;
+    }
+
+    times.sort();
+
+    times[times.len() / 2]
+}
+
+/// Computes the bits a block must have to follow `ancestors`.
+///
+/// `ancestors` yields the previous block first and then walks back towards genesis. It only needs
+/// to yield `target_window + MEDIAN_TIMESPAN` entries; anything past that is ignored. An empty
+/// iterator means the block is the genesis block. `time` is the new block's timestamp.
+pub fn get_next_bits<I, E>(network: Network, time: u64, ancestors: I) -> u32
+where
+    I: IntoIterator<Item = E>,
+    E: HeaderInfo,
+{
+    let mut ancestors = ancestors.into_iter();
+
+    let prev = match ancestors.next() {
+        Some(prev) => prev,
+        None => return network.pow_bits(),
+    };
+
+    if network.no_retargeting() {
+        return network.pow_bits();
+    }
+
+    //Testnet lets a block fall back to the minimum difficulty if it's late enough.
+    if network.target_reset() && time > prev.time() + network.target_spacing() * 2 {
+        return network.pow_bits();
+    }
+
+    let window = network.target_window() as usize;
+
+    //(time, bits) for the previous block and the entries behind it. We need `window` entries to
+    //average and then another MEDIAN_TIMESPAN starting at the first block of the window.
+    let mut entries = vec![(prev.time(), prev.bits())];
+    entries.extend(
+        ancestors
+            .take(window + MEDIAN_TIMESPAN - 1)
+            .map(|entry| (entry.time(), entry.bits())),
+    );
+
+    //Not enough history yet.
+    if entries.len() <= window {
+        return network.pow_bits();
+    }
+
+    let mut target = Uint256::from(0u64);
+
+    for (_, bits) in entries.iter().take(window) {
+        //Bits in the chain have already been validated.
+        let entry_target = target_from_compact_bits(*bits).unwrap_or_else(|x| x);
+        target = target + entry_target;
+    }
+
+    target = target / Uint256::from(window as u64);
+
+    let end = median_of(&entries[..]);
+    let start = median_of(&entries[window..]);
+
+    retarget(network, target, start, end)
+}
+
+fn median_of(entries: &[(u64, u32)]) -> u64 {
+    let mut times: Vec<u64> = entries
+        .iter()
+        .take(MEDIAN_TIMESPAN)
+        .map(|(time, _)| *time)
+        .collect();
+
+    times.sort();
+
+    times[times.len() / 2]
+}
+
+/// Scales an averaged target by how long the window actually took, clamped to the network's
+/// adjustment limits.
+pub fn retarget(network: Network, target: Uint256, start: u64, end: u64) -> u32 {
+    let mut actual_timespan = end.saturating_sub(start);
+
+    if actual_timespan < network.min_actual() {
+        actual_timespan = network.min_actual();
+    }
+
+    if actual_timespan > network.max_actual() {
+        actual_timespan = network.max_actual();
+    }
+
+    let target = target * Uint256::from(actual_timespan) / Uint256::from(network.target_timespan());
+
+    if target > network.pow_limit() {
+        return network.pow_bits();
+    }
+
+    Compact::from_u256(target).to_u32()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Entry {
+        time: u64,
+        bits: u32,
+    }
+
+    impl HeaderInfo for Entry {
+        fn time(&self) -> u64 {
+            self.time
+        }
+
+        fn bits(&self) -> u32 {
+            self.bits
+        }
+    }
+
+    //Builds `count` entries ending at `tip_time`, newest first.
+    fn entries(count: u64, tip_time: u64, spacing: u64, bits: u32) -> Vec<Entry> {
+        (0..count)
+            .map(|i| Entry {
+                time: tip_time - i * spacing,
+                bits,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_median_time() {
+        let history = entries(20, 10_000, 10, 0);
+        assert_eq!(get_median_time(&history), 10_000 - 50);
+
+        let history = entries(3, 10_000, 10, 0);
+        assert_eq!(get_median_time(&history), 9_990);
+
+        let history: Vec<Entry> = Vec::new();
+        assert_eq!(get_median_time(&history), 0);
+    }
+
+    #[test]
+    fn test_get_reward() {
+        let interval = Network::Mainnet.halvening_interval();
+        let reward = |height| get_reward(height, interval).as_doos();
+
+        assert_eq!(reward(0), 2_000_000_000);
+        assert_eq!(reward(interval - 1), 2_000_000_000);
+        assert_eq!(reward(interval), 1_000_000_000);
+
+        //Halving in doos keeps the fractions of a coin.
+        assert_eq!(reward(interval * 5 - 1), 125_000_000);
+        assert_eq!(reward(interval * 5), 62_500_000);
+        assert_eq!(reward(interval * 6), 31_250_000);
+        assert_eq!(reward(interval * 30), 1);
+        assert_eq!(reward(interval * 31), 0);
+
+        //No subsidy from the 52nd halving, and no overflow on the way there.
+        let regtest = Network::Regtest.halvening_interval();
+        assert_eq!(get_reward(regtest * 32, regtest), Amount::ZERO);
+        assert_eq!(get_reward(regtest * 52 - 1, regtest), Amount::ZERO);
+        assert_eq!(get_reward(regtest * 52, regtest), Amount::ZERO);
+        assert_eq!(get_reward(u32::max_value(), 1), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_genesis_and_no_retarget() {
+        let history: Vec<Entry> = Vec::new();
+        assert_eq!(get_next_bits(Network::Mainnet, 0, &history), 0x1c00ffff);
+
+        let history = entries(200, 1_000_000, 1, 0x1f00ffff);
+        assert_eq!(
+            get_next_bits(Network::Regtest, 1_000_001, &history),
+            Network::Regtest.pow_bits()
+        );
+    }
+
+    #[test]
+    fn test_retarget_steady() {
+        let history = entries(200, 1_000_000, 600, 0x1c00ffff);
+        assert_eq!(
+            get_next_bits(Network::Mainnet, 1_000_600, &history),
+            0x1c00ffff
+        );
+    }
+
+    #[test]
+    fn test_retarget_clamps() {
+        let bits = 0x1b00ffff;
+        let target = target_from_compact_bits(bits).unwrap();
+
+        //Blocks coming in far too fast are clamped to the minimum timespan.
+        let history = entries(200, 1_000_000, 60, bits);
+        let expected = Compact::from_u256(
+            target * Uint256::from(Network::Mainnet.min_actual())
+                / Uint256::from(Network::Mainnet.target_timespan()),
+        );
+        assert_eq!(
+            get_next_bits(Network::Mainnet, 1_000_060, &history),
+            expected.to_u32()
+        );
+
+        //And far too slow to the maximum.
+        let history = entries(200, 10_000_000, 6_000, bits);
+        let expected = Compact::from_u256(
+            target * Uint256::from(Network::Mainnet.max_actual())
+                / Uint256::from(Network::Mainnet.target_timespan()),
+        );
+        assert_eq!(
+            get_next_bits(Network::Mainnet, 10_006_000, &history),
+            expected.to_u32()
+        );
+    }
+
+    #[test]
+    fn test_retarget_limit() {
+        //Easing past the limit falls back to the default bits.
+        let history = entries(200, 10_000_000, 6_000, 0x1c00ffff);
+        assert_eq!(
+            get_next_bits(Network::Mainnet, 10_006_000, &history),
+            0x1c00ffff
+        );
+    }
+
+    #[test]
+    fn test_target_reset() {
+        let history = entries(200, 1_000_000, 600, 0x1c00ffff);
+        assert_eq!(
+            get_next_bits(Network::Testnet, 1_000_000 + 1201, &history),
+            Network::Testnet.pow_bits()
+        );
+    }
+}
@@ -0,0 +1,73 @@
+/// A soft fork deployed through version bits signalling (BIP9).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deployment {
+    pub name: &'static str,
+    /// Version bit miners set to signal for the deployment.
+    pub bit: u8,
+    /// Median time past at which signalling starts.
+    pub start_time: u64,
+    /// Median time past after which the deployment fails if it has not locked in.
+    pub timeout: u64,
+    /// Overrides the network's activation threshold.
+    pub threshold: Option<u32>,
+    /// Overrides the network's miner window.
+    pub window: Option<u32>,
+    /// Whether blocks must signal for the deployment once it is started.
+    pub required: bool,
+    /// Whether block templates list the deployment as a rule miners can't ignore. This only affects
+    /// getblocktemplate: the deployment still has to be signalled for like any other.
+    pub force: bool,
+}
+
+const HARDENING_START: u64 = 1_581_638_400;
+const HARDENING_TIMEOUT: u64 = 1_707_868_800;
+
+pub const MAINNET_DEPLOYMENTS: [Deployment; 2] = [
+    Deployment {
+        name: "hardening",
+        bit: 0,
+        start_time: HARDENING_START,
+        timeout: HARDENING_TIMEOUT,
+        threshold: None,
+        window: None,
+        required: false,
+        force: false,
+    },
+    Deployment {
+        name: "testdummy",
+        bit: 28,
+        start_time: 1_199_145_601,
+        timeout: 1_230_767_999,
+        threshold: None,
+        window: None,
+        required: false,
+        force: true,
+    },
+];
+
+pub const TESTNET_DEPLOYMENTS: [Deployment; 2] = MAINNET_DEPLOYMENTS;
+
+pub const REGTEST_DEPLOYMENTS: [Deployment; 2] = [
+    Deployment {
+        name: "hardening",
+        bit: 0,
+        start_time: HARDENING_START,
+        timeout: HARDENING_TIMEOUT,
+        threshold: None,
+        window: None,
+        required: false,
+        force: false,
+    },
+    Deployment {
+        name: "testdummy",
+        bit: 28,
+        start_time: 0,
+        timeout: 0xffff_ffff,
+        threshold: None,
+        window: None,
+        required: false,
+        force: true,
+    },
+];
+
+pub const SIMNET_DEPLOYMENTS: [Deployment; 2] = REGTEST_DEPLOYMENTS;
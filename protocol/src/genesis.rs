@@ -1,13 +1,54 @@
-//use handshake_primitives::Block;
+// Genesis blocks for each network, serialized as hex. They only differ in their timestamp and
+// proof of work bits.
 
-////Base64
-//pub const MAIN_HEX: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAjkyXVv7yrRA3XzYOBWD8x1h+tSI934zXx+BuYKEUCxV8fCgYxgWpcXhGCq1IkN8q/MqWLLy2ObgS2wr4OZSXmAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD9CpFwAAAAA//8AHQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQAAAAABAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD//////////wHQTFd3AAAAAAAU8CN64uj4YPfXkST8UT8BLlqqjSMAAAAAAAABODAxL05vdi8yMDE3IEVGRiB0byBJQ0FOTjogRG9uJ3QgUGljayBVcCB0aGUgQ2Vuc29yJ3MgUGVu";
+pub const MAINNET_GENESIS_HASH: &str =
+    "5b6ef2d3c1f3cdcadfd9a030ba1811efdd17740f14e166489760741d075992e0";
 
-////Base64
-//pub const TESTNET_HEX: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAjkyXVv7yrRA3XzYOBWD8x1h+tSI934zXx+BuYKEUCxV8fCgYxgWpcXhGCq1IkN8q/MqWLLy2ObgS2wr4OZSXmAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD9CpFwAAAAAAAAwHQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQAAAAABAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD//////////wHQTFd3AAAAAAAU8CN64uj4YPfXkST8UT8BLlqqjSMAAAAAAAABODAxL05vdi8yMDE3IEVGRiB0byBJQ0FOTjogRG9uJ3QgUGljayBVcCB0aGUgQ2Vuc29yJ3MgUGVu";
+pub const MAINNET_GENESIS: &str = "\
+     000000007641385e00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+     000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000\
+     0000000000000000000000000000000000000000000000000000000000000000000000001a2c60b9439206938f8d7823\
+     782abdb8b211a57431e9c9b6a6365d8d428933518e4c9756fef2ad10375f360e0560fcc7587eb5223ddf8cd7c7e06e60\
+     a1140b1500000000ffff001c000000000000000000000000000000000000000000000000000000000000000001000000\
+     00010000000000000000000000000000000000000000000000000000000000000000ffffffffffffffff01d04c577700\
+     0000000014f0237ae2e8f860f7d79124fc513f012e5aaa8d23000000000000013830312f4e6f762f3230313720454646\
+     20746f204943414e4e3a20446f6e2774205069636b205570207468652043656e736f7227732050656e";
 
-////Base64
-//pub const REGTEST_HEX: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAjkyXVv7yrRA3XzYOBWD8x1h+tSI934zXx+BuYKEUCxV8fCgYxgWpcXhGCq1IkN8q/MqWLLy2ObgS2wr4OZSXmAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD9CpFwAAAAA//9/IAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQAAAAABAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD//////////wHQTFd3AAAAAAAU8CN64uj4YPfXkST8UT8BLlqqjSMAAAAAAAABODAxL05vdi8yMDE3IEVGRiB0byBJQ0FOTjogRG9uJ3QgUGljayBVcCB0aGUgQ2Vuc29yJ3MgUGVu";
+pub const TESTNET_GENESIS_HASH: &str =
+    "b1520dd24372f82ec94ebf8cf9d9b037d419c4aa3575d05dec70aedd1b427901";
 
-////Base64
-//pub const SIMNET_HEX: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAjkyXVv7yrRA3XzYOBWD8x1h+tSI934zXx+BuYKEUCxV8fCgYxgWpcXhGCq1IkN8q/MqWLLy2ObgS2wr4OZSXmAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD9CpFwAAAAA//9/IAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQAAAAABAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD//////////wHQTFd3AAAAAAAU8CN64uj4YPfXkST8UT8BLlqqjSMAAAAAAAABODAxL05vdi8yMDE3IEVGRiB0byBJQ0FOTjogRG9uJ3QgUGljayBVcCB0aGUgQ2Vuc29yJ3MgUGVu";
+pub const TESTNET_GENESIS: &str = "\
+     000000007741385e00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+     000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000\
+     0000000000000000000000000000000000000000000000000000000000000000000000001a2c60b9439206938f8d7823\
+     782abdb8b211a57431e9c9b6a6365d8d428933518e4c9756fef2ad10375f360e0560fcc7587eb5223ddf8cd7c7e06e60\
+     a1140b1500000000ffff001d000000000000000000000000000000000000000000000000000000000000000001000000\
+     00010000000000000000000000000000000000000000000000000000000000000000ffffffffffffffff01d04c577700\
+     0000000014f0237ae2e8f860f7d79124fc513f012e5aaa8d23000000000000013830312f4e6f762f3230313720454646\
+     20746f204943414e4e3a20446f6e2774205069636b205570207468652043656e736f7227732050656e";
+
+pub const REGTEST_GENESIS_HASH: &str =
+    "ae3895cf597eff05b19e02a70ceeeecb9dc72dbfe6504a50e9343a72f06a87c5";
+
+pub const REGTEST_GENESIS: &str = "\
+     000000007841385e00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+     000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000\
+     0000000000000000000000000000000000000000000000000000000000000000000000001a2c60b9439206938f8d7823\
+     782abdb8b211a57431e9c9b6a6365d8d428933518e4c9756fef2ad10375f360e0560fcc7587eb5223ddf8cd7c7e06e60\
+     a1140b1500000000ffff7f20000000000000000000000000000000000000000000000000000000000000000001000000\
+     00010000000000000000000000000000000000000000000000000000000000000000ffffffffffffffff01d04c577700\
+     0000000014f0237ae2e8f860f7d79124fc513f012e5aaa8d23000000000000013830312f4e6f762f3230313720454646\
+     20746f204943414e4e3a20446f6e2774205069636b205570207468652043656e736f7227732050656e";
+
+pub const SIMNET_GENESIS_HASH: &str =
+    "0e648edc9cddb179014658061ea3f666a45cf44881877ae506e6babefbef6992";
+
+pub const SIMNET_GENESIS: &str = "\
+     000000007941385e00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+     000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000\
+     0000000000000000000000000000000000000000000000000000000000000000000000001a2c60b9439206938f8d7823\
+     782abdb8b211a57431e9c9b6a6365d8d428933518e4c9756fef2ad10375f360e0560fcc7587eb5223ddf8cd7c7e06e60\
+     a1140b1500000000ffff7f20000000000000000000000000000000000000000000000000000000000000000001000000\
+     00010000000000000000000000000000000000000000000000000000000000000000ffffffffffffffff01d04c577700\
+     0000000014f0237ae2e8f860f7d79124fc513f012e5aaa8d23000000000000013830312f4e6f762f3230313720454646\
+     20746f204943414e4e3a20446f6e2774205069636b205570207468652043656e736f7227732050656e";
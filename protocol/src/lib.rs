@@ -1,3 +1,5 @@
 pub mod consensus;
+pub mod deployment;
 pub mod genesis;
 pub mod network;
+pub mod time_data;
@@ -1,3 +1,14 @@
+use crate::consensus::target_from_compact_bits;
+use crate::deployment::{
+    Deployment, MAINNET_DEPLOYMENTS, REGTEST_DEPLOYMENTS, SIMNET_DEPLOYMENTS, TESTNET_DEPLOYMENTS,
+};
+use crate::genesis::{
+    MAINNET_GENESIS_HASH, REGTEST_GENESIS_HASH, SIMNET_GENESIS_HASH, TESTNET_GENESIS_HASH,
+};
+use encodings::hex::FromHex;
+use extended_primitives::{Hash, Uint256};
+use handshake_types::NameParams;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Network {
     Mainnet,
@@ -52,6 +63,398 @@ impl Network {
             Network::Simnet => 6,
         }
     }
+
+    /// Hash of the network's genesis block. The block itself is decoded by the primitives crate.
+    pub fn genesis_hash(&self) -> Hash {
+        let hex = match *self {
+            Network::Mainnet => MAINNET_GENESIS_HASH,
+            Network::Testnet => TESTNET_GENESIS_HASH,
+            Network::Regtest => REGTEST_GENESIS_HASH,
+            Network::Simnet => SIMNET_GENESIS_HASH,
+        };
+
+        Hash::from_hex(hex).expect("genesis hashes are valid hex")
+    }
+
+    // ===== Proof of Work ===== //
+
+    /// Default bits, also used by the genesis block.
+    pub fn pow_bits(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 0x1c00ffff,
+            Network::Testnet => 0x1d00ffff,
+            Network::Regtest => 0x207fffff,
+            Network::Simnet => 0x207fffff,
+        }
+    }
+
+    /// The highest (easiest) target a block may have.
+    pub fn pow_limit(&self) -> Uint256 {
+        //The limits are exactly representable by the default bits.
+        target_from_compact_bits(self.pow_bits()).unwrap()
+    }
+
+    /// Number of blocks averaged when retargeting.
+    pub fn target_window(&self) -> u32 {
+        144
+    }
+
+    /// Average block time in seconds.
+    pub fn target_spacing(&self) -> u64 {
+        10 * 60
+    }
+
+    pub fn target_timespan(&self) -> u64 {
+        u64::from(self.target_window()) * self.target_spacing()
+    }
+
+    /// Lower clamp on the measured timespan, limits how far difficulty can rise.
+    pub fn min_actual(&self) -> u64 {
+        (self.target_timespan() * (100 - 16)) / 100
+    }
+
+    /// Upper clamp on the measured timespan, limits how far difficulty can fall.
+    pub fn max_actual(&self) -> u64 {
+        (self.target_timespan() * (100 + 32)) / 100
+    }
+
+    /// Whether a block more than two spacings late may drop back to the default bits.
+    pub fn target_reset(&self) -> bool {
+        match *self {
+            Network::Mainnet => false,
+            Network::Testnet => true,
+            Network::Regtest => true,
+            Network::Simnet => false,
+        }
+    }
+
+    pub fn no_retargeting(&self) -> bool {
+        match *self {
+            Network::Mainnet => false,
+            Network::Testnet => false,
+            Network::Regtest => true,
+            Network::Simnet => true,
+        }
+    }
+
+    // ===== Names ===== //
+
+    /// Height the first names can be opened at.
+    pub fn auction_start(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 2016,
+            Network::Testnet => 36,
+            Network::Regtest => 0,
+            Network::Simnet => 0,
+        }
+    }
+
+    /// Blocks between each weekly batch of names being released for auction.
+    pub fn rollout_interval(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 1008,
+            Network::Testnet => 36,
+            Network::Regtest => 2,
+            Network::Simnet => 1,
+        }
+    }
+
+    /// Blocks a newly registered name is locked for.
+    pub fn lockup_period(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 4320,
+            Network::Testnet => 36,
+            Network::Regtest => 2,
+            Network::Simnet => 1,
+        }
+    }
+
+    /// How often (in blocks) the name tree is committed to a block header.
+    pub fn tree_interval(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 36,
+            Network::Testnet => 36,
+            Network::Regtest => 5,
+            Network::Simnet => 2,
+        }
+    }
+
+    /// Blocks after an OPEN before bidding starts. An open has to be in the tree before anyone
+    /// can bid on it, so this is one more than the tree interval.
+    pub fn open_period(&self) -> u32 {
+        self.tree_interval() + 1
+    }
+
+    pub fn bidding_period(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 720,
+            Network::Testnet => 144,
+            Network::Regtest => 5,
+            Network::Simnet => 25,
+        }
+    }
+
+    pub fn reveal_period(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 1440,
+            Network::Testnet => 288,
+            Network::Regtest => 10,
+            Network::Simnet => 50,
+        }
+    }
+
+    /// Blocks a name stays registered without a renewal.
+    pub fn renewal_window(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 105_120,
+            Network::Testnet => 4320,
+            Network::Regtest => 5000,
+            Network::Simnet => 2500,
+        }
+    }
+
+    /// Blocks a renewal's committed block hash must be buried by.
+    pub fn renewal_maturity(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 4320,
+            Network::Testnet => 144,
+            Network::Regtest => 50,
+            Network::Simnet => 25,
+        }
+    }
+
+    /// How old a renewal's committed block hash may be.
+    pub fn renewal_period(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 26_208,
+            Network::Testnet => 1008,
+            Network::Regtest => 2500,
+            Network::Simnet => 1250,
+        }
+    }
+
+    /// Blocks between a TRANSFER and the FINALIZE that completes it.
+    pub fn transfer_lockup(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 288,
+            Network::Testnet => 288,
+            Network::Regtest => 10,
+            Network::Simnet => 5,
+        }
+    }
+
+    /// Blocks required between revoking a name and registering it again.
+    pub fn revocation_delay(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 2016,
+            Network::Testnet => 576,
+            Network::Regtest => 50,
+            Network::Simnet => 25,
+        }
+    }
+
+    /// Blocks after a revoke before the name can be opened again.
+    pub fn auction_maturity(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 4176,
+            Network::Testnet => 1008,
+            Network::Regtest => 65,
+            Network::Simnet => 100,
+        }
+    }
+
+    /// Height until which reserved names can be claimed with a DNSSEC proof.
+    pub fn claim_period(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 210_240,
+            Network::Testnet => 12_960,
+            Network::Regtest => 250_000,
+            Network::Simnet => 75_000,
+        }
+    }
+
+    /// Height until which names from the Alexa top 100k stay reserved.
+    pub fn alexa_lockup_period(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 420_480,
+            Network::Testnet => 25_920,
+            Network::Regtest => 500_000,
+            Network::Simnet => 150_000,
+        }
+    }
+
+    /// Seconds between the claim proofs a miner will accept into a block.
+    pub fn claim_frequency(&self) -> u64 {
+        match *self {
+            Network::Mainnet => 2 * 60 * 60,
+            Network::Testnet => 2 * 60 * 60,
+            Network::Regtest => 0,
+            Network::Simnet => 0,
+        }
+    }
+
+    /// Every name can be opened from the auction start, rather than in weekly batches.
+    pub fn no_rollout(&self) -> bool {
+        match *self {
+            Network::Mainnet => false,
+            Network::Testnet => false,
+            Network::Regtest => true,
+            Network::Simnet => false,
+        }
+    }
+
+    /// Reserved names can be opened like any other.
+    pub fn no_reserved(&self) -> bool {
+        match *self {
+            Network::Mainnet => false,
+            Network::Testnet => false,
+            Network::Regtest => true,
+            Network::Simnet => false,
+        }
+    }
+
+    // ===== Deployments ===== //
+
+    /// Soft forks signalled through version bits.
+    pub fn deployments(&self) -> &'static [Deployment] {
+        match *self {
+            Network::Mainnet => &MAINNET_DEPLOYMENTS,
+            Network::Testnet => &TESTNET_DEPLOYMENTS,
+            Network::Regtest => &REGTEST_DEPLOYMENTS,
+            Network::Simnet => &SIMNET_DEPLOYMENTS,
+        }
+    }
+
+    pub fn get_deployment(&self, name: &str) -> Option<&'static Deployment> {
+        self.deployments()
+            .iter()
+            .find(|deployment| deployment.name == name)
+    }
+
+    /// Signalling blocks needed within a miner window for a deployment to lock in.
+    pub fn activation_threshold(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 1916,
+            Network::Testnet => 1512,
+            Network::Regtest => 108,
+            Network::Simnet => 75,
+        }
+    }
+
+    pub fn miner_window(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 2016,
+            Network::Testnet => 2016,
+            Network::Regtest => 144,
+            Network::Simnet => 100,
+        }
+    }
+
+    // ===== Checkpoints ===== //
+
+    /// Known good block hashes (as hex) by height, mirroring hsd's `checkpointMap`. Only mainnet
+    /// has them; the test networks are too easy to mine for a checkpoint to mean anything.
+    pub fn checkpoints(&self) -> &'static [(u32, &'static str)] {
+        match *self {
+            Network::Mainnet => MAINNET_CHECKPOINTS,
+            Network::Testnet => &[],
+            Network::Regtest => &[],
+            Network::Simnet => &[],
+        }
+    }
+
+    /// The checkpointed block hash at `height`, if there is one.
+    pub fn get_checkpoint(&self, height: u32) -> Option<Hash> {
+        self.checkpoints()
+            .iter()
+            .find(|(at, _)| *at == height)
+            .map(|(_, hex)| Hash::from_hex(hex).expect("checkpoints are valid hex"))
+    }
+
+    /// The height of the last checkpoint, hsd's `lastCheckpoint`. Blocks below it are on the
+    /// checkpointed chain.
+    pub fn last_checkpoint(&self) -> u32 {
+        self.checkpoints()
+            .iter()
+            .map(|(height, _)| *height)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+//hsd's `main.checkpointMap` from lib/protocol/networks.js belongs here, in height order. It has
+//not been copied over yet, so mainnet has no checkpoints for now. Entries have to come from there
+//verbatim: a wrong hash here forks the node off mainnet.
+const MAINNET_CHECKPOINTS: &[(u32, &str)] = &[];
+
+impl NameParams for Network {
+    fn auction_start(&self) -> u32 {
+        Network::auction_start(self)
+    }
+
+    fn rollout_interval(&self) -> u32 {
+        Network::rollout_interval(self)
+    }
+
+    fn claim_period(&self) -> u32 {
+        Network::claim_period(self)
+    }
+
+    fn no_rollout(&self) -> bool {
+        Network::no_rollout(self)
+    }
+
+    fn no_reserved(&self) -> bool {
+        Network::no_reserved(self)
+    }
 }
 
 //from string
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deployments() {
+        for network in [
+            Network::Mainnet,
+            Network::Testnet,
+            Network::Regtest,
+            Network::Simnet,
+        ]
+        .iter()
+        {
+            let hardening = network.get_deployment("hardening").unwrap();
+            assert_eq!(hardening.bit, 0);
+
+            //Every deployment needs its own bit.
+            let deployments = network.deployments();
+            for (i, a) in deployments.iter().enumerate() {
+                for b in deployments[i + 1..].iter() {
+                    assert_ne!(a.bit, b.bit);
+                }
+            }
+
+            assert!(network.activation_threshold() <= network.miner_window());
+        }
+
+        for network in [Network::Testnet, Network::Regtest, Network::Simnet].iter() {
+            assert!(network.checkpoints().is_empty());
+            assert_eq!(network.last_checkpoint(), 0);
+            assert_eq!(network.get_checkpoint(0), None);
+        }
+
+        //Checkpoints are kept in height order, each a full block hash.
+        let checkpoints = Network::Mainnet.checkpoints();
+        for (i, (height, hash)) in checkpoints.iter().enumerate() {
+            assert_eq!(hash.len(), 64);
+            assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+            if let Some((next, _)) = checkpoints.get(i + 1) {
+                assert!(height < next);
+            }
+        }
+
+        assert!(Network::Mainnet.get_deployment("segwit").is_none());
+    }
+}
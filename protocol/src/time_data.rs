@@ -0,0 +1,112 @@
+use handshake_types::Time;
+use std::collections::HashMap;
+
+/// Maximum number of peer samples we will take.
+const MAX_SAMPLES: usize = 200;
+
+/// Offsets larger than this (70 minutes) are ignored.
+const MAX_OFFSET: i64 = 70 * 60;
+
+/// Network-adjusted time. Collects the clock offsets reported by peers and adjusts our own clock
+/// by their median, like hsd's TimeData.
+#[derive(Debug, Default)]
+pub struct TimeData {
+    samples: Vec<i64>,
+    known: HashMap<String, i64>,
+    offset: i64,
+}
+
+impl TimeData {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records the time a peer reported. Each peer (by id) is only sampled once.
+    pub fn add(&mut self, id: &str, time: u64) {
+        if self.samples.len() >= MAX_SAMPLES {
+            return;
+        }
+
+        if self.known.contains_key(id) {
+            return;
+        }
+
+        let sample = time as i64 - Time::now().to_seconds() as i64;
+
+        self.known.insert(id.to_owned(), sample);
+
+        let index = match self.samples.binary_search(&sample) {
+            Ok(index) => index,
+            Err(index) => index,
+        };
+        self.samples.insert(index, sample);
+
+        //Only move the offset on an odd number of samples, and once we have a few.
+        if self.samples.len() >= 5 && self.samples.len() % 2 == 1 {
+            let median = self.samples[self.samples.len() / 2];
+
+            if median.abs() >= MAX_OFFSET {
+                self.offset = 0;
+            } else {
+                self.offset = median;
+            }
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    /// Current network-adjusted time, in seconds.
+    pub fn now(&self) -> u64 {
+        self.adjust(Time::now().to_seconds())
+    }
+
+    /// Adjusts a local time to network time.
+    pub fn adjust(&self, time: u64) -> u64 {
+        (time as i64 + self.offset) as u64
+    }
+
+    /// Converts a network time back to local time.
+    pub fn local(&self, time: u64) -> u64 {
+        (time as i64 - self.offset) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_offset() {
+        let mut data = TimeData::new();
+        let now = Time::now().to_seconds();
+
+        for i in 0..4 {
+            data.add(&format!("peer{}", i), now + 100);
+        }
+
+        //Not enough samples yet.
+        assert_eq!(data.offset(), 0);
+
+        data.add("peer4", now + 100);
+        assert!(data.offset() >= 99 && data.offset() <= 100);
+
+        //Duplicate peers are ignored.
+        data.add("peer4", now + 10_000);
+        data.add("peer4", now + 10_000);
+        assert!(data.offset() >= 99 && data.offset() <= 100);
+    }
+
+    #[test]
+    fn test_time_offset_too_large() {
+        let mut data = TimeData::new();
+        let now = Time::now().to_seconds();
+
+        for i in 0..5 {
+            data.add(&format!("peer{}", i), now + 2 * 60 * 60);
+        }
+
+        assert_eq!(data.offset(), 0);
+    }
+}
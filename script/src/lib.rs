@@ -1,7 +1,9 @@
 pub mod opcode;
+pub mod script;
 pub mod stack;
 pub mod witness;
 
 pub use opcode::Opcode;
+pub use script::Script;
 pub use stack::Stack;
 pub use witness::Witness;
@@ -1,8 +1,114 @@
 use crate::Opcode;
+use extended_primitives::Buffer;
 
+//@todo parse into opcodes once the interpreter needs them. For now we only walk the raw bytes.
+#[derive(Clone, PartialEq, Debug)]
 pub struct Script {
     raw: Buffer,
-    //TODO not sure.
-    code: Vec<Opcode>,
 }
 
+impl Script {
+    pub fn new(raw: Buffer) -> Self {
+        Script { raw }
+    }
+
+    pub fn raw(&self) -> &Buffer {
+        &self.raw
+    }
+
+    /// Count the signature operations in the script. With `accurate` set, a multisig preceded by
+    /// a small integer counts as that many keys instead of the 20 key maximum (as in hsd).
+    pub fn get_sigops(&self, accurate: bool) -> usize {
+        let raw: &[u8] = &self.raw;
+        let mut total = 0;
+        let mut last = Opcode::OP_INVALIDOPCODE as u8;
+        let mut i = 0;
+
+        while i < raw.len() {
+            let op = raw[i];
+            i += 1;
+
+            let push = match op {
+                0x01..=0x4b => op as usize,
+                x if x == Opcode::OP_PUSHDATA1 as u8 => match raw.get(i) {
+                    Some(len) => 1 + *len as usize,
+                    None => break,
+                },
+                x if x == Opcode::OP_PUSHDATA2 as u8 => {
+                    if i + 2 > raw.len() {
+                        break;
+                    }
+                    2 + u16::from_le_bytes([raw[i], raw[i + 1]]) as usize
+                }
+                x if x == Opcode::OP_PUSHDATA4 as u8 => {
+                    if i + 4 > raw.len() {
+                        break;
+                    }
+                    4 + u32::from_le_bytes([raw[i], raw[i + 1], raw[i + 2], raw[i + 3]]) as usize
+                }
+                _ => 0,
+            };
+
+            if push > 0 {
+                // A truncated push ends the script, the same as a parse failure in hsd.
+                if i + push > raw.len() {
+                    break;
+                }
+                i += push;
+                last = op;
+                continue;
+            }
+
+            if op == Opcode::OP_CHECKSIG as u8 || op == Opcode::OP_CHECKSIGVERIFY as u8 {
+                total += 1;
+            } else if op == Opcode::OP_CHECKMULTISIG as u8
+                || op == Opcode::OP_CHECKMULTISIGVERIFY as u8
+            {
+                if accurate && last >= Opcode::OP_1 as u8 && last <= Opcode::OP_16 as u8 {
+                    total += (last - Opcode::OP_1 as u8 + 1) as usize;
+                } else {
+                    total += 20;
+                }
+            }
+
+            last = op;
+        }
+
+        total
+    }
+}
+
+impl From<Buffer> for Script {
+    fn from(raw: Buffer) -> Self {
+        Script::new(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigops() {
+        // OP_2 <key> <key> <key> OP_3 OP_CHECKMULTISIG
+        let mut raw = vec![0x52];
+        for _ in 0..3 {
+            raw.push(33);
+            raw.extend_from_slice(&[0x02; 33]);
+        }
+        raw.push(0x53);
+        raw.push(0xae);
+        let script = Script::new(Buffer::from(raw));
+
+        assert_eq!(script.get_sigops(true), 3);
+        assert_eq!(script.get_sigops(false), 20);
+
+        // <key> OP_CHECKSIG, where the pushed key contains a checksig byte.
+        let mut raw = vec![33];
+        raw.extend_from_slice(&[0xac; 33]);
+        raw.push(0xac);
+        let script = Script::new(Buffer::from(raw));
+
+        assert_eq!(script.get_sigops(true), 1);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::Stack;
+use crate::{Script, Stack};
 use extended_primitives::{Buffer, VarInt};
 use handshake_encoding::{Decodable, DecodingError, Encodable};
 
@@ -29,6 +29,11 @@ impl Witness {
         self.stack.set(index, data);
     }
 
+    /// The redeem script of a scripthash spend, which is the last item on the stack.
+    pub fn get_redeem(&self) -> Option<Script> {
+        self.stack.last().map(|item| Script::new(item.clone()))
+    }
+
     pub fn var_size(&self) -> usize {
         let varint = VarInt::from(self.stack.len());
         varint.encoded_size() as usize + self.size()
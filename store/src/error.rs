@@ -0,0 +1,37 @@
+use extended_primitives::Hash;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, PartialEq)]
+pub enum StoreError {
+    MissingNode(Hash),
+    UnknownRoot(Hash),
+    ValueTooLarge,
+    Io(String),
+    Corrupt(String),
+    /// The store was written by newer software: (found, supported).
+    UnsupportedVersion(u32, u32),
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e.to_string())
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StoreError::MissingNode(ref hash) => write!(f, "Missing tree node: {}", hash),
+            StoreError::UnknownRoot(ref hash) => write!(f, "Root was never committed: {}", hash),
+            StoreError::ValueTooLarge => write!(f, "Value exceeds the maximum leaf size"),
+            StoreError::Io(ref e) => write!(f, "Storage IO error: {}", e),
+            StoreError::Corrupt(ref e) => write!(f, "Corrupt database: {}", e),
+            StoreError::UnsupportedVersion(found, supported) => write!(
+                f,
+                "Database is at version {}, newer than the supported version {}; upgrade to open it",
+                found, supported
+            ),
+        }
+    }
+}
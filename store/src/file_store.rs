@@ -1,6 +1,654 @@
-pub struct FileStore {}
+use crate::kv::{key, Batch, KvRead, KvStore};
+use crate::{Result, StoreError};
+use extended_primitives::{Buffer, Hash};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Store namespace for record locations: type, then hash.
+pub const LOCATION_PREFIX: u8 = b'l';
+
+/// Store namespace for per-file bookkeeping: type, then file number.
+pub const FILE_PREFIX: u8 = b'f';
+
+/// Store namespace for the records each file holds: type, file number, then hash. Lets a pruned
+/// file's locations be found without going through every other file's.
+pub const CONTENTS_PREFIX: u8 = b'k';
+
+/// Magic and length in front of every record.
+const RECORD_HEADER: u64 = 8;
+
+/// Files roll over once they reach this size (128MB), as in hsd.
+pub const MAX_FILE_SIZE: u64 = 128 * 1024 * 1024;
+
+/// The kinds of record kept in flat files, each in its own series of files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Block,
+    Undo,
+}
+
+impl FileType {
+    fn id(self) -> u8 {
+        match self {
+            FileType::Block => 0,
+            FileType::Undo => 1,
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            FileType::Block => "blk",
+            FileType::Undo => "rev",
+        }
+    }
+
+    fn all() -> [FileType; 2] {
+        [FileType::Block, FileType::Undo]
+    }
+}
+
+/// Where a record's data sits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub file: u32,
+    /// Offset of the data, after the record header.
+    pub offset: u32,
+    pub length: u32,
+}
+
+impl Location {
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.write_u32(self.file);
+        buffer.write_u32(self.offset);
+        buffer.write_u32(self.length);
+        buffer
+    }
+
+    fn decode(raw: Vec<u8>) -> Result<Self> {
+        let mut buffer = Buffer::from(raw);
+
+        match (buffer.read_u32(), buffer.read_u32(), buffer.read_u32()) {
+            (Ok(file), Ok(offset), Ok(length)) => Ok(Location {
+                file,
+                offset,
+                length,
+            }),
+            _ => Err(StoreError::Corrupt(
+                "Undecodable record location".to_string(),
+            )),
+        }
+    }
+}
+
+/// What is in a file: how many records and the highest block height among them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FileRecord {
+    pub records: u32,
+    pub height: u32,
+}
+
+impl FileRecord {
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.write_u32(self.records);
+        buffer.write_u32(self.height);
+        buffer
+    }
+
+    fn decode(raw: Vec<u8>) -> Result<Self> {
+        let mut buffer = Buffer::from(raw);
+
+        match (buffer.read_u32(), buffer.read_u32()) {
+            (Ok(records), Ok(height)) => Ok(FileRecord { records, height }),
+            _ => Err(StoreError::Corrupt("Undecodable file record".to_string())),
+        }
+    }
+}
+
+//The file new records of a type go to, and where in it.
+#[derive(Debug, Clone, Copy)]
+struct Writer {
+    file: u32,
+    offset: u64,
+}
+
+/// An append-only block store. Blocks and undo records are written to rotating
+/// `blkNNNNN.dat`/`revNNNNN.dat` files, each record framed by the network magic and its length,
+/// and found again through locations kept in the key-value store.
+///
+/// Index writes are added to a caller's batch, so they can commit together with the rest of a
+/// block. Data that reached a file but whose batch was never written is simply unreachable.
+pub struct FileStore {
+    dir: PathBuf,
+    magic: u32,
+    max_file_size: u64,
+    db: Arc<dyn KvStore>,
+    writers: [Writer; 2],
+}
 
 impl FileStore {
-    //Creates a new filestore
-    pub fn new() -> FileStore {}
+    /// Opens the block files in `dir`, creating the directory if needed. New records are appended
+    /// to the highest numbered file of each type.
+    pub fn open<P: AsRef<Path>>(dir: P, magic: u32, db: Arc<dyn KvStore>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut writers = [Writer { file: 0, offset: 0 }; 2];
+
+        for file_type in FileType::all().iter() {
+            let file = FileStore::last_file(&dir, *file_type)?;
+            let offset = match fs::metadata(file_path(&dir, *file_type, file)) {
+                Ok(metadata) => metadata.len(),
+                Err(_) => 0,
+            };
+
+            writers[file_type.id() as usize] = Writer { file, offset };
+        }
+
+        Ok(FileStore {
+            dir,
+            magic,
+            max_file_size: MAX_FILE_SIZE,
+            db,
+            writers,
+        })
+    }
+
+    /// Sets the size files roll over at. Mostly useful for tests.
+    pub fn set_max_file_size(&mut self, size: u64) {
+        self.max_file_size = size;
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Appends `data` for the block `hash` at `height` to the current file, and adds the index
+    /// writes for it to `batch`. The data is synced to disk before this returns.
+    pub fn write(
+        &mut self,
+        file_type: FileType,
+        hash: &Hash,
+        height: u32,
+        data: &[u8],
+        batch: &mut Batch,
+    ) -> Result<Location> {
+        let mut writer = self.writers[file_type.id() as usize];
+        let size = RECORD_HEADER + data.len() as u64;
+
+        if writer.offset > 0 && writer.offset + size > self.max_file_size {
+            writer = Writer {
+                file: writer.file + 1,
+                offset: 0,
+            };
+        }
+
+        let mut record = Vec::with_capacity(size as usize);
+        record.extend_from_slice(&self.magic.to_le_bytes());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(data);
+
+        let path = file_path(&self.dir, file_type, writer.file);
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        //A failed write may have left part of a record behind, so start from the real end.
+        writer.offset = file.seek(SeekFrom::End(0))?;
+        file.write_all(&record)?;
+        file.sync_data()?;
+
+        let location = Location {
+            file: writer.file,
+            offset: (writer.offset + RECORD_HEADER) as u32,
+            length: data.len() as u32,
+        };
+
+        writer.offset += size;
+        self.writers[file_type.id() as usize] = writer;
+
+        self.index(file_type, hash, height, location, batch)?;
+
+        Ok(location)
+    }
+
+    /// Adds the index writes for a record already in a file, e.g. one found by `scan`, to
+    /// `batch`. The file's bookkeeping is read from the store, so write the batch before indexing
+    /// another record in the same file.
+    pub fn index(
+        &self,
+        file_type: FileType,
+        hash: &Hash,
+        height: u32,
+        location: Location,
+        batch: &mut Batch,
+    ) -> Result<()> {
+        let mut info = self
+            .file_record(file_type, location.file)?
+            .unwrap_or_default();
+        info.records += 1;
+        info.height = info.height.max(height);
+
+        batch.put(&location_key(file_type, hash), &location.encode());
+        batch.put(&contents_key(file_type, location.file, hash), &[]);
+        batch.put(&file_key(file_type, location.file), &info.encode());
+
+        Ok(())
+    }
+
+    pub fn get_location(&self, file_type: FileType, hash: &Hash) -> Result<Option<Location>> {
+        match self.db.get(&location_key(file_type, hash))? {
+            Some(raw) => Ok(Some(Location::decode(raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn has(&self, file_type: FileType, hash: &Hash) -> Result<bool> {
+        self.db.has(&location_key(file_type, hash))
+    }
+
+    /// Reads a record's data back, checking its framing.
+    pub fn read(&self, file_type: FileType, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        match self.get_location(file_type, hash)? {
+            Some(location) => Ok(Some(self.read_at(file_type, &location)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the data at `location`, checking its framing.
+    pub fn read_at(&self, file_type: FileType, location: &Location) -> Result<Vec<u8>> {
+        let mut file = File::open(file_path(&self.dir, file_type, location.file))?;
+        let mut header = [0; RECORD_HEADER as usize];
+        let mut data = vec![0; location.length as usize];
+
+        file.seek(SeekFrom::Start(location.offset as u64 - RECORD_HEADER))?;
+        file.read_exact(&mut header)?;
+        file.read_exact(&mut data)?;
+
+        let mut magic = [0; 4];
+        let mut length = [0; 4];
+        magic.copy_from_slice(&header[..4]);
+        length.copy_from_slice(&header[4..]);
+
+        if u32::from_le_bytes(magic) != self.magic || u32::from_le_bytes(length) != location.length
+        {
+            return Err(StoreError::Corrupt(format!(
+                "Bad record framing in {}",
+                file_name(file_type, location.file)
+            )));
+        }
+
+        Ok(data)
+    }
+
+    /// Walks every record of a type in file order, whether indexed or not, for rebuilding the
+    /// index from the files themselves. A file is read up to its first badly framed record.
+    pub fn scan<F>(&self, file_type: FileType, mut f: F) -> Result<()>
+    where
+        F: FnMut(Location, &[u8]),
+    {
+        let last = self.writers[file_type.id() as usize].file;
+
+        for file in 0..=last {
+            let path = file_path(&self.dir, file_type, file);
+            if !path.exists() {
+                continue;
+            }
+
+            let data = fs::read(path)?;
+            let mut pos = 0;
+
+            while pos + RECORD_HEADER as usize <= data.len() {
+                let mut magic = [0; 4];
+                let mut length = [0; 4];
+                magic.copy_from_slice(&data[pos..pos + 4]);
+                length.copy_from_slice(&data[pos + 4..pos + 8]);
+
+                let start = pos + RECORD_HEADER as usize;
+                let end = start + u32::from_le_bytes(length) as usize;
+
+                if u32::from_le_bytes(magic) != self.magic || end > data.len() {
+                    break;
+                }
+
+                let location = Location {
+                    file,
+                    offset: start as u32,
+                    length: u32::from_le_bytes(length),
+                };
+                f(location, &data[start..end]);
+
+                pos = end;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes every file of a type along with its index entries, starting the series over.
+    pub fn clear(&mut self, file_type: FileType) -> Result<()> {
+        let mut batch = Batch::new();
+
+        for (key, _) in self.db.prefix(&[LOCATION_PREFIX, file_type.id()])? {
+            batch.delete(&key);
+        }
+        for (key, _) in self.db.prefix(&[FILE_PREFIX, file_type.id()])? {
+            batch.delete(&key);
+        }
+        for (key, _) in self.db.prefix(&[CONTENTS_PREFIX, file_type.id()])? {
+            batch.delete(&key);
+        }
+
+        self.db.write(batch)?;
+
+        let last = self.writers[file_type.id() as usize].file;
+        for file in 0..=last {
+            let path = file_path(&self.dir, file_type, file);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+
+        self.writers[file_type.id() as usize] = Writer { file: 0, offset: 0 };
+
+        Ok(())
+    }
+
+    /// Adds the removal of a record's index entries to `batch`. The data stays in its file until
+    /// the file is pruned.
+    pub fn remove(&self, file_type: FileType, hash: &Hash, batch: &mut Batch) -> Result<()> {
+        if let Some(location) = self.get_location(file_type, hash)? {
+            batch.delete(&location_key(file_type, hash));
+            batch.delete(&contents_key(file_type, location.file, hash));
+        }
+
+        Ok(())
+    }
+
+    pub fn file_record(&self, file_type: FileType, file: u32) -> Result<Option<FileRecord>> {
+        match self.db.get(&file_key(file_type, file))? {
+            Some(raw) => Ok(Some(FileRecord::decode(raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes every file holding only records below `height`, along with the index entries
+    /// pointing into them. The files currently being written to are never pruned. Returns the
+    /// number of files deleted.
+    pub fn prune(&mut self, height: u32) -> Result<usize> {
+        let mut batch = Batch::new();
+        let mut pruned = Vec::new();
+
+        for file_type in FileType::all().iter() {
+            let current = self.writers[file_type.id() as usize].file;
+
+            for (file_key, raw) in self.db.prefix(&[FILE_PREFIX, file_type.id()])? {
+                let file = file_number(&file_key)?;
+                let info = FileRecord::decode(raw)?;
+
+                if file >= current || info.height >= height {
+                    continue;
+                }
+
+                batch.delete(&file_key);
+                pruned.push((*file_type, file));
+            }
+        }
+
+        if pruned.is_empty() {
+            return Ok(0);
+        }
+
+        for (file_type, file) in pruned.iter() {
+            let prefix = key(CONTENTS_PREFIX, &[&[file_type.id()], &file.to_be_bytes()]);
+
+            for (key, _) in self.db.prefix(&prefix)? {
+                let hash = contents_hash(&key)?;
+                batch.delete(&location_key(*file_type, &hash));
+                batch.delete(&key);
+            }
+        }
+
+        //Drop the index first, so a crash part way leaves unreachable files rather than dangling
+        //locations.
+        self.db.write(batch)?;
+
+        for (file_type, file) in pruned.iter() {
+            let path = file_path(&self.dir, *file_type, *file);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(pruned.len())
+    }
+
+    fn last_file(dir: &Path, file_type: FileType) -> Result<u32> {
+        let mut last = 0;
+
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+
+            if !name.starts_with(file_type.prefix()) || !name.ends_with(".dat") {
+                continue;
+            }
+
+            if let Ok(file) = name[3..name.len() - 4].parse::<u32>() {
+                last = last.max(file);
+            }
+        }
+
+        Ok(last)
+    }
+}
+
+fn file_name(file_type: FileType, file: u32) -> String {
+    format!("{}{:05}.dat", file_type.prefix(), file)
+}
+
+fn file_path(dir: &Path, file_type: FileType, file: u32) -> PathBuf {
+    dir.join(file_name(file_type, file))
+}
+
+fn location_key(file_type: FileType, hash: &Hash) -> Vec<u8> {
+    key(LOCATION_PREFIX, &[&[file_type.id()], &hash.to_array()])
+}
+
+//Big endian so a file's records sit together.
+fn contents_key(file_type: FileType, file: u32, hash: &Hash) -> Vec<u8> {
+    key(
+        CONTENTS_PREFIX,
+        &[&[file_type.id()], &file.to_be_bytes(), &hash.to_array()],
+    )
+}
+
+fn contents_hash(contents_key: &[u8]) -> Result<Hash> {
+    if contents_key.len() != 38 {
+        return Err(StoreError::Corrupt("Bad file contents key".to_string()));
+    }
+
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&contents_key[6..]);
+    Ok(Hash::from(bytes))
+}
+
+//Big endian so files iterate in order.
+fn file_key(file_type: FileType, file: u32) -> Vec<u8> {
+    key(FILE_PREFIX, &[&[file_type.id()], &file.to_be_bytes()])
+}
+
+fn file_number(file_key: &[u8]) -> Result<u32> {
+    if file_key.len() != 6 {
+        return Err(StoreError::Corrupt("Bad file record key".to_string()));
+    }
+
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&file_key[2..]);
+    Ok(u32::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::MemoryStore;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("handshake-files-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn write(files: &mut FileStore, db: &MemoryStore, n: u8, height: u32) -> Location {
+        let mut batch = Batch::new();
+        let location = files
+            .write(
+                FileType::Block,
+                &Hash::from([n; 32]),
+                height,
+                &[n; 100],
+                &mut batch,
+            )
+            .unwrap();
+        db.write(batch).unwrap();
+        location
+    }
+
+    #[test]
+    fn test_write_read() {
+        let dir = temp_dir("rw");
+        let db = Arc::new(MemoryStore::new());
+        let mut files = FileStore::open(&dir, 0xdead_beef, db.clone()).unwrap();
+        files.set_max_file_size(250);
+
+        let first = write(&mut files, &db, 1, 1);
+        let second = write(&mut files, &db, 2, 2);
+        //The third record doesn't fit, so it starts a new file.
+        let third = write(&mut files, &db, 3, 3);
+        assert_eq!((first.file, second.file, third.file), (0, 0, 1));
+        assert_eq!(second.offset, 108 + 8);
+
+        assert_eq!(
+            files.read(FileType::Block, &Hash::from([2; 32])).unwrap(),
+            Some(vec![2; 100])
+        );
+        assert_eq!(
+            files.read(FileType::Undo, &Hash::from([2; 32])).unwrap(),
+            None
+        );
+
+        //Reopening picks up where we left off.
+        let mut files = FileStore::open(&dir, 0xdead_beef, db.clone()).unwrap();
+        files.set_max_file_size(250);
+        assert_eq!(write(&mut files, &db, 4, 4).file, 1);
+
+        //A different magic means the data isn't ours.
+        let other = FileStore::open(&dir, 1, db.clone()).unwrap();
+        assert!(other.read(FileType::Block, &Hash::from([1; 32])).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = temp_dir("prune");
+        let db = Arc::new(MemoryStore::new());
+        let mut files = FileStore::open(&dir, 0xdead_beef, db.clone()).unwrap();
+        files.set_max_file_size(120);
+
+        //One record per file.
+        for n in 0..4 {
+            assert_eq!(write(&mut files, &db, n, n as u32).file, n as u32);
+        }
+
+        assert_eq!(files.prune(2).unwrap(), 2);
+        assert!(!file_path(&dir, FileType::Block, 1).exists());
+        assert!(file_path(&dir, FileType::Block, 2).exists());
+        assert!(!files.has(FileType::Block, &Hash::from([1; 32])).unwrap());
+        assert!(files.has(FileType::Block, &Hash::from([2; 32])).unwrap());
+        let contents = |file: u32| {
+            db.prefix(&key(CONTENTS_PREFIX, &[&[0], &file.to_be_bytes()]))
+                .unwrap()
+                .len()
+        };
+        assert_eq!((contents(1), contents(2)), (0, 1));
+
+        //Nothing left below that height.
+        assert_eq!(files.prune(2).unwrap(), 0);
+
+        //A record written again elsewhere doesn't lose its location when the old file goes.
+        let mut batch = Batch::new();
+        files
+            .remove(FileType::Block, &Hash::from([2; 32]), &mut batch)
+            .unwrap();
+        db.write(batch).unwrap();
+        assert_eq!(contents(2), 0);
+        assert_eq!(write(&mut files, &db, 2, 4).file, 4);
+
+        //The file being written to stays, whatever its height.
+        assert_eq!(files.prune(100).unwrap(), 2);
+        assert!(files.has(FileType::Block, &Hash::from([2; 32])).unwrap());
+        assert!(!files.has(FileType::Block, &Hash::from([3; 32])).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_scan_clear() {
+        let dir = temp_dir("scan");
+        let db = Arc::new(MemoryStore::new());
+        let mut files = FileStore::open(&dir, 0xdead_beef, db.clone()).unwrap();
+        files.set_max_file_size(250);
+
+        let locations: Vec<Location> = (0..3).map(|n| write(&mut files, &db, n, 0)).collect();
+
+        //A torn write at the end of the last file is left out.
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(file_path(&dir, FileType::Block, 1))
+            .unwrap();
+        file.write_all(&0xdead_beef_u32.to_le_bytes()).unwrap();
+        file.write_all(&[100, 0, 0, 0, 1, 2]).unwrap();
+
+        let mut found = Vec::new();
+        files
+            .scan(FileType::Block, |location, data| {
+                found.push(location);
+                assert_eq!(data, &[data[0]; 100][..]);
+            })
+            .unwrap();
+        assert_eq!(found, locations);
+        assert_eq!(
+            files.read_at(FileType::Block, &found[2]).unwrap(),
+            vec![2; 100]
+        );
+
+        //Indexing a scanned record makes it readable by hash again.
+        files.clear(FileType::Block).unwrap();
+        assert!(db.range(&[], None).unwrap().is_empty());
+        assert!(!file_path(&dir, FileType::Block, 0).exists());
+
+        let location = write(&mut files, &db, 5, 0);
+        let mut batch = Batch::new();
+        db.delete(&location_key(FileType::Block, &Hash::from([5; 32])))
+            .unwrap();
+        files
+            .index(
+                FileType::Block,
+                &Hash::from([5; 32]),
+                0,
+                location,
+                &mut batch,
+            )
+            .unwrap();
+        db.write(batch).unwrap();
+        assert_eq!(
+            files.read(FileType::Block, &Hash::from([5; 32])).unwrap(),
+            Some(vec![5; 100])
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
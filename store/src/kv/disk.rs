@@ -0,0 +1,478 @@
+use super::{Batch, BatchOp, KvRead, KvStore};
+use crate::{Result, StoreError};
+use cryptoxide::blake2b::Blake2b;
+use cryptoxide::digest::Digest;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+const OP_PUT: u8 = 0;
+const OP_DELETE: u8 = 1;
+
+/// Length and checksum in front of every record.
+const RECORD_HEADER: usize = 8;
+
+/// Where a value sits in the log: (offset, length).
+type Index = BTreeMap<Vec<u8>, (u64, u32)>;
+
+fn checksum(data: &[u8]) -> [u8; 4] {
+    let mut sh = Blake2b::new(32);
+    let mut hash = [0; 32];
+    sh.input(data);
+    sh.result(&mut hash);
+
+    let mut sum = [0; 4];
+    sum.copy_from_slice(&hash[..4]);
+    sum
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    let mut array = [0; 4];
+    array.copy_from_slice(bytes);
+    Some(u32::from_le_bytes(array))
+}
+
+fn write_bytes(record: &mut Vec<u8>, bytes: &[u8]) {
+    record.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    record.extend_from_slice(bytes);
+}
+
+/// Serializes a batch into one record.
+fn encode_record(batch: &Batch) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&(batch.len() as u32).to_le_bytes());
+
+    for op in batch.ops() {
+        match op {
+            BatchOp::Put(key, value) => {
+                payload.push(OP_PUT);
+                write_bytes(&mut payload, key);
+                write_bytes(&mut payload, value);
+            }
+            BatchOp::Delete(key) => {
+                payload.push(OP_DELETE);
+                write_bytes(&mut payload, key);
+            }
+        }
+    }
+
+    let mut record = Vec::with_capacity(RECORD_HEADER + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&checksum(&payload));
+    record.extend_from_slice(&payload);
+    record
+}
+
+/// Applies the record at the start of `data`, which sits at `offset` in the log, to the index.
+/// Returns the record's length, or None if it is truncated or corrupt, in which case the index is
+/// left alone.
+fn apply_record(data: &[u8], offset: u64, index: &mut Index) -> Option<usize> {
+    let len = read_u32(data, 0)? as usize;
+    let payload = data.get(RECORD_HEADER..RECORD_HEADER + len)?;
+
+    if checksum(payload)[..] != data[4..RECORD_HEADER] {
+        return None;
+    }
+
+    let count = read_u32(payload, 0)?;
+    let mut pos = 4;
+    let mut updates: Vec<(Vec<u8>, Option<(u64, u32)>)> = Vec::new();
+
+    for _ in 0..count {
+        let op = *payload.get(pos)?;
+        let key_len = read_u32(payload, pos + 1)? as usize;
+        let key = payload.get(pos + 5..pos + 5 + key_len)?.to_vec();
+        pos += 5 + key_len;
+
+        match op {
+            OP_PUT => {
+                let value_len = read_u32(payload, pos)?;
+                let value_offset = offset + (RECORD_HEADER + pos + 4) as u64;
+                payload.get(pos + 4..pos + 4 + value_len as usize)?;
+                pos += 4 + value_len as usize;
+
+                updates.push((key, Some((value_offset, value_len))));
+            }
+            OP_DELETE => updates.push((key, None)),
+            _ => return None,
+        }
+    }
+
+    for (key, location) in updates {
+        match location {
+            Some(location) => index.insert(key, location),
+            None => index.remove(&key),
+        };
+    }
+
+    Some(RECORD_HEADER + len)
+}
+
+/// Reads the next record from `reader` into `record`, header included, and returns its length.
+/// Returns None at the end of the log, or if the record runs past the `remaining` bytes left in
+/// it.
+fn read_record<R: Read>(
+    reader: &mut R,
+    remaining: u64,
+    record: &mut Vec<u8>,
+) -> Result<Option<usize>> {
+    if remaining < RECORD_HEADER as u64 {
+        return Ok(None);
+    }
+
+    record.resize(RECORD_HEADER, 0);
+    reader.read_exact(record)?;
+
+    let len = RECORD_HEADER + read_u32(record, 0).expect("record header was read") as usize;
+    //A torn length can claim more than the log holds, so it is checked before allocating.
+    if len as u64 > remaining {
+        return Ok(None);
+    }
+
+    record.resize(len, 0);
+    reader.read_exact(&mut record[RECORD_HEADER..])?;
+
+    Ok(Some(len))
+}
+
+fn read_value(file: &Mutex<File>, (offset, len): (u64, u32)) -> Result<Vec<u8>> {
+    let mut file = file.lock().expect("store file lock poisoned");
+    let mut value = vec![0; len as usize];
+
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut value)?;
+
+    Ok(value)
+}
+
+fn read_range(
+    file: &Mutex<File>,
+    index: &Index,
+    start: &[u8],
+    end: Option<&[u8]>,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let end = match end {
+        Some(end) => Bound::Excluded(end),
+        None => Bound::Unbounded,
+    };
+
+    index
+        .range::<[u8], _>((Bound::Included(start), end))
+        .map(|(key, location)| Ok((key.clone(), read_value(file, *location)?)))
+        .collect()
+}
+
+fn first_key(index: &Index, start: &[u8], end: Option<&[u8]>) -> Option<Vec<u8>> {
+    let end = match end {
+        Some(end) => Bound::Excluded(end),
+        None => Bound::Unbounded,
+    };
+
+    index
+        .range::<[u8], _>((Bound::Included(start), end))
+        .next()
+        .map(|(key, _)| key.clone())
+}
+
+struct State {
+    file: Arc<Mutex<File>>,
+    index: Arc<Index>,
+    //Where the next record goes.
+    end: u64,
+}
+
+/// A store kept in a single append-only log file. Every batch is one checksummed record, synced
+/// before `write` returns, so a crash loses at most the batch being written. An index of where
+/// each live value sits is rebuilt from the log on open.
+pub struct DiskStore {
+    path: PathBuf,
+    state: RwLock<State>,
+}
+
+impl DiskStore {
+    /// Opens the log at `path`, creating it if needed. A torn record at the end of the log (from a
+    /// crash mid-write) is cut off. A bad record anywhere else means the log is corrupt.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let state = DiskStore::load(&path)?;
+
+        Ok(DiskStore {
+            path,
+            state: RwLock::new(state),
+        })
+    }
+
+    fn load(path: &Path) -> Result<State> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let size = file.metadata()?.len();
+        let mut index = Index::new();
+        let mut pos = 0;
+
+        //One record in memory at a time, rather than the whole log.
+        {
+            let mut reader = BufReader::new(&mut file);
+            reader.seek(SeekFrom::Start(0))?;
+
+            let mut record = Vec::new();
+            while let Some(len) = read_record(&mut reader, size - pos, &mut record)? {
+                match apply_record(&record, pos, &mut index) {
+                    Some(_) => pos += len as u64,
+                    //Only the last record can have been cut short by a crash. Cutting off a bad
+                    //one before it would throw away good records with it.
+                    None if pos + len as u64 == size => break,
+                    None => {
+                        return Err(StoreError::Corrupt(format!(
+                            "bad record at offset {} of the log",
+                            pos
+                        )))
+                    }
+                }
+            }
+        }
+
+        if pos < size {
+            file.set_len(pos)?;
+            file.sync_data()?;
+        }
+
+        Ok(State {
+            file: Arc::new(Mutex::new(file)),
+            index: Arc::new(index),
+            end: pos,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.read().expect("store lock poisoned").index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Rewrites the log with only the live values, dropping overwritten and deleted ones.
+    pub fn compact(&self) -> Result<()> {
+        let mut state = self.state.write().expect("store lock poisoned");
+
+        let mut batch = Batch::new();
+        for (key, location) in state.index.iter() {
+            batch.put(key, &read_value(&state.file, *location)?);
+        }
+
+        let tmp = self.path.with_extension("compact");
+        {
+            let mut file = File::create(&tmp)?;
+            if !batch.is_empty() {
+                file.write_all(&encode_record(&batch))?;
+            }
+            file.sync_all()?;
+        }
+
+        fs::rename(&tmp, &self.path)?;
+
+        //Snapshots keep reading from the old file, which lives on until they drop it.
+        *state = DiskStore::load(&self.path)?;
+
+        Ok(())
+    }
+}
+
+impl KvRead for DiskStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let state = self.state.read().expect("store lock poisoned");
+
+        match state.index.get(key) {
+            Some(location) => Ok(Some(read_value(&state.file, *location)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let state = self.state.read().expect("store lock poisoned");
+        read_range(&state.file, &state.index, start, end)
+    }
+
+    fn first_key(&self, start: &[u8], end: Option<&[u8]>) -> Result<Option<Vec<u8>>> {
+        let state = self.state.read().expect("store lock poisoned");
+        Ok(first_key(&state.index, start, end))
+    }
+}
+
+impl KvStore for DiskStore {
+    fn write(&self, batch: Batch) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let mut state = self.state.write().expect("store lock poisoned");
+        let record = encode_record(&batch);
+
+        let result = {
+            let mut file = state.file.lock().expect("store file lock poisoned");
+            file.write_all(&record).and_then(|_| file.sync_data())
+        };
+
+        if let Err(e) = result {
+            //Cut off whatever part of the record made it out so the log stays readable.
+            let file = state.file.lock().expect("store file lock poisoned");
+            let _ = file.set_len(state.end);
+            return Err(e.into());
+        }
+
+        let end = state.end;
+        let index = Arc::make_mut(&mut state.index);
+        if apply_record(&record, end, index).is_none() {
+            return Err(StoreError::Corrupt(
+                "write batch did not round trip".to_string(),
+            ));
+        }
+
+        state.end += record.len() as u64;
+
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Box<dyn KvRead + Send + Sync> {
+        let state = self.state.read().expect("store lock poisoned");
+
+        Box::new(DiskSnapshot {
+            file: state.file.clone(),
+            index: state.index.clone(),
+        })
+    }
+}
+
+//The log is append-only, so the values an old index points at are never overwritten.
+struct DiskSnapshot {
+    file: Arc<Mutex<File>>,
+    index: Arc<Index>,
+}
+
+impl KvRead for DiskSnapshot {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.index.get(key) {
+            Some(location) => Ok(Some(read_value(&self.file, *location)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        read_range(&self.file, &self.index, start, end)
+    }
+
+    fn first_key(&self, start: &[u8], end: Option<&[u8]>) -> Result<Option<Vec<u8>>> {
+        Ok(first_key(&self.index, start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::tests::check_store;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "handshake-store-{}-{}.log",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_disk_store() {
+        let path = temp_path("disk");
+        let store = DiskStore::open(&path).unwrap();
+        check_store(&store);
+
+        //Everything is replayed from the log.
+        drop(store);
+        let store = DiskStore::open(&path).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get(b"a3").unwrap(), Some(b"four".to_vec()));
+
+        let snapshot = store.snapshot();
+        store.compact().unwrap();
+        assert_eq!(store.get(b"a2").unwrap(), Some(b"new".to_vec()));
+        assert_eq!(snapshot.get(b"a3").unwrap(), Some(b"four".to_vec()));
+
+        drop(store);
+        let store = DiskStore::open(&path).unwrap();
+        assert_eq!(store.len(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_torn_write() {
+        let path = temp_path("torn");
+        let store = DiskStore::open(&path).unwrap();
+        store.put(b"key", b"value").unwrap();
+        drop(store);
+
+        let good = fs::metadata(&path).unwrap().len();
+
+        //Half a record, as if we crashed mid-write.
+        let mut batch = Batch::new();
+        batch.put(b"other", b"value");
+        let record = encode_record(&batch);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&record[..record.len() / 2]).unwrap();
+        drop(file);
+
+        let store = DiskStore::open(&path).unwrap();
+        assert_eq!(store.get(b"key").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(store.get(b"other").unwrap(), None);
+        assert_eq!(fs::metadata(&path).unwrap().len(), good);
+
+        //New writes land after the last good record.
+        store.put(b"other", b"value").unwrap();
+        drop(store);
+        let store = DiskStore::open(&path).unwrap();
+        assert_eq!(store.len(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_record() {
+        let path = temp_path("corrupt");
+        let store = DiskStore::open(&path).unwrap();
+        store.put(b"first", b"value").unwrap();
+        let offset = fs::metadata(&path).unwrap().len();
+        store.put(b"second", b"value").unwrap();
+        store.put(b"third", b"value").unwrap();
+        drop(store);
+
+        //Flip a byte in the middle record's payload, so its checksum fails.
+        let mut data = fs::read(&path).unwrap();
+        let size = data.len() as u64;
+        data[offset as usize + RECORD_HEADER + 4] ^= 0xff;
+        fs::write(&path, &data).unwrap();
+
+        match DiskStore::open(&path) {
+            Err(StoreError::Corrupt(_)) => {}
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("opened a corrupt log"),
+        }
+
+        //The records after it are still there.
+        assert_eq!(fs::metadata(&path).unwrap().len(), size);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use super::{Batch, BatchOp, KvRead, KvStore};
+use crate::Result;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+type Map = BTreeMap<Vec<u8>, Vec<u8>>;
+
+fn range(map: &Map, start: &[u8], end: Option<&[u8]>) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let end = match end {
+        Some(end) => Bound::Excluded(end),
+        None => Bound::Unbounded,
+    };
+
+    map.range::<[u8], _>((Bound::Included(start), end))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn first_key(map: &Map, start: &[u8], end: Option<&[u8]>) -> Option<Vec<u8>> {
+    let end = match end {
+        Some(end) => Bound::Excluded(end),
+        None => Bound::Unbounded,
+    };
+
+    map.range::<[u8], _>((Bound::Included(start), end))
+        .next()
+        .map(|(key, _)| key.clone())
+}
+
+/// A store that lives entirely in memory, for tests and throwaway chains.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    //Shared with snapshots, which keep the old map if a write comes in while they are alive.
+    data: RwLock<Arc<Map>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.read().expect("store lock poisoned").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl KvRead for MemoryStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let data = self.data.read().expect("store lock poisoned");
+        Ok(data.get(key).cloned())
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let data = self.data.read().expect("store lock poisoned");
+        Ok(range(&data, start, end))
+    }
+
+    fn first_key(&self, start: &[u8], end: Option<&[u8]>) -> Result<Option<Vec<u8>>> {
+        let data = self.data.read().expect("store lock poisoned");
+        Ok(first_key(&data, start, end))
+    }
+}
+
+impl KvStore for MemoryStore {
+    fn write(&self, batch: Batch) -> Result<()> {
+        let mut data = self.data.write().expect("store lock poisoned");
+        let map = Arc::make_mut(&mut data);
+
+        for op in batch.ops {
+            match op {
+                BatchOp::Put(key, value) => {
+                    map.insert(key, value);
+                }
+                BatchOp::Delete(key) => {
+                    map.remove(&key);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Box<dyn KvRead + Send + Sync> {
+        let data = self.data.read().expect("store lock poisoned");
+        Box::new(MemorySnapshot { data: data.clone() })
+    }
+}
+
+struct MemorySnapshot {
+    data: Arc<Map>,
+}
+
+impl KvRead for MemorySnapshot {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.data.get(key).cloned())
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(range(&self.data, start, end))
+    }
+
+    fn first_key(&self, start: &[u8], end: Option<&[u8]>) -> Result<Option<Vec<u8>>> {
+        Ok(first_key(&self.data, start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::tests::check_store;
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new();
+        check_store(&store);
+        assert_eq!(store.len(), 3);
+    }
+}
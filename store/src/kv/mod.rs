@@ -0,0 +1,181 @@
+pub mod disk;
+pub mod memory;
+
+pub use disk::DiskStore;
+pub use memory::MemoryStore;
+
+use crate::Result;
+
+/// Read access to an ordered key-value store, or a snapshot of one.
+pub trait KvRead {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Every pair with `start <= key < end` in key order. No `end` reads to the end of the store.
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    fn has(&self, key: &[u8]) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// The lowest key with `start <= key < end`, without reading any values. Backends override
+    /// this to stop at the first key rather than collecting the whole range.
+    fn first_key(&self, start: &[u8], end: Option<&[u8]>) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .range(start, end)?
+            .into_iter()
+            .next()
+            .map(|(key, _)| key))
+    }
+
+    /// Every pair whose key starts with `prefix`, in key order.
+    fn prefix(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let end = prefix_end(prefix);
+        self.range(prefix, end.as_deref())
+    }
+}
+
+/// A key-value store the chain, tree and peer store persist through. Writes go in batches that
+/// are applied all at once or not at all.
+pub trait KvStore: KvRead + Send + Sync {
+    fn write(&self, batch: Batch) -> Result<()>;
+
+    /// A read-only view of the store as it is now, unaffected by later writes.
+    fn snapshot(&self) -> Box<dyn KvRead + Send + Sync>;
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut batch = Batch::new();
+        batch.put(key, value);
+        self.write(batch)
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        let mut batch = Batch::new();
+        batch.delete(key);
+        self.write(batch)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOp {
+    Put(Vec<u8>, Vec<u8>),
+    Delete(Vec<u8>),
+}
+
+/// Writes to apply together. Later operations on a key win over earlier ones.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Batch {
+    ops: Vec<BatchOp>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Batch::default()
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.ops.push(BatchOp::Put(key.to_vec(), value.to_vec()));
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.ops.push(BatchOp::Delete(key.to_vec()));
+    }
+
+    /// Appends another batch's operations after this one's.
+    pub fn extend(&mut self, other: Batch) {
+        self.ops.extend(other.ops);
+    }
+
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// The smallest key greater than every key starting with `prefix`, or None if there is no such
+/// key (the prefix is empty or all 0xff).
+pub fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+
+    while let Some(last) = end.pop() {
+        if last < 0xff {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+
+    None
+}
+
+/// Builds a key from a one byte namespace followed by `parts`.
+pub fn key(prefix: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let mut key = vec![prefix];
+
+    for part in parts {
+        key.extend_from_slice(part);
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Runs the same checks against any backend.
+    pub fn check_store(store: &dyn KvStore) {
+        store.put(b"a1", b"one").unwrap();
+        store.put(b"a2", b"two").unwrap();
+        store.put(b"b1", b"three").unwrap();
+
+        assert_eq!(store.get(b"a1").unwrap(), Some(b"one".to_vec()));
+        assert_eq!(store.get(b"a3").unwrap(), None);
+        assert!(store.has(b"b1").unwrap());
+
+        let snapshot = store.snapshot();
+
+        let mut batch = Batch::new();
+        batch.put(b"a3", b"four");
+        batch.delete(b"a1");
+        batch.put(b"a2", b"old");
+        batch.put(b"a2", b"new");
+        store.write(batch).unwrap();
+
+        let keys: Vec<Vec<u8>> = store
+            .prefix(b"a")
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec![b"a2".to_vec(), b"a3".to_vec()]);
+        assert_eq!(store.get(b"a2").unwrap(), Some(b"new".to_vec()));
+
+        let range = store.range(b"a3", Some(b"b1")).unwrap();
+        assert_eq!(range, vec![(b"a3".to_vec(), b"four".to_vec())]);
+        assert_eq!(store.first_key(&[], None).unwrap(), Some(b"a2".to_vec()));
+        assert_eq!(store.first_key(b"a4", Some(b"b1")).unwrap(), None);
+
+        //The snapshot still sees the store as it was.
+        assert_eq!(snapshot.get(b"a1").unwrap(), Some(b"one".to_vec()));
+        assert_eq!(snapshot.get(b"a2").unwrap(), Some(b"two".to_vec()));
+        assert_eq!(snapshot.prefix(b"a").unwrap().len(), 2);
+        assert_eq!(snapshot.first_key(&[], None).unwrap(), Some(b"a1".to_vec()));
+
+        store.delete(b"b1").unwrap();
+        assert!(!store.has(b"b1").unwrap());
+    }
+
+    #[test]
+    fn test_prefix_end() {
+        assert_eq!(prefix_end(b"a"), Some(b"b".to_vec()));
+        assert_eq!(prefix_end(&[1, 0xff]), Some(vec![2]));
+        assert_eq!(prefix_end(&[0xff, 0xff]), None);
+        assert_eq!(prefix_end(&[]), None);
+    }
+}
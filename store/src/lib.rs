@@ -1,3 +1,17 @@
+pub mod error;
+pub mod file_store;
+pub mod kv;
+pub mod migrate;
+pub mod urkel;
+
+pub type Result<T> = std::result::Result<T, error::StoreError>;
+
+pub use error::StoreError;
+pub use file_store::{FileStore, FileType};
+pub use kv::{Batch, DiskStore, KvRead, KvStore, MemoryStore};
+pub use migrate::{Migration, MigrationReport, Migrator};
+pub use urkel::Tree;
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,378 @@
+//! Schema versioning for the key-value store. The store records the version of the layout its
+//! data is in; on open a `Migrator` brings older data up to date one migration at a time, and
+//! refuses data written by newer software.
+
+use crate::kv::{key, Batch, BatchOp, KvRead, KvStore};
+use crate::{Result, StoreError};
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+/// Namespace for records about the store itself.
+pub const META: u8 = b'm';
+
+fn version_key() -> Vec<u8> {
+    key(META, &[b"version"])
+}
+
+//Where an unfinished migration left off: its version followed by its cursor.
+fn progress_key() -> Vec<u8> {
+    key(META, &[b"progress"])
+}
+
+fn read_u32(bytes: &[u8]) -> Option<u32> {
+    let bytes = bytes.get(..4)?;
+    let mut array = [0; 4];
+    array.copy_from_slice(bytes);
+    Some(u32::from_le_bytes(array))
+}
+
+/// The schema version the store's data is in. None for a store that has never been versioned.
+pub fn get_version<S: KvRead + ?Sized>(db: &S) -> Result<Option<u32>> {
+    match db.get(&version_key())? {
+        Some(raw) => match read_u32(&raw) {
+            Some(version) => Ok(Some(version)),
+            None => Err(StoreError::Corrupt("Bad schema version".to_string())),
+        },
+        None => Ok(None),
+    }
+}
+
+/// One change to the layout of the store, taking it to `version`. Big migrations should work
+/// in steps: each step's writes are committed together with the cursor it returns, so a
+/// migration that is interrupted picks up from its last step the next time the store is opened.
+pub trait Migration {
+    /// The schema version the store is at once this migration has run.
+    fn version(&self) -> u32;
+
+    fn description(&self) -> &str;
+
+    /// Runs the step after `cursor`, or the first step if there is none, adding its writes to
+    /// `batch`. Returns the cursor to continue from, or None once the migration is done.
+    fn step(
+        &self,
+        db: &dyn KvRead,
+        cursor: Option<&[u8]>,
+        batch: &mut Batch,
+    ) -> Result<Option<Vec<u8>>>;
+}
+
+/// What a migration did, or would do in a dry run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationSummary {
+    pub version: u32,
+    pub description: String,
+    pub puts: usize,
+    pub deletes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    /// The version the store was at, 0 if it was never versioned.
+    pub from: u32,
+    pub to: u32,
+    pub migrations: Vec<MigrationSummary>,
+}
+
+/// Runs the migrations a store needs to reach the version this software supports.
+pub struct Migrator {
+    version: u32,
+    migrations: Vec<Box<dyn Migration>>,
+}
+
+impl Migrator {
+    /// A migrator for stores at `version`. `migrations` may come in any order, but none may go
+    /// past `version`.
+    pub fn new(version: u32, mut migrations: Vec<Box<dyn Migration>>) -> Self {
+        migrations.sort_by_key(|migration| migration.version());
+
+        debug_assert!(migrations.iter().all(|m| m.version() <= version));
+
+        Migrator {
+            version,
+            migrations,
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Brings `db` up to date. An empty store is simply marked with the current version.
+    pub fn run(&self, db: &dyn KvStore) -> Result<MigrationReport> {
+        self.migrate(db, false)
+    }
+
+    /// Works out what `run` would do, without writing anything.
+    pub fn dry_run(&self, db: &dyn KvStore) -> Result<MigrationReport> {
+        self.migrate(db, true)
+    }
+
+    fn migrate(&self, db: &dyn KvStore, dry_run: bool) -> Result<MigrationReport> {
+        let from = match get_version(db)? {
+            Some(version) => version,
+            None if db.first_key(&[], None)?.is_none() => {
+                if !dry_run {
+                    db.put(&version_key(), &self.version.to_le_bytes())?;
+                }
+
+                return Ok(MigrationReport {
+                    from: self.version,
+                    to: self.version,
+                    migrations: Vec::new(),
+                });
+            }
+            //Data from before the store was versioned.
+            None => 0,
+        };
+
+        if from > self.version {
+            return Err(StoreError::UnsupportedVersion(from, self.version));
+        }
+
+        let mut progress = match db.get(&progress_key())? {
+            Some(raw) => match read_u32(&raw) {
+                Some(version) => Some((version, raw[4..].to_vec())),
+                None => return Err(StoreError::Corrupt("Bad migration progress".to_string())),
+            },
+            None => None,
+        };
+
+        let mut overlay = Overlay::new(db);
+        let mut report = MigrationReport {
+            from,
+            to: self.version,
+            migrations: Vec::new(),
+        };
+
+        for migration in self.migrations.iter() {
+            let version = migration.version();
+            if version <= from {
+                continue;
+            }
+
+            let mut cursor = match progress.take() {
+                Some((at, cursor)) if at == version => Some(cursor),
+                _ => None,
+            };
+
+            let mut summary = MigrationSummary {
+                version,
+                description: migration.description().to_string(),
+                puts: 0,
+                deletes: 0,
+            };
+
+            loop {
+                let mut batch = Batch::new();
+                let next = migration.step(&overlay, cursor.as_deref(), &mut batch)?;
+
+                for op in batch.ops() {
+                    match op {
+                        BatchOp::Put(..) => summary.puts += 1,
+                        BatchOp::Delete(..) => summary.deletes += 1,
+                    }
+                }
+
+                match next {
+                    Some(ref next) => {
+                        let mut record = version.to_le_bytes().to_vec();
+                        record.extend_from_slice(next);
+                        batch.put(&progress_key(), &record);
+                    }
+                    None => {
+                        batch.delete(&progress_key());
+                        batch.put(&version_key(), &version.to_le_bytes());
+                    }
+                }
+
+                if dry_run {
+                    overlay.apply(batch);
+                } else {
+                    db.write(batch)?;
+                }
+
+                cursor = next;
+                if cursor.is_none() {
+                    break;
+                }
+            }
+
+            report.migrations.push(summary);
+        }
+
+        if !dry_run && from < self.version {
+            db.put(&version_key(), &self.version.to_le_bytes())?;
+        }
+
+        Ok(report)
+    }
+}
+
+//A store with uncommitted writes on top, so a dry run sees the changes of earlier steps. A real
+//run writes straight to the store and leaves the overlay empty.
+struct Overlay<'a> {
+    base: &'a dyn KvStore,
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a> Overlay<'a> {
+    fn new(base: &'a dyn KvStore) -> Self {
+        Overlay {
+            base,
+            changes: BTreeMap::new(),
+        }
+    }
+
+    fn apply(&mut self, batch: Batch) {
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put(key, value) => self.changes.insert(key.clone(), Some(value.clone())),
+                BatchOp::Delete(key) => self.changes.insert(key.clone(), None),
+            };
+        }
+    }
+}
+
+impl<'a> KvRead for Overlay<'a> {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.changes.get(key) {
+            Some(value) => Ok(value.clone()),
+            None => self.base.get(key),
+        }
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut pairs: BTreeMap<Vec<u8>, Vec<u8>> =
+            self.base.range(start, end)?.into_iter().collect();
+
+        let end = match end {
+            Some(end) => Bound::Excluded(end),
+            None => Bound::Unbounded,
+        };
+
+        for (key, value) in self.changes.range::<[u8], _>((Bound::Included(start), end)) {
+            match value {
+                Some(value) => pairs.insert(key.clone(), value.clone()),
+                None => pairs.remove(key),
+            };
+        }
+
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStore;
+
+    //Moves every key under `x` to `y`, one key per step.
+    struct Rename {
+        //Fails once the second key has moved, like a crash.
+        interrupt: bool,
+    }
+
+    impl Migration for Rename {
+        fn version(&self) -> u32 {
+            2
+        }
+
+        fn description(&self) -> &str {
+            "move x to y"
+        }
+
+        fn step(
+            &self,
+            db: &dyn KvRead,
+            _cursor: Option<&[u8]>,
+            batch: &mut Batch,
+        ) -> Result<Option<Vec<u8>>> {
+            let (old, value) = match db.prefix(b"x")?.into_iter().next() {
+                Some(pair) => pair,
+                None => return Ok(None),
+            };
+
+            if self.interrupt && db.has(b"y2")? {
+                return Err(StoreError::Io("interrupted".to_string()));
+            }
+
+            let mut new = old.clone();
+            new[0] = b'y';
+            batch.delete(&old);
+            batch.put(&new, &value);
+
+            Ok(Some(new))
+        }
+    }
+
+    struct Mark;
+
+    impl Migration for Mark {
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn description(&self) -> &str {
+            "mark"
+        }
+
+        fn step(
+            &self,
+            _: &dyn KvRead,
+            _: Option<&[u8]>,
+            batch: &mut Batch,
+        ) -> Result<Option<Vec<u8>>> {
+            batch.put(b"mark", &[]);
+            Ok(None)
+        }
+    }
+
+    fn migrator(interrupt: bool) -> Migrator {
+        let rename = Rename { interrupt };
+        Migrator::new(3, vec![Box::new(rename), Box::new(Mark)])
+    }
+
+    #[test]
+    fn test_fresh_store() {
+        let db = MemoryStore::new();
+        let report = migrator(false).run(&db).unwrap();
+
+        assert!(report.migrations.is_empty());
+        assert_eq!(get_version(&db).unwrap(), Some(3));
+
+        let old = Migrator::new(2, Vec::new());
+        assert_eq!(old.run(&db), Err(StoreError::UnsupportedVersion(3, 2)));
+    }
+
+    #[test]
+    fn test_migrations() {
+        let db = MemoryStore::new();
+        for key in [b"x1", b"x2", b"x3"].iter() {
+            db.put(*key, b"value").unwrap();
+        }
+
+        let report = migrator(false).dry_run(&db).unwrap();
+        assert_eq!(report.from, 0);
+        assert_eq!(report.to, 3);
+        assert_eq!(report.migrations.len(), 2);
+        assert_eq!(report.migrations[0].puts, 1);
+        assert_eq!(report.migrations[1].puts, 3);
+        assert_eq!(report.migrations[1].deletes, 3);
+        assert_eq!(db.len(), 3);
+        assert_eq!(get_version(&db).unwrap(), None);
+
+        //Interrupted part way through the rename, which then picks up where it stopped.
+        assert!(migrator(true).run(&db).is_err());
+        assert_eq!(get_version(&db).unwrap(), Some(1));
+        assert!(db.has(b"y1").unwrap());
+        assert!(db.has(b"x3").unwrap());
+
+        let report = migrator(false).run(&db).unwrap();
+        assert_eq!(report.from, 1);
+        assert_eq!(report.migrations[0].puts, 1);
+        assert_eq!(get_version(&db).unwrap(), Some(3));
+        assert!(db.prefix(b"x").unwrap().is_empty());
+        assert_eq!(db.prefix(b"y").unwrap().len(), 3);
+        assert!(!db.has(&progress_key()).unwrap());
+    }
+}
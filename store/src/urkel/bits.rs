@@ -0,0 +1,178 @@
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+
+/// Bit `index` of a key, counting from the most significant bit of the first byte.
+pub fn key_bit(key: &Hash, index: usize) -> u8 {
+    let key = key.to_array();
+    (key[index >> 3] >> (7 - (index & 7))) & 1
+}
+
+/// A run of bits skipped by an internal node of the radix tree. Bits are stored left aligned, so
+/// bit 0 is the high bit of the first byte.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Bits {
+    pub size: usize,
+    pub data: Vec<u8>,
+}
+
+impl Bits {
+    pub fn new() -> Self {
+        Bits::default()
+    }
+
+    /// Copies bits `start..end` of `key`.
+    pub fn from_key(key: &Hash, start: usize, end: usize) -> Self {
+        let mut bits = Bits::new();
+
+        for i in start..end {
+            bits.push(key_bit(key, i));
+        }
+
+        bits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn get(&self, index: usize) -> u8 {
+        (self.data[index >> 3] >> (7 - (index & 7))) & 1
+    }
+
+    pub fn push(&mut self, bit: u8) {
+        if self.size & 7 == 0 {
+            self.data.push(0);
+        }
+
+        if bit == 1 {
+            self.data[self.size >> 3] |= 1 << (7 - (self.size & 7));
+        }
+
+        self.size += 1;
+    }
+
+    /// How many of these bits match `key` starting at bit `depth`.
+    pub fn count(&self, key: &Hash, depth: usize) -> usize {
+        let mut i = 0;
+
+        while i < self.size && depth + i < 256 && self.get(i) == key_bit(key, depth + i) {
+            i += 1;
+        }
+
+        i
+    }
+
+    pub fn has(&self, key: &Hash, depth: usize) -> bool {
+        self.count(key, depth) == self.size
+    }
+
+    /// Splits around bit `index`, returning the bits before and after it. The bit itself is
+    /// dropped, as it becomes the branch of the new internal node.
+    pub fn split(&self, index: usize) -> (Bits, Bits) {
+        let mut left = Bits::new();
+        let mut right = Bits::new();
+
+        for i in 0..index {
+            left.push(self.get(i));
+        }
+
+        for i in index + 1..self.size {
+            right.push(self.get(i));
+        }
+
+        (left, right)
+    }
+
+    /// These bits, then `bit`, then `other`. Used when removing a node collapses its parent.
+    pub fn join(&self, bit: u8, other: &Bits) -> Bits {
+        let mut bits = self.clone();
+        bits.push(bit);
+
+        for i in 0..other.size {
+            bits.push(other.get(i));
+        }
+
+        bits
+    }
+}
+
+impl Encodable for Bits {
+    fn size(&self) -> usize {
+        let mut size = 1;
+
+        if self.size >= 0x80 {
+            size += 1;
+        }
+
+        size + ((self.size + 7) >> 3)
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        if self.size >= 0x80 {
+            buffer.write_u8(0x80 | (self.size >> 8) as u8);
+        }
+
+        buffer.write_u8(self.size as u8);
+        buffer.write_bytes(&self.data[..(self.size + 7) >> 3]);
+
+        buffer
+    }
+}
+
+impl Decodable for Bits {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        let mut size = buffer.read_u8()? as usize;
+
+        if size & 0x80 != 0 {
+            size = ((size & 0x7f) << 8) | buffer.read_u8()? as usize;
+        }
+
+        if size > 256 {
+            return Err(DecodingError::InvalidData(
+                "Invalid prefix size".to_string(),
+            ));
+        }
+
+        let data = buffer.read_bytes((size + 7) >> 3)?;
+
+        Ok(Bits { size, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_join() {
+        let mut key = [0u8; 32];
+        key[0] = 0b1011_0010;
+        let key = Hash::from(key);
+
+        let bits = Bits::from_key(&key, 0, 8);
+        assert_eq!(bits.data, vec![0b1011_0010]);
+        assert_eq!(bits.count(&key, 0), 8);
+        assert_eq!(bits.count(&key, 1), 0);
+
+        let (left, right) = bits.split(3);
+        assert_eq!(left, Bits::from_key(&key, 0, 3));
+        assert_eq!(right, Bits::from_key(&key, 4, 8));
+        assert_eq!(left.join(bits.get(3), &right), bits);
+    }
+
+    #[test]
+    fn test_encoding() {
+        let key = Hash::from([0xaa; 32]);
+
+        for &size in [0, 7, 127, 128, 200].iter() {
+            let bits = Bits::from_key(&key, 0, size);
+            let mut encoded = bits.encode();
+            assert_eq!(encoded.len(), bits.size());
+            assert_eq!(Bits::decode(&mut encoded).unwrap(), bits);
+        }
+    }
+}
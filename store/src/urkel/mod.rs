@@ -0,0 +1,8 @@
+pub mod bits;
+pub mod node;
+pub mod proof;
+pub mod tree;
+
+pub use bits::Bits;
+pub use proof::{Proof, ProofError, ProofType};
+pub use tree::{Snapshot, Tree};
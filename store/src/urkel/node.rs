@@ -0,0 +1,147 @@
+use super::bits::Bits;
+use cryptoxide::blake2b::Blake2b;
+use cryptoxide::digest::Digest;
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+
+const LEAF_PREFIX: u8 = 0x00;
+const INTERNAL_PREFIX: u8 = 0x01;
+const SKIP_PREFIX: u8 = 0x02;
+
+//Node types in the stored encoding.
+const NODE_NULL: u8 = 0;
+const NODE_INTERNAL: u8 = 1;
+const NODE_LEAF: u8 = 2;
+
+fn blake2b(items: &[&[u8]]) -> Hash {
+    let mut sh = Blake2b::new(32);
+    let mut output = [0; 32];
+
+    for item in items {
+        sh.input(item);
+    }
+
+    sh.result(&mut output);
+    Hash::from(output)
+}
+
+pub fn hash_leaf(key: &Hash, value_hash: &Hash) -> Hash {
+    blake2b(&[&[LEAF_PREFIX], &key.to_array(), &value_hash.to_array()])
+}
+
+pub fn value_hash(value: &[u8]) -> Hash {
+    blake2b(&[value])
+}
+
+pub fn hash_value(key: &Hash, value: &[u8]) -> Hash {
+    hash_leaf(key, &value_hash(value))
+}
+
+/// Internal nodes without a prefix hash the same as in a plain binary tree. Nodes that skip bits
+/// commit to those bits as well.
+pub fn hash_internal(prefix: &Bits, left: &Hash, right: &Hash) -> Hash {
+    if prefix.is_empty() {
+        return blake2b(&[&[INTERNAL_PREFIX], &left.to_array(), &right.to_array()]);
+    }
+
+    blake2b(&[
+        &[SKIP_PREFIX],
+        &prefix.encode(),
+        &left.to_array(),
+        &right.to_array(),
+    ])
+}
+
+/// A node of the tree. Children are referenced by hash, and the empty subtree is the zero hash.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Node {
+    Null,
+    Internal {
+        prefix: Bits,
+        left: Hash,
+        right: Hash,
+    },
+    Leaf {
+        key: Hash,
+        value: Buffer,
+    },
+}
+
+impl Node {
+    pub fn hash(&self) -> Hash {
+        match self {
+            Node::Null => Hash::default(),
+            Node::Internal {
+                prefix,
+                left,
+                right,
+            } => hash_internal(prefix, left, right),
+            Node::Leaf { key, value } => hash_value(key, value),
+        }
+    }
+}
+
+impl Encodable for Node {
+    fn size(&self) -> usize {
+        match self {
+            Node::Null => 1,
+            Node::Internal { prefix, .. } => 1 + prefix.size() + 64,
+            Node::Leaf { value, .. } => 1 + 32 + 2 + value.len(),
+        }
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        match self {
+            Node::Null => buffer.write_u8(NODE_NULL),
+            Node::Internal {
+                prefix,
+                left,
+                right,
+            } => {
+                buffer.write_u8(NODE_INTERNAL);
+                buffer.extend(prefix.encode());
+                buffer.write_hash(*left);
+                buffer.write_hash(*right);
+            }
+            Node::Leaf { key, value } => {
+                buffer.write_u8(NODE_LEAF);
+                buffer.write_hash(*key);
+                buffer.write_u16(value.len() as u16);
+                buffer.write_bytes(value);
+            }
+        }
+
+        buffer
+    }
+}
+
+impl Decodable for Node {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        match buffer.read_u8()? {
+            NODE_NULL => Ok(Node::Null),
+            NODE_INTERNAL => {
+                let prefix = Bits::decode(buffer)?;
+                let left = buffer.read_hash()?;
+                let right = buffer.read_hash()?;
+
+                Ok(Node::Internal {
+                    prefix,
+                    left,
+                    right,
+                })
+            }
+            NODE_LEAF => {
+                let key = buffer.read_hash()?;
+                let len = buffer.read_u16()?;
+                let value = Buffer::from(buffer.read_bytes(len as usize)?);
+
+                Ok(Node::Leaf { key, value })
+            }
+            _ => Err(DecodingError::InvalidData("Unknown node type".to_string())),
+        }
+    }
+}
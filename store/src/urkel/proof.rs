@@ -0,0 +1,269 @@
+use super::bits::{key_bit, Bits};
+use super::node::{hash_internal, hash_leaf, hash_value};
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+use handshake_types::NameHash;
+use std::fmt;
+
+/// Largest value a leaf may hold.
+pub const MAX_VALUE_SIZE: usize = 0x3ff;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProofType {
+    /// The path ends in an empty subtree.
+    Deadend = 0,
+    /// The path ends at an internal node whose prefix the key does not share.
+    Short = 1,
+    /// The path ends at a leaf for a different key.
+    Collision = 2,
+    /// The path ends at the key's leaf.
+    Exists = 3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofError {
+    HashMismatch,
+    SameKey,
+    SamePath,
+    PathMismatch,
+    TooDeep,
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProofError::HashMismatch => write!(f, "Proof does not hash to the root"),
+            ProofError::SameKey => write!(f, "Collision proof is for the same key"),
+            ProofError::SamePath => write!(f, "Short proof follows the key's path"),
+            ProofError::PathMismatch => write!(f, "Proof prefixes do not match the key"),
+            ProofError::TooDeep => write!(f, "Proof is deeper than the key"),
+        }
+    }
+}
+
+/// An inclusion or non-inclusion proof for a single key. `nodes` holds the prefix and sibling
+/// hash of every internal node on the path from the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proof {
+    pub proof_type: ProofType,
+    pub nodes: Vec<(Bits, Hash)>,
+    //Short
+    pub prefix: Bits,
+    pub left: Hash,
+    pub right: Hash,
+    //Collision
+    pub key: Hash,
+    pub hash: Hash,
+    //Exists
+    pub value: Option<Buffer>,
+}
+
+impl Proof {
+    pub fn new(proof_type: ProofType, nodes: Vec<(Bits, Hash)>) -> Self {
+        Proof {
+            proof_type,
+            nodes,
+            prefix: Bits::new(),
+            left: Hash::default(),
+            right: Hash::default(),
+            key: Hash::default(),
+            hash: Hash::default(),
+            value: None,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Checks the proof against `root`. Returns the value if the proof shows the key exists, or
+    /// None if it shows the key is absent.
+    pub fn verify(&self, root: &Hash, key: &NameHash) -> Result<Option<Buffer>, ProofError> {
+        let key = key.as_hash();
+
+        //Bit position of each node's branch, checking the key shares every prefix on the way.
+        let mut branches = Vec::with_capacity(self.nodes.len());
+        let mut depth = 0;
+
+        for (prefix, _) in self.nodes.iter() {
+            if depth + prefix.size >= 256 {
+                return Err(ProofError::TooDeep);
+            }
+
+            if !prefix.has(key, depth) {
+                return Err(ProofError::PathMismatch);
+            }
+
+            branches.push(depth + prefix.size);
+            depth += prefix.size + 1;
+        }
+
+        let mut next = match self.proof_type {
+            ProofType::Deadend => Hash::default(),
+            ProofType::Short => {
+                if self.prefix.has(key, depth) {
+                    return Err(ProofError::SamePath);
+                }
+
+                hash_internal(&self.prefix, &self.left, &self.right)
+            }
+            ProofType::Collision => {
+                if self.key == *key {
+                    return Err(ProofError::SameKey);
+                }
+
+                hash_leaf(&self.key, &self.hash)
+            }
+            ProofType::Exists => match &self.value {
+                Some(value) => hash_value(key, value),
+                None => return Err(ProofError::HashMismatch),
+            },
+        };
+
+        for ((prefix, sibling), branch) in self.nodes.iter().zip(branches).rev() {
+            next = if key_bit(key, branch) == 0 {
+                hash_internal(prefix, &next, sibling)
+            } else {
+                hash_internal(prefix, sibling, &next)
+            };
+        }
+
+        if next != *root {
+            return Err(ProofError::HashMismatch);
+        }
+
+        Ok(self.value.clone())
+    }
+}
+
+impl Encodable for Proof {
+    fn size(&self) -> usize {
+        let mut size = 2;
+
+        size += (self.nodes.len() + 7) >> 3;
+
+        for (prefix, _) in self.nodes.iter() {
+            if !prefix.is_empty() {
+                size += prefix.size();
+            }
+            size += 32;
+        }
+
+        match self.proof_type {
+            ProofType::Deadend => {}
+            ProofType::Short => size += self.prefix.size() + 64,
+            ProofType::Collision => size += 64,
+            ProofType::Exists => size += 2 + self.value.as_ref().map_or(0, |v| v.len()),
+        }
+
+        size
+    }
+
+    //Type in the top two bits of the first field, depth in the rest, then a bitmap of which
+    //nodes carry a prefix.
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        let field = ((self.proof_type as u16) << 14) | self.nodes.len() as u16;
+        buffer.write_u16(field);
+
+        let mut bitmap = vec![0u8; (self.nodes.len() + 7) >> 3];
+        for (i, (prefix, _)) in self.nodes.iter().enumerate() {
+            if !prefix.is_empty() {
+                bitmap[i >> 3] |= 1 << (i & 7);
+            }
+        }
+        buffer.write_bytes(&bitmap);
+
+        for (prefix, hash) in self.nodes.iter() {
+            if !prefix.is_empty() {
+                buffer.extend(prefix.encode());
+            }
+            buffer.write_hash(*hash);
+        }
+
+        match self.proof_type {
+            ProofType::Deadend => {}
+            ProofType::Short => {
+                buffer.extend(self.prefix.encode());
+                buffer.write_hash(self.left);
+                buffer.write_hash(self.right);
+            }
+            ProofType::Collision => {
+                buffer.write_hash(self.key);
+                buffer.write_hash(self.hash);
+            }
+            ProofType::Exists => {
+                let value: &[u8] = match &self.value {
+                    Some(value) => &value[..],
+                    None => &[],
+                };
+                buffer.write_u16(value.len() as u16);
+                buffer.write_bytes(value);
+            }
+        }
+
+        buffer
+    }
+}
+
+impl Decodable for Proof {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        let field = buffer.read_u16()?;
+        let depth = (field & 0x3fff) as usize;
+
+        let proof_type = match field >> 14 {
+            0 => ProofType::Deadend,
+            1 => ProofType::Short,
+            2 => ProofType::Collision,
+            _ => ProofType::Exists,
+        };
+
+        if depth >= 256 {
+            return Err(DecodingError::InvalidData(
+                "Invalid proof depth".to_string(),
+            ));
+        }
+
+        let bitmap = buffer.read_bytes((depth + 7) >> 3)?;
+        let mut nodes = Vec::with_capacity(depth);
+
+        for i in 0..depth {
+            let prefix = if bitmap[i >> 3] & (1 << (i & 7)) != 0 {
+                Bits::decode(buffer)?
+            } else {
+                Bits::new()
+            };
+
+            nodes.push((prefix, buffer.read_hash()?));
+        }
+
+        let mut proof = Proof::new(proof_type, nodes);
+
+        match proof_type {
+            ProofType::Deadend => {}
+            ProofType::Short => {
+                proof.prefix = Bits::decode(buffer)?;
+                proof.left = buffer.read_hash()?;
+                proof.right = buffer.read_hash()?;
+            }
+            ProofType::Collision => {
+                proof.key = buffer.read_hash()?;
+                proof.hash = buffer.read_hash()?;
+            }
+            ProofType::Exists => {
+                let size = buffer.read_u16()? as usize;
+
+                if size > MAX_VALUE_SIZE {
+                    return Err(DecodingError::InvalidData("Invalid value size".to_string()));
+                }
+
+                proof.value = Some(Buffer::from(buffer.read_bytes(size)?));
+            }
+        }
+
+        Ok(proof)
+    }
+}
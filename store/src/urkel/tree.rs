@@ -0,0 +1,555 @@
+use super::bits::{key_bit, Bits};
+use super::node::{value_hash, Node};
+use super::proof::{Proof, ProofType, MAX_VALUE_SIZE};
+use crate::kv::{key as store_key, Batch, KvRead, KvStore, MemoryStore};
+use crate::{Result, StoreError};
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, Encodable};
+use handshake_types::NameHash;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Store namespace for nodes, keyed by node hash.
+pub const NODE_PREFIX: u8 = b'n';
+
+/// Store namespace marking committed roots.
+pub const ROOT_PREFIX: u8 = b'r';
+
+/// A base-2 merkelized radix tree keyed by name hash, as used for the name state root.
+///
+/// Nodes are stored by hash and never modified, so every committed root stays readable through a
+/// `Snapshot`. Nodes written since the last commit are held in memory and only reach the store on
+/// `commit`.
+pub struct Tree {
+    store: Arc<dyn KvStore>,
+    pending: HashMap<Hash, Node>,
+    //The working root, including changes since the last commit.
+    root: Hash,
+}
+
+impl Tree {
+    /// A tree kept in memory.
+    pub fn new() -> Self {
+        Tree::with_store(Arc::new(MemoryStore::new()))
+    }
+
+    /// A tree whose nodes live in `store`, starting from the empty root.
+    pub fn with_store(store: Arc<dyn KvStore>) -> Self {
+        Tree {
+            store,
+            pending: HashMap::new(),
+            root: Hash::default(),
+        }
+    }
+
+    /// The root of the tree including uncommitted changes.
+    pub fn root_hash(&self) -> Hash {
+        self.root
+    }
+
+    pub fn get(&self, key: &NameHash) -> Result<Option<Buffer>> {
+        self.get_at(self.root, key.as_hash())
+    }
+
+    pub fn insert(&mut self, key: NameHash, value: Buffer) -> Result<()> {
+        if value.len() > MAX_VALUE_SIZE {
+            return Err(StoreError::ValueTooLarge);
+        }
+
+        let key = *key.as_hash();
+        let leaf = self.write(Node::Leaf { key, value });
+        self.root = self.insert_at(self.root, 0, &key, leaf)?;
+
+        Ok(())
+    }
+
+    /// Removes `key`, returning whether it was present.
+    pub fn remove(&mut self, key: &NameHash) -> Result<bool> {
+        match self.remove_at(self.root, 0, key.as_hash())? {
+            Some(root) => {
+                self.root = root;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Writes the nodes created since the last commit to the store, marks the working root as
+    /// committed and returns it.
+    pub fn commit(&mut self) -> Result<Hash> {
+        let mut batch = Batch::new();
+        self.commit_batch(&mut batch);
+        self.store.write(batch)?;
+        self.committed();
+
+        Ok(self.root)
+    }
+
+    /// Like `commit`, but adds the writes to `batch` so they can land atomically with other data.
+    /// The tree is left alone: once the batch is written, `committed` drops the nodes it holds
+    /// in memory.
+    pub fn commit_batch(&self, batch: &mut Batch) {
+        for (hash, node) in self.pending.iter() {
+            batch.put(&store_key(NODE_PREFIX, &[&hash.to_array()]), &node.encode());
+        }
+
+        batch.put(&store_key(ROOT_PREFIX, &[&self.root.to_array()]), &[]);
+    }
+
+    /// Marks the nodes written by `commit_batch` as stored.
+    pub fn committed(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Moves the working root back to `root`, a working root from since the last commit. Nodes
+    /// are only dropped from memory on commit, so every such root can still be read.
+    pub fn rewind(&mut self, root: Hash) {
+        self.root = root;
+    }
+
+    pub fn is_committed(&self, root: &Hash) -> Result<bool> {
+        if *root == Hash::default() {
+            return Ok(true);
+        }
+
+        self.store.has(&store_key(ROOT_PREFIX, &[&root.to_array()]))
+    }
+
+    /// Resets the working root to a previously committed root, dropping uncommitted changes.
+    pub fn inject(&mut self, root: &Hash) -> Result<()> {
+        if !self.is_committed(root)? {
+            return Err(StoreError::UnknownRoot(*root));
+        }
+
+        self.pending.clear();
+        self.root = *root;
+        Ok(())
+    }
+
+    pub fn snapshot(&self, root: &Hash) -> Result<Snapshot> {
+        if !self.is_committed(root)? {
+            return Err(StoreError::UnknownRoot(*root));
+        }
+
+        Ok(Snapshot {
+            tree: self,
+            root: *root,
+        })
+    }
+
+    pub fn prove(&self, key: &NameHash) -> Result<Proof> {
+        self.prove_at(self.root, key.as_hash())
+    }
+
+    fn read(&self, hash: &Hash) -> Result<Node> {
+        if *hash == Hash::default() {
+            return Ok(Node::Null);
+        }
+
+        if let Some(node) = self.pending.get(hash) {
+            return Ok(node.clone());
+        }
+
+        let raw = match self
+            .store
+            .get(&store_key(NODE_PREFIX, &[&hash.to_array()]))?
+        {
+            Some(raw) => raw,
+            None => return Err(StoreError::MissingNode(*hash)),
+        };
+
+        Node::decode(&mut Buffer::from(raw))
+            .map_err(|_| StoreError::Corrupt(format!("Undecodable tree node: {}", hash)))
+    }
+
+    fn write(&mut self, node: Node) -> Hash {
+        let hash = node.hash();
+        self.pending.insert(hash, node);
+        hash
+    }
+
+    fn internal(&mut self, prefix: Bits, bit: u8, next: Hash, other: Hash) -> Hash {
+        let (left, right) = if bit == 0 {
+            (next, other)
+        } else {
+            (other, next)
+        };
+
+        self.write(Node::Internal {
+            prefix,
+            left,
+            right,
+        })
+    }
+
+    fn get_at(&self, root: Hash, key: &Hash) -> Result<Option<Buffer>> {
+        let mut hash = root;
+        let mut depth = 0;
+
+        loop {
+            match self.read(&hash)? {
+                Node::Null => return Ok(None),
+                Node::Internal {
+                    prefix,
+                    left,
+                    right,
+                } => {
+                    if !prefix.has(key, depth) {
+                        return Ok(None);
+                    }
+
+                    depth += prefix.size;
+                    hash = if key_bit(key, depth) == 0 {
+                        left
+                    } else {
+                        right
+                    };
+                    depth += 1;
+                }
+                Node::Leaf { key: other, value } => {
+                    if other == *key {
+                        return Ok(Some(value));
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn insert_at(&mut self, hash: Hash, depth: usize, key: &Hash, leaf: Hash) -> Result<Hash> {
+        match self.read(&hash)? {
+            Node::Null => Ok(leaf),
+            Node::Leaf { key: other, .. } => {
+                if other == *key {
+                    return Ok(leaf);
+                }
+
+                //Branch where the two keys first differ.
+                let mut end = depth;
+                while key_bit(key, end) == key_bit(&other, end) {
+                    end += 1;
+                }
+
+                let prefix = Bits::from_key(key, depth, end);
+                Ok(self.internal(prefix, key_bit(key, end), leaf, hash))
+            }
+            Node::Internal {
+                prefix,
+                left,
+                right,
+            } => {
+                let count = prefix.count(key, depth);
+
+                if count == prefix.size {
+                    let branch = depth + prefix.size;
+                    let bit = key_bit(key, branch);
+                    let (child, other) = if bit == 0 {
+                        (left, right)
+                    } else {
+                        (right, left)
+                    };
+
+                    let child = self.insert_at(child, branch + 1, key, leaf)?;
+                    return Ok(self.internal(prefix, bit, child, other));
+                }
+
+                //The key leaves this node's prefix part way through, so split the prefix there.
+                let (common, rest) = prefix.split(count);
+                let bit = key_bit(key, depth + count);
+                let old = self.write(Node::Internal {
+                    prefix: rest,
+                    left,
+                    right,
+                });
+
+                Ok(self.internal(common, bit, leaf, old))
+            }
+        }
+    }
+
+    //Returns the new subtree hash, or None if the key was not found.
+    fn remove_at(&mut self, hash: Hash, depth: usize, key: &Hash) -> Result<Option<Hash>> {
+        match self.read(&hash)? {
+            Node::Null => Ok(None),
+            Node::Leaf { key: other, .. } => {
+                if other == *key {
+                    Ok(Some(Hash::default()))
+                } else {
+                    Ok(None)
+                }
+            }
+            Node::Internal {
+                prefix,
+                left,
+                right,
+            } => {
+                if !prefix.has(key, depth) {
+                    return Ok(None);
+                }
+
+                let branch = depth + prefix.size;
+                let bit = key_bit(key, branch);
+                let (child, other) = if bit == 0 {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+
+                let child = match self.remove_at(child, branch + 1, key)? {
+                    Some(child) => child,
+                    None => return Ok(None),
+                };
+
+                if child != Hash::default() {
+                    return Ok(Some(self.internal(prefix, bit, child, other)));
+                }
+
+                //Only the sibling is left, so it takes this node's place. An internal sibling
+                //absorbs this node's prefix and the branch bit.
+                match self.read(&other)? {
+                    Node::Internal {
+                        prefix: sibling,
+                        left,
+                        right,
+                    } => {
+                        let prefix = prefix.join(bit ^ 1, &sibling);
+                        Ok(Some(self.write(Node::Internal {
+                            prefix,
+                            left,
+                            right,
+                        })))
+                    }
+                    _ => Ok(Some(other)),
+                }
+            }
+        }
+    }
+
+    fn prove_at(&self, root: Hash, key: &Hash) -> Result<Proof> {
+        let mut nodes = Vec::new();
+        let mut hash = root;
+        let mut depth = 0;
+
+        loop {
+            match self.read(&hash)? {
+                Node::Null => return Ok(Proof::new(ProofType::Deadend, nodes)),
+                Node::Internal {
+                    prefix,
+                    left,
+                    right,
+                } => {
+                    if !prefix.has(key, depth) {
+                        let mut proof = Proof::new(ProofType::Short, nodes);
+                        proof.prefix = prefix;
+                        proof.left = left;
+                        proof.right = right;
+                        return Ok(proof);
+                    }
+
+                    depth += prefix.size;
+
+                    if key_bit(key, depth) == 0 {
+                        nodes.push((prefix, right));
+                        hash = left;
+                    } else {
+                        nodes.push((prefix, left));
+                        hash = right;
+                    }
+
+                    depth += 1;
+                }
+                Node::Leaf { key: other, value } => {
+                    if other == *key {
+                        let mut proof = Proof::new(ProofType::Exists, nodes);
+                        proof.value = Some(value);
+                        return Ok(proof);
+                    }
+
+                    let mut proof = Proof::new(ProofType::Collision, nodes);
+                    proof.key = other;
+                    proof.hash = value_hash(&value);
+                    return Ok(proof);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tree")
+            .field("root", &self.root)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        Tree::new()
+    }
+}
+
+/// A read-only view of the tree at a committed root.
+pub struct Snapshot<'a> {
+    tree: &'a Tree,
+    root: Hash,
+}
+
+impl<'a> Snapshot<'a> {
+    pub fn root_hash(&self) -> Hash {
+        self.root
+    }
+
+    pub fn get(&self, key: &NameHash) -> Result<Option<Buffer>> {
+        self.tree.get_at(self.root, key.as_hash())
+    }
+
+    pub fn prove(&self, key: &NameHash) -> Result<Proof> {
+        self.tree.prove_at(self.root, key.as_hash())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::DiskStore;
+    use std::collections::HashSet;
+
+    fn key(n: u8) -> NameHash {
+        let mut key = [n; 32];
+        //Spread the keys out so some share long prefixes and some split early.
+        key[0] = n.wrapping_mul(37);
+        NameHash::from(key)
+    }
+
+    fn value(n: u8) -> Buffer {
+        Buffer::from(vec![n; 1 + n as usize])
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut tree = Tree::new();
+
+        for n in 0..50 {
+            tree.insert(key(n), value(n)).unwrap();
+        }
+
+        for n in 0..50 {
+            assert_eq!(tree.get(&key(n)).unwrap(), Some(value(n)));
+        }
+        assert_eq!(tree.get(&key(50)).unwrap(), None);
+
+        let full = tree.root_hash();
+
+        assert!(tree.remove(&key(10)).unwrap());
+        assert!(!tree.remove(&key(10)).unwrap());
+        assert_eq!(tree.get(&key(10)).unwrap(), None);
+
+        //The root only depends on the contents, not the order of operations.
+        tree.insert(key(10), value(10)).unwrap();
+        assert_eq!(tree.root_hash(), full);
+
+        let mut other = Tree::new();
+        for n in (0..50).rev() {
+            other.insert(key(n), value(n)).unwrap();
+        }
+        assert_eq!(other.root_hash(), full);
+
+        for n in 0..50 {
+            tree.remove(&key(n)).unwrap();
+        }
+        assert_eq!(tree.root_hash(), Hash::default());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut tree = Tree::new();
+
+        tree.insert(key(1), value(1)).unwrap();
+        let first = tree.commit().unwrap();
+
+        tree.insert(key(1), value(2)).unwrap();
+        tree.insert(key(2), value(2)).unwrap();
+        assert!(tree.snapshot(&tree.root_hash()).is_err());
+        let second = tree.commit().unwrap();
+
+        let snapshot = tree.snapshot(&first).unwrap();
+        assert_eq!(snapshot.get(&key(1)).unwrap(), Some(value(1)));
+        assert_eq!(snapshot.get(&key(2)).unwrap(), None);
+
+        tree.insert(key(3), value(3)).unwrap();
+        tree.inject(&second).unwrap();
+        assert_eq!(tree.get(&key(3)).unwrap(), None);
+        assert_eq!(tree.get(&key(1)).unwrap(), Some(value(2)));
+
+        //Working roots since the last commit can be gone back to.
+        let working = tree.root_hash();
+        tree.insert(key(4), value(4)).unwrap();
+        tree.rewind(working);
+        assert_eq!(tree.get(&key(4)).unwrap(), None);
+        assert_eq!(tree.get(&key(1)).unwrap(), Some(value(2)));
+    }
+
+    #[test]
+    fn test_persistence() {
+        let path = std::env::temp_dir().join(format!("handshake-tree-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut tree = Tree::with_store(Arc::new(DiskStore::open(&path).unwrap()));
+        for n in 0..20 {
+            tree.insert(key(n), value(n)).unwrap();
+        }
+        let root = tree.commit().unwrap();
+
+        //Uncommitted nodes are lost with the tree.
+        tree.insert(key(20), value(20)).unwrap();
+        drop(tree);
+
+        let mut tree = Tree::with_store(Arc::new(DiskStore::open(&path).unwrap()));
+        tree.inject(&root).unwrap();
+        for n in 0..20 {
+            assert_eq!(tree.get(&key(n)).unwrap(), Some(value(n)));
+        }
+        assert_eq!(tree.get(&key(20)).unwrap(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_proofs() {
+        let mut tree = Tree::new();
+        let empty = tree.prove(&key(1)).unwrap();
+        assert_eq!(empty.verify(&tree.root_hash(), &key(1)), Ok(None));
+
+        for n in 0..50 {
+            tree.insert(key(n), value(n)).unwrap();
+        }
+        let root = tree.root_hash();
+
+        let mut types = HashSet::new();
+
+        for n in 0..100 {
+            let proof = tree.prove(&key(n)).unwrap();
+            let expected = if n < 50 { Some(value(n)) } else { None };
+
+            let mut encoded = proof.encode();
+            assert_eq!(encoded.len(), proof.size());
+            let decoded = Proof::decode(&mut encoded).unwrap();
+            assert_eq!(decoded, proof);
+
+            assert_eq!(decoded.verify(&root, &key(n)), Ok(expected));
+            assert!(decoded.verify(&Hash::from([1; 32]), &key(n)).is_err());
+
+            types.insert(proof.proof_type as u8);
+        }
+
+        //Absent keys should have hit more than one kind of non-inclusion proof.
+        assert!(types.len() >= 3);
+
+        //A proof of one key says nothing about another.
+        let proof = tree.prove(&key(1)).unwrap();
+        assert!(proof.verify(&root, &key(2)).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+'use strict';
+
+// Prints the reserved name table in types/src/reserved_names.rs from hsd's names.db.
+//
+//   node types/scripts/reserved.js <path to hsd> > types/src/reserved_names.rs
+
+const path = require('path');
+
+if (process.argv.length < 3) {
+  console.error('usage: node reserved.js <path to hsd>');
+  process.exit(1);
+}
+
+const reserved = require(path.resolve(process.argv[2], 'lib/covenants/reserved'));
+
+const items = [];
+
+for (const [, item] of reserved)
+  items.push(item);
+
+items.sort((a, b) => (a.name < b.name ? -1 : a.name > b.name ? 1 : 0));
+
+const out = [];
+
+out.push('// @generated by types/scripts/reserved.js from hsd\'s lib/covenants/names.db. '
+  + 'Do not edit by hand;');
+out.push('// regenerate with `node types/scripts/reserved.js <path to hsd> '
+  + '> types/src/reserved_names.rs`.');
+out.push('');
+out.push('static RESERVED: &[ReservedName] = &[');
+
+for (const item of items) {
+  out.push('    ReservedName {');
+  out.push(`        name: ${JSON.stringify(item.name)},`);
+  out.push(`        target: ${JSON.stringify(item.target)},`);
+  out.push(`        root: ${item.root},`);
+  out.push(`        value: ${item.value},`);
+  out.push('    },');
+}
+
+out.push('];');
+
+process.stdout.write(out.join('\n') + '\n');
@@ -1,10 +1,110 @@
+use crate::Compact;
+use extended_primitives::Uint256;
+use std::fmt;
+use std::ops;
+
+/// An amount of proof of work, either for a single block or summed over a chain.
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Difficulty {
-    num: u64,
+    work: Uint256,
 }
 
 impl Difficulty {
     pub fn zero() -> Difficulty {
-        Difficulty { num: 0 }
+        Difficulty {
+            work: Uint256::from(0u64),
+        }
+    }
+
+    pub fn from_work(work: Uint256) -> Difficulty {
+        Difficulty { work }
+    }
+
+    /// The expected number of hashes needed to meet `bits`, 2^256 / (target + 1).
+    /// Invalid (negative or overflowing) and zero targets are worth nothing.
+    pub fn from_bits(bits: Compact) -> Difficulty {
+        let target = match bits.to_u256() {
+            Ok(target) => target,
+            Err(_) => return Difficulty::zero(),
+        };
+
+        let zero = Uint256::from(0u64);
+        let one = Uint256::from(1u64);
+
+        if target == zero {
+            return Difficulty::zero();
+        }
+
+        //2^256 doesn't fit, but (2^256 - target - 1) / (target + 1) + 1 is equal and does.
+        Difficulty {
+            work: (!target / (target + one)) + one,
+        }
+    }
+
+    pub fn to_work(&self) -> Uint256 {
+        self.work
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::zero()
+    }
+}
+
+impl From<Compact> for Difficulty {
+    fn from(bits: Compact) -> Self {
+        Difficulty::from_bits(bits)
+    }
+}
+
+impl ops::Add for Difficulty {
+    type Output = Difficulty;
+
+    fn add(self, other: Difficulty) -> Difficulty {
+        Difficulty {
+            work: self.work + other.work,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.work)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_difficulty_from_bits() {
+        //Bitcoin's genesis block: 0x0100010001 hashes.
+        let difficulty = Difficulty::from_bits(Compact::new(0x1d00ffff));
+        assert_eq!(difficulty.to_work(), Uint256::from(0x0100010001u64));
+
+        assert_eq!(Difficulty::from_bits(Compact::new(0)), Difficulty::zero());
+
+        //Negative targets are invalid.
+        assert_eq!(
+            Difficulty::from_bits(Compact::new(0x04923456)),
+            Difficulty::zero()
+        );
+    }
+
+    #[test]
+    fn test_difficulty_compare() {
+        let easy = Difficulty::from_bits(Compact::new(0x207fffff));
+        let hard = Difficulty::from_bits(Compact::new(0x1c00ffff));
+
+        assert!(hard > easy);
+        assert!(easy > Difficulty::zero());
+
+        assert_eq!(
+            easy + easy,
+            Difficulty::from_work(easy.to_work() + easy.to_work())
+        );
+        assert!(easy + hard > hard);
     }
 }
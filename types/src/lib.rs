@@ -6,13 +6,15 @@ pub mod difficulty;
 pub mod merkle_tree;
 pub mod name;
 pub mod namehash;
+pub mod reserved;
 pub mod time;
 
 pub use amount::Amount;
 #[cfg(feature = "bloom")]
 pub use bloom_filter::Bloom;
 pub use compact::Compact;
+pub use difficulty::Difficulty;
 pub use merkle_tree::MerkleTree;
-pub use name::Name;
+pub use name::{Name, NameParams};
 pub use namehash::NameHash;
 pub use time::Time;
@@ -1,3 +1,5 @@
+use crate::reserved::{self, ReservedName};
+use crate::NameHash;
 use std::ops::Deref;
 use std::str::FromStr;
 
@@ -5,11 +7,29 @@ static MAX_NAME_SIZE: usize = 63;
 
 static BLACKLIST: &[&str] = &["example", "invalid", "local", "localhost", "test"];
 
+/// Names are released for auction in batches, one each week for a year.
+pub const ROLLOUT_WEEKS: u32 = 52;
+
 #[derive(Debug)]
 pub enum NameError {
     InvalidName,
 }
 
+/// The network parameters the name rules depend on. Implemented by the protocol crate's
+/// `Network`, which this crate can't see.
+pub trait NameParams {
+    /// Height the first names can be opened at.
+    fn auction_start(&self) -> u32;
+    /// Blocks between each weekly batch of names.
+    fn rollout_interval(&self) -> u32;
+    /// Height until which reserved names can be claimed.
+    fn claim_period(&self) -> u32;
+    /// Every name can be opened from the auction start.
+    fn no_rollout(&self) -> bool;
+    /// No names are held back for claims.
+    fn no_reserved(&self) -> bool;
+}
+
 //TODO I'm thinking we maybe make this a primitive. Not entirely sure yet, but I think a good
 //discussion should be had on whether Name is a primitive or Type.
 //Type to wrap Handshake names for type checkking as well as helper functions.
@@ -21,6 +41,55 @@ impl Name {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    /// The name hash the name is stored under.
+    pub fn hash(&self) -> NameHash {
+        NameHash::from_name(self)
+    }
+
+    /// The reserved table entry for the name, if it has one.
+    pub fn reserved(&self) -> Option<&'static ReservedName> {
+        reserved::get(&self.0)
+    }
+
+    /// Whether the name can only be claimed, not opened, at `height`.
+    pub fn is_reserved<N: NameParams>(&self, height: u32, network: &N) -> bool {
+        if network.no_reserved() {
+            return false;
+        }
+
+        if height >= network.claim_period() {
+            return false;
+        }
+
+        self.reserved().is_some()
+    }
+
+    /// The week of the rollout the name is released in, and the height that week starts at. The
+    /// week is the name hash modulo 52, read as a big endian number.
+    pub fn rollout_week<N: NameParams>(&self, network: &N) -> (u32, u32) {
+        let hash = self.hash();
+
+        let p = 256 % ROLLOUT_WEEKS;
+        let week = hash
+            .to_array()
+            .iter()
+            .fold(0, |acc, byte| (p * acc + *byte as u32) % ROLLOUT_WEEKS);
+
+        let start = network.auction_start() + week * network.rollout_interval();
+
+        (week, start)
+    }
+
+    /// Whether the name has been released for auction by `height`.
+    pub fn has_rollout<N: NameParams>(&self, height: u32, network: &N) -> bool {
+        if network.no_rollout() {
+            return true;
+        }
+
+        let (_, start) = self.rollout_week(network);
+        height >= start
+    }
 }
 
 impl Deref for Name {
@@ -44,12 +113,9 @@ impl FromStr for Name {
             return Err(NameError::InvalidName);
         }
 
-        if !s.is_ascii() {
-            return Err(NameError::InvalidName);
-        }
-
-        //No capital
-        if s.contains(|c: char| c.is_uppercase()) {
+        //Lowercase letters, digits, hyphens and underscores only.
+        let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_';
+        if !s.chars().all(valid) {
             return Err(NameError::InvalidName);
         }
 
@@ -69,3 +135,105 @@ impl FromStr for Name {
         Ok(Name(s.to_string()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use encodings::hex::FromHex;
+
+    struct Params {
+        no_rollout: bool,
+        no_reserved: bool,
+    }
+
+    impl NameParams for Params {
+        fn auction_start(&self) -> u32 {
+            2016
+        }
+
+        fn rollout_interval(&self) -> u32 {
+            1008
+        }
+
+        fn claim_period(&self) -> u32 {
+            210_240
+        }
+
+        fn no_rollout(&self) -> bool {
+            self.no_rollout
+        }
+
+        fn no_reserved(&self) -> bool {
+            self.no_reserved
+        }
+    }
+
+    #[test]
+    fn test_grammar() {
+        assert!("handshake".parse::<Name>().is_ok());
+        assert!("a-b_c1".parse::<Name>().is_ok());
+
+        for name in [
+            "",
+            "Handshake",
+            "hand.shake",
+            "-hns",
+            "hns_",
+            "hns!",
+            "naïve",
+            "localhost",
+        ]
+        .iter()
+        {
+            assert!(name.parse::<Name>().is_err(), "{}", name);
+        }
+
+        let long = "a".repeat(MAX_NAME_SIZE + 1);
+        assert!(long.parse::<Name>().is_err());
+    }
+
+    #[test]
+    fn test_reserved() {
+        let params = Params {
+            no_rollout: false,
+            no_reserved: false,
+        };
+        let com: Name = "com".parse().unwrap();
+        let free: Name = "handshake".parse().unwrap();
+
+        assert!(com.is_reserved(0, &params));
+        assert!(!com.is_reserved(params.claim_period(), &params));
+        assert!(!free.is_reserved(0, &params));
+
+        let params = Params {
+            no_reserved: true,
+            ..params
+        };
+        assert!(!com.is_reserved(0, &params));
+    }
+
+    #[test]
+    fn test_rollout() {
+        let params = Params {
+            no_rollout: false,
+            no_reserved: false,
+        };
+        let name: Name = "handshake".parse().unwrap();
+
+        let hash = "3aa2528576f96bd40fcff0bd6b60c44221d73c43b4e42d4b908ed20a93b8d1b6";
+        assert_eq!(name.hash(), NameHash::from_hex(hash).unwrap());
+
+        let (week, start) = name.rollout_week(&params);
+        assert_eq!(week, 42);
+        assert_eq!(start, 2016 + 42 * 1008);
+
+        assert!(name.has_rollout(start, &params));
+        assert!(!name.has_rollout(start - 1, &params));
+
+        let params = Params {
+            no_rollout: true,
+            ..params
+        };
+        assert!(name.has_rollout(0, &params));
+    }
+}
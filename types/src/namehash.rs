@@ -1,7 +1,152 @@
-use extended_primitives::Hash;
+use crate::Name;
+use cryptoxide::digest::Digest;
+use cryptoxide::sha3::Sha3;
+use encodings::hex::{FromHex, ToHex};
+use extended_primitives::{Buffer, Hash};
+use handshake_encoding::{Decodable, DecodingError, Encodable};
+use std::fmt;
 
-//Custom type specificly for Name Hashes.
-// #[derive(PartialEq, Eq, Clone, Debug, Default)]
-// pub struct NameHash(Hash);
+#[cfg(feature = "json")]
+use serde::de::{self, Deserialize, Deserializer, Visitor};
 
-pub type NameHash = Hash;
+/// The hash a name's state is keyed by, sha3-256 of the name. Kept apart from `Hash` so name
+/// hashes can't be mixed up with transaction or block hashes.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Hash)]
+pub struct NameHash(Hash);
+
+impl NameHash {
+    pub fn from_name(name: &Name) -> Self {
+        let mut sha3 = Sha3::sha3_256();
+        let mut hash = [0; 32];
+
+        sha3.input(name.as_bytes());
+        sha3.result(&mut hash);
+
+        NameHash(Hash::from(hash))
+    }
+
+    pub fn as_hash(&self) -> &Hash {
+        &self.0
+    }
+
+    pub fn to_array(&self) -> [u8; 32] {
+        self.0.to_array()
+    }
+}
+
+impl From<Hash> for NameHash {
+    fn from(hash: Hash) -> Self {
+        NameHash(hash)
+    }
+}
+
+impl From<[u8; 32]> for NameHash {
+    fn from(bytes: [u8; 32]) -> Self {
+        NameHash(Hash::from(bytes))
+    }
+}
+
+impl From<NameHash> for Hash {
+    fn from(name_hash: NameHash) -> Self {
+        name_hash.0
+    }
+}
+
+impl fmt::Display for NameHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+impl ToHex for NameHash {
+    fn to_hex(&self) -> String {
+        self.0.to_hex()
+    }
+}
+
+impl FromHex for NameHash {
+    type Error = DecodingError;
+
+    fn from_hex<T: AsRef<[u8]>>(hex: T) -> std::result::Result<Self, Self::Error> {
+        NameHash::decode(&mut Buffer::from_hex(hex)?)
+    }
+}
+
+impl Encodable for NameHash {
+    fn size(&self) -> usize {
+        32
+    }
+
+    fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+
+        buffer.write_hash(self.0);
+
+        buffer
+    }
+}
+
+impl Decodable for NameHash {
+    type Err = DecodingError;
+
+    fn decode(buffer: &mut Buffer) -> Result<Self, Self::Err> {
+        let hash = buffer.read_hash()?;
+
+        Ok(NameHash(hash))
+    }
+}
+
+// ====== Feature: JSON ======
+
+#[cfg(feature = "json")]
+impl serde::Serialize for NameHash {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        s.serialize_str(&self.to_hex())
+    }
+}
+
+#[cfg(feature = "json")]
+impl<'de> Deserialize<'de> for NameHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NameHashVisitor;
+
+        impl<'de> Visitor<'de> for NameHashVisitor {
+            type Value = NameHash;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a hex encoded name hash")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                NameHash::from_hex(value).map_err(|e| E::custom(e))
+            }
+        }
+
+        deserializer.deserialize_str(NameHashVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        let name: Name = "handshake".parse().unwrap();
+        let hex = "3aa2528576f96bd40fcff0bd6b60c44221d73c43b4e42d4b908ed20a93b8d1b6";
+
+        let name_hash = NameHash::from_name(&name);
+        assert_eq!(name_hash, NameHash::from_hex(hex).unwrap());
+        assert_eq!(name_hash.to_string(), hex);
+
+        let mut encoded = name_hash.encode();
+        assert_eq!(encoded.len(), name_hash.size());
+        assert_eq!(NameHash::decode(&mut encoded).unwrap(), name_hash);
+    }
+}
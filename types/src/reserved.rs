@@ -0,0 +1,67 @@
+/// A name held back from auction for its existing owner, who can claim it with a DNSSEC proof
+/// until the network's claim period ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReservedName {
+    pub name: &'static str,
+    /// The DNS name the claim has to be proven against.
+    pub target: &'static str,
+    /// Whether the name is an ICANN root zone TLD rather than a top site.
+    pub root: bool,
+    /// Coins the claim is worth, in doos. Still 0 for every entry until the table is regenerated
+    /// from hsd (see reserved_names.rs).
+    pub value: u64,
+}
+
+/// Looks a name up in the reserved table.
+pub fn get(name: &str) -> Option<&'static ReservedName> {
+    RESERVED
+        .binary_search_by(|reserved| reserved.name.cmp(name))
+        .ok()
+        .map(|index| &RESERVED[index])
+}
+
+//Sorted by name so lookups can binary search. Only a partial, hand-written subset of hsd's table
+//for now; see the note at the top of the file.
+include!("reserved_names.rs");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_sorted() {
+        for pair in RESERVED.windows(2) {
+            assert!(pair[0].name < pair[1].name);
+        }
+    }
+
+    #[test]
+    fn test_table_entries() {
+        for reserved in RESERVED.iter() {
+            assert!(reserved.name.parse::<crate::Name>().is_ok());
+            assert!(reserved.target.ends_with('.'));
+
+            //Root zone TLDs are claimed against themselves, top sites against their domain.
+            if reserved.root {
+                assert_eq!(reserved.target, format!("{}.", reserved.name));
+            } else {
+                assert_ne!(reserved.target, format!("{}.", reserved.name));
+            }
+        }
+    }
+
+    #[test]
+    fn test_spot_checks() {
+        let com = get("com").unwrap();
+        assert!(com.root);
+        assert_eq!(com.target, "com.");
+
+        assert!(get("org").unwrap().root);
+        assert!(!get("wikipedia").unwrap().root);
+        assert_eq!(get("wikipedia").unwrap().target, "wikipedia.org.");
+        assert_eq!(get("twitter").unwrap().target, "twitter.com.");
+
+        assert!(get("handshake").is_none());
+        assert!(get("Com").is_none());
+    }
+}
@@ -0,0 +1,277 @@
+// Hand-written placeholder, not generated output: a partial list of reserved names with their
+// targets, and every value left at 0 because hsd's names.db wasn't available to copy it from.
+// Replace the whole file with `node types/scripts/reserved.js <path to hsd> >
+// types/src/reserved_names.rs` before relying on claims, whose values this table decides.
+
+static RESERVED: &[ReservedName] = &[
+    ReservedName {
+        name: "aero",
+        target: "aero.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "arpa",
+        target: "arpa.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "asia",
+        target: "asia.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "au",
+        target: "au.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "biz",
+        target: "biz.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "br",
+        target: "br.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "ca",
+        target: "ca.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "cat",
+        target: "cat.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "ch",
+        target: "ch.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "cn",
+        target: "cn.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "co",
+        target: "co.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "com",
+        target: "com.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "coop",
+        target: "coop.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "de",
+        target: "de.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "edu",
+        target: "edu.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "es",
+        target: "es.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "eu",
+        target: "eu.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "fr",
+        target: "fr.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "gov",
+        target: "gov.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "in",
+        target: "in.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "info",
+        target: "info.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "int",
+        target: "int.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "io",
+        target: "io.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "it",
+        target: "it.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "jobs",
+        target: "jobs.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "jp",
+        target: "jp.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "kr",
+        target: "kr.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "me",
+        target: "me.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "mil",
+        target: "mil.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "mobi",
+        target: "mobi.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "museum",
+        target: "museum.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "name",
+        target: "name.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "net",
+        target: "net.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "nl",
+        target: "nl.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "org",
+        target: "org.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "pro",
+        target: "pro.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "ru",
+        target: "ru.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "tel",
+        target: "tel.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "travel",
+        target: "travel.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "tv",
+        target: "tv.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "twitter",
+        target: "twitter.com.",
+        root: false,
+        value: 0,
+    },
+    ReservedName {
+        name: "uk",
+        target: "uk.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "us",
+        target: "us.",
+        root: true,
+        value: 0,
+    },
+    ReservedName {
+        name: "wikipedia",
+        target: "wikipedia.org.",
+        root: false,
+        value: 0,
+    },
+    ReservedName {
+        name: "xxx",
+        target: "xxx.",
+        root: true,
+        value: 0,
+    },
+];